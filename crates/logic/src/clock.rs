@@ -0,0 +1,91 @@
+//! Sources of the current time.
+//! Everything time-dependent asks a [`Clock`], so that a [`FakeClock`] can be swapped in for testing or offline
+//! rendering.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::{Local, NaiveDateTime};
+
+/// Somewhere to get the current time from.
+pub trait Clock: Send {
+    /// The current monotonic time, used for animations and timing how long things are displayed.
+    fn now(&self) -> Instant;
+
+    /// The current local wall-clock time.
+    fn local_now(&self) -> NaiveDateTime;
+}
+
+/// The actual system clock, in the system's local timezone.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn local_now(&self) -> NaiveDateTime {
+        Local::now().naive_local()
+    }
+}
+
+/// A clock that only changes when told to.
+/// Clones share the same time, so one can be given to [`crate::DisplayLogic`] and the other kept to control it.
+#[derive(Debug, Clone)]
+pub struct FakeClock {
+    state: Arc<Mutex<FakeClockState>>,
+}
+
+#[derive(Debug)]
+struct FakeClockState {
+    /// What [`Clock::now`] returned when the clock was created.
+    start: Instant,
+    /// What [`Clock::local_now`] returned when the clock was created.
+    local_start: NaiveDateTime,
+    /// How far the clock has been advanced since it was created.
+    elapsed: Duration,
+}
+
+impl FakeClock {
+    /// Create a clock stopped at the given local time.
+    pub fn new(local_now: NaiveDateTime) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(FakeClockState {
+                start: Instant::now(),
+                local_start: local_now,
+                elapsed: Duration::ZERO,
+            })),
+        }
+    }
+
+    /// Set the current local time. The monotonic time is unaffected.
+    pub fn set(&self, local_now: NaiveDateTime) {
+        let mut state = self.state.lock().unwrap();
+        state.local_start = local_now - state.elapsed;
+    }
+
+    /// Move the current time forwards by the given amount.
+    pub fn advance(&self, by: Duration) {
+        self.state.lock().unwrap().elapsed += by;
+    }
+
+    /// How far the clock has been advanced since it was created.
+    pub fn elapsed(&self) -> Duration {
+        self.state.lock().unwrap().elapsed
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> Instant {
+        let state = self.state.lock().unwrap();
+        state.start + state.elapsed
+    }
+
+    fn local_now(&self) -> NaiveDateTime {
+        let state = self.state.lock().unwrap();
+        state.local_start + state.elapsed
+    }
+}
@@ -0,0 +1,135 @@
+use std::{sync::mpsc, time::Duration};
+
+use serde::{Serialize, Serializer};
+
+use crate::{
+    framebuffer::Framebuffer,
+    layout::Layout,
+    overlay::Toast,
+    playlist::Playlist,
+    screens::{Priority, Screen, ScreenMessage},
+    sleep::SleepMode,
+};
+
+/// Something for [`crate::DisplayLogic`] to do, sent from another thread.
+/// Commands are handled in the order they're sent, at the start of the next frame.
+#[derive(Debug)]
+pub enum Command {
+    /// Add a screen to the rotation of the main zone, in the active playlist.
+    Add(Box<dyn Screen<Framebuffer>>),
+    /// Remove all screens with the given [`Screen::id`], in any zone.
+    Delete(String),
+    /// Remove all screens with the same id as the given screen, then add it.
+    Replace(Box<dyn Screen<Framebuffer>>),
+    /// Swap the first screen with the same id as the given screen for it, keeping its place in the rotation and
+    /// how long it has been displayed for. Any other screens with that id are removed.
+    /// If there's no screen with that id in any zone, this is the same as [`Command::Add`].
+    Upsert(Box<dyn Screen<Framebuffer>>),
+    /// Send a message to all screens with the given [`Screen::id`], in any zone. See [`Screen::handle_message`].
+    Message(String, ScreenMessage),
+    /// Like [`Command::Message`], but if no screen understood the message, the given screen is added instead.
+    MessageOrAdd(String, ScreenMessage, Box<dyn Screen<Framebuffer>>),
+    /// Show a notification over the top of everything, once any already queued have been shown.
+    /// The rotation carries on as normal underneath.
+    Notify(Toast),
+    /// Remove every screen from every zone.
+    ClearAll,
+    /// Skip to the next screen in the main zone's rotation.
+    Next,
+    /// Go back to the previous screen in the main zone's rotation.
+    Previous,
+    /// Stop automatically moving on to the next screen.
+    PauseRotation,
+    /// Start automatically moving on to the next screen again.
+    ResumeRotation,
+    /// Set the brightness of everything drawn, from 0 (off) to 255 (full).
+    SetBrightness(u8),
+    /// Change what's shown while the display is sleeping. See [`crate::DisplayLogic::set_sleep_mode`].
+    SetSleepMode(SleepMode),
+    /// Show the playlist with the given name in the main zone. It's saved as the active playlist if playlists are
+    /// being saved.
+    SwitchPlaylist(String),
+    /// Add a playlist, or replace the one with the same name. See [`crate::playlist::Playlist`].
+    DefinePlaylist(Playlist),
+    /// Remove the playlist with the given name, and all of its screens.
+    DeletePlaylist(String),
+    /// Set the last message received on an MQTT topic, for scripts to use. Every screen is sent it as a
+    /// [`ScreenMessage::Value`], including ones added later.
+    SetValue(String, String),
+    /// Split the display up differently. See [`crate::DisplayLogic::set_layout`].
+    SetLayout(Layout),
+    /// Carry out the given command in the zone with the given name, instead of the main zone or all zones.
+    /// Commands that don't affect screens work the same as if they weren't sent to a zone.
+    ToZone(String, Box<Command>),
+    /// Send the current [`DisplayState`] back on the given channel.
+    QueryState(mpsc::Sender<DisplayState>),
+    /// Send the current [`DisplayState`] on the given channel now, then again whenever it changes.
+    /// Changes to timing alone don't count.
+    Subscribe(mpsc::Sender<DisplayState>),
+}
+
+/// A summary of what [`crate::DisplayLogic`] is currently doing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct DisplayState {
+    /// All screens in each zone's rotation, starting with the one at the front of the main zone.
+    pub screens: Vec<ScreenInfo>,
+    /// The name of the [`crate::playlist::Playlist`] being shown in the main zone.
+    pub playlist: String,
+    /// Whether automatically moving to the next screen is paused.
+    pub rotation_paused: bool,
+    /// The current brightness, from 0 to 255.
+    pub brightness: u8,
+    /// Whether the display is currently sleeping.
+    pub sleeping: bool,
+    /// How many times a screen has failed to draw since starting up.
+    pub draw_failures: u64,
+    /// The screens most recently taken out of the rotation for failing to draw, oldest first.
+    pub quarantined: Vec<QuarantinedScreen>,
+}
+
+/// Information about a single screen in the rotation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ScreenInfo {
+    /// See [`Screen::id`].
+    pub id: String,
+    /// See [`Screen::kind`].
+    pub kind: String,
+    /// The name of the [`crate::layout::Zone`] it's in.
+    pub zone: String,
+    /// Where it is in its zone's rotation. The current screen is at position 0.
+    pub position: usize,
+    /// See [`Screen::priority`].
+    pub priority: Priority,
+    /// Whether the screen is within its [`Screen::schedule`].
+    pub scheduled: bool,
+    /// How long the screen is shown for each time it comes up.
+    #[serde(rename = "display_duration_ms", serialize_with = "as_millis")]
+    pub display_duration: Duration,
+    /// How long until the rotation moves on, if this is the screen currently being shown.
+    /// Left out when it's serialised, as it's out of date as soon as it's sent.
+    #[serde(skip)]
+    pub time_left: Option<Duration>,
+    /// See [`Screen::remaining_shows`].
+    pub remaining_shows: Option<u32>,
+}
+
+/// A screen that was taken out of the rotation because it failed to draw.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct QuarantinedScreen {
+    /// See [`Screen::id`].
+    pub id: String,
+    /// See [`Screen::kind`].
+    pub kind: String,
+    /// The name of the [`crate::layout::Zone`] it was in, or `sleep` if it was shown while sleeping.
+    pub zone: String,
+    /// What went wrong, ie the panic message.
+    pub reason: String,
+}
+
+/// Serialise a duration as a whole number of milliseconds.
+fn as_millis<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_millis() as u64)
+}
@@ -0,0 +1,283 @@
+//! The configuration file, which says how the display is set up and what it starts off showing.
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use log::{debug, warn};
+use serde::Deserialize;
+
+use crate::{
+    fonts::{self, BitmapFont},
+    layout::Layout,
+    schedule::Schedule,
+    screens::{ScreenSpec, ScriptScreen},
+    sleep::SleepMode,
+    transition::Transition,
+    DisplayLogic,
+};
+
+/// Where the configuration file is, unless told otherwise.
+pub const DEFAULT_CONFIG_FILE: &str = "display.toml";
+
+/// The configuration in the repository, used if there's no configuration file.
+pub const DEFAULT_CONFIG: &str = include_str!("../display.toml");
+
+/// How the display is set up, and the screens it starts off with. Read from a TOML file like:
+///
+/// ```toml
+/// layout = "main 0,0 152x32; clock 152,0 40x32"
+/// sleep_mode = "ambient"
+/// transition = "crossfade 400ms"
+/// font_dir = "/home/pi/fonts"
+/// script_dir = "/home/pi/screens"
+/// script_topics = ["sensor/outside/temperature"]
+///
+/// [[screens]]
+/// kind = "text"
+/// text = "Open night tonight!"
+/// schedule = "tue 18:00-23:00"
+/// duration = 10
+///
+/// [[screens]]
+/// kind = "clock"
+/// zone = "clock"
+/// ```
+///
+/// See [`ScreenConfig`] for what each screen can have.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// See [`DisplayLogic::set_layout`]. If not given, there's just the main zone.
+    pub layout: Option<Layout>,
+    /// See [`DisplayLogic::set_sleep_mode`].
+    pub sleep_mode: Option<SleepMode>,
+    /// See [`DisplayLogic::set_sleep_brightness`].
+    pub sleep_brightness: Option<u8>,
+    /// See [`DisplayLogic::set_default_transition`].
+    pub transition: Option<Transition>,
+    /// A directory of BDF or PCF fonts, which can be used by name like the built in ones. See [`BitmapFont`].
+    /// If it's relative, it's relative to the configuration file.
+    pub font_dir: Option<PathBuf>,
+    /// A directory of scripts, each of which is shown as a [`ScriptScreen`] in the main zone.
+    /// See [`Self::add_scripts`]. If it's relative, it's relative to the configuration file.
+    pub script_dir: Option<PathBuf>,
+    /// MQTT topics whose values scripts can use. See [`crate::Command::SetValue`].
+    #[serde(default)]
+    pub script_topics: Vec<String>,
+    /// The screens to start off with, in the order they're shown.
+    #[serde(default)]
+    pub screens: Vec<ScreenConfig>,
+}
+
+/// A screen to start off with. This is a [`ScreenSpec`], with some extra settings that work for any screen.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ScreenConfig {
+    /// The name of the zone to put it in. If not given, it goes in the main zone.
+    pub zone: Option<String>,
+    /// How many seconds to show it for each time it comes up, instead of however long it would choose itself.
+    pub duration: Option<f32>,
+    /// When it's allowed to be shown. See [`Schedule`].
+    pub schedule: Option<Schedule>,
+    #[serde(flatten)]
+    pub spec: ScreenSpec,
+}
+
+impl ScreenConfig {
+    /// A description of the screen, including the extra settings.
+    pub fn spec(&self) -> ScreenSpec {
+        let mut spec = self.spec.clone();
+        if let Some(seconds) = self.duration {
+            spec = ScreenSpec::Timed {
+                seconds,
+                screen: Box::new(spec),
+            };
+        }
+        if let Some(schedule) = self.schedule {
+            spec = ScreenSpec::Scheduled {
+                schedule,
+                screen: Box::new(spec),
+            };
+        }
+
+        spec
+    }
+}
+
+/// Returned when the configuration can't be read, or doesn't make sense.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError(String);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid config: {}", self.0)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl FromStr for Config {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let config: Config = toml::from_str(s).map_err(|e| ConfigError(e.to_string()))?;
+        config.validate()?;
+
+        Ok(config)
+    }
+}
+
+impl Config {
+    /// Read the configuration from the given file, or `None` if it doesn't exist. This also checks the scripts can be
+    /// loaded, and loads the fonts in the [`Self::font_dir`], so it should only be called once, before any screens
+    /// that might use them are added.
+    pub fn load(path: &Path) -> Result<Option<Self>, ConfigError> {
+        let toml = match fs::read_to_string(path) {
+            Ok(toml) => toml,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(ConfigError(format!(
+                    "couldn't read {}: {e}",
+                    path.display()
+                )))
+            }
+        };
+
+        let in_file = |ConfigError(e)| ConfigError(format!("in {}: {e}", path.display()));
+        let mut config: Config = toml.parse().map_err(in_file)?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        for dir in [&mut config.font_dir, &mut config.script_dir]
+            .into_iter()
+            .flatten()
+        {
+            *dir = base.join(&dir);
+        }
+        config.load_files().map_err(in_file)?;
+
+        Ok(Some(config))
+    }
+
+    /// Read the configuration from the given file, or use [`DEFAULT_CONFIG`] if it doesn't exist.
+    pub fn load_or_default(path: &Path) -> Result<Self, ConfigError> {
+        match Self::load(path)? {
+            Some(config) => Ok(config),
+            None => {
+                debug!("{} doesn't exist, using the default config", path.display());
+                DEFAULT_CONFIG.parse()
+            }
+        }
+    }
+
+    /// Check for anything that parses, but doesn't make sense.
+    fn validate(&self) -> Result<(), ConfigError> {
+        let layout = self.layout.clone().unwrap_or_default();
+        for (i, screen) in self.screens.iter().enumerate() {
+            let n = i + 1;
+            let zone = screen.zone.as_deref();
+            if let Some(zone) = zone.filter(|z| layout.position(z).is_none()) {
+                return Err(ConfigError(format!(
+                    "screen {n} is in zone {zone:?}, which isn't in the layout ({layout})"
+                )));
+            }
+            let bad_duration = |d: &f32| !(d.is_finite() && *d > 0.0);
+            if let Some(duration) = screen.duration.filter(bad_duration) {
+                return Err(ConfigError(format!(
+                    "screen {n} has duration {duration}, which should be a positive number of seconds"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the scripts can be loaded, and add the fonts to [`fonts`]. Nothing is added unless everything can be
+    /// loaded.
+    fn load_files(&self) -> Result<(), ConfigError> {
+        for (i, screen) in self.screens.iter().enumerate() {
+            if let ScreenSpec::Script { path } = &screen.spec {
+                ScriptScreen::load(path)
+                    .map_err(|e| ConfigError(format!("screen {} can't be loaded: {e}", i + 1)))?;
+            }
+        }
+        if let Some(dir) = &self.script_dir {
+            let scripts = ScriptScreen::load_dir(dir).map_err(|e| {
+                ConfigError(format!("couldn't read scripts from {}: {e}", dir.display()))
+            })?;
+            if let Some(Err(e)) = scripts.into_iter().find(Result::is_err) {
+                return Err(ConfigError(e.to_string()));
+            }
+        }
+        if let Some(dir) = &self.font_dir {
+            let loaded = BitmapFont::load_dir(dir)
+                .map_err(|e| {
+                    ConfigError(format!("couldn't read fonts from {}: {e}", dir.display()))
+                })?
+                .into_iter()
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| ConfigError(e.to_string()))?;
+            for font in loaded {
+                let name = fonts::add(font).name;
+                debug!("loaded font {name:?}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Set up the display as configured, apart from adding the screens.
+    pub fn apply(&self, logic: &mut DisplayLogic) {
+        if let Some(layout) = &self.layout {
+            logic.set_layout(layout.clone());
+        }
+        if let Some(mode) = &self.sleep_mode {
+            logic.set_sleep_mode(mode.clone());
+        }
+        if let Some(brightness) = self.sleep_brightness {
+            logic.set_sleep_brightness(brightness);
+        }
+        if let Some(transition) = self.transition {
+            logic.set_default_transition(transition);
+        }
+    }
+
+    /// Add the configured screens to the display, in order. [`Self::apply`] should be called first, so their zones
+    /// exist.
+    pub fn add_screens(&self, logic: &mut DisplayLogic) {
+        for screen in &self.screens {
+            let zone = screen.zone.as_deref();
+            let built = screen.spec().build();
+            match zone {
+                Some(zone) => {
+                    if logic.add_to_zone(zone, built).is_err() {
+                        warn!("ignoring screen for unknown zone {zone:?}");
+                    }
+                }
+                None => logic.add(built),
+            }
+        }
+    }
+
+    /// Load the scripts in the [`Self::script_dir`], if there is one, and put them in the main zone. Scripts that are
+    /// already in the rotation, ie because it was restored, are reloaded in place. Scripts that can't be loaded are
+    /// skipped.
+    pub fn add_scripts(&self, logic: &mut DisplayLogic) {
+        let Some(dir) = &self.script_dir else {
+            return;
+        };
+        let scripts = match ScriptScreen::load_dir(dir) {
+            Ok(scripts) => scripts,
+            Err(e) => {
+                warn!("couldn't read scripts from {}: {e}", dir.display());
+                return;
+            }
+        };
+        for script in scripts {
+            match script {
+                Ok(script) => logic.add_or_update(Box::new(script)),
+                Err(e) => warn!("skipping script: {e}"),
+            }
+        }
+    }
+}
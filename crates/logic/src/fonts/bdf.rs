@@ -0,0 +1,564 @@
+use std::{collections::HashMap, convert::Infallible, fmt, fs, io, path::Path};
+
+use embedded_graphics::{
+    mono_font::{MonoFont, MonoTextStyle},
+    pixelcolor::{BinaryColor, Rgb888},
+    prelude::*,
+    primitives::Rectangle,
+    text::{
+        renderer::{CharacterStyle, TextMetrics, TextRenderer},
+        Baseline,
+    },
+};
+
+mod pcf;
+
+/// The file extensions fonts are loaded with by [`BitmapFont::load_dir`].
+pub const FONT_EXTENSIONS: &[&str] = &["bdf", "pcf"];
+
+/// The file extension of the kerning pairs for a font, which are kept next to it. See [`BitmapFont::with_kerning`].
+pub const KERNING_EXTENSION: &str = "kern";
+
+/// Returned when a font file can't be loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FontError(String);
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid font: {}", self.0)
+    }
+}
+
+impl std::error::Error for FontError {}
+
+/// A bitmap font loaded from a [BDF](https://en.wikipedia.org/wiki/Glyph_Bitmap_Distribution_Format) or
+/// [PCF](https://fontforge.org/docs/techref/pcf-format.html) file, so fonts made for the panel can be tried without
+/// rebuilding.
+///
+/// Unlike the built in fonts, each glyph has its own width, and can be drawn offset from where it starts, so it can
+/// overhang its neighbours. Neither format has kerning tables, so pairs of characters that should be closer together
+/// or further apart go in a separate file. See [`Self::with_kerning`].
+///
+/// Only fonts encoded in Unicode (`ISO10646`) or `ISO8859-1` can be loaded. Built in fonts can also be made bigger
+/// with [`Self::scaled`].
+#[derive(Clone)]
+pub struct BitmapFont {
+    /// The file name, without the extension
+    name: String,
+    /// Rows above the baseline, including it, and below it
+    ascent: i32,
+    descent: i32,
+    glyphs: HashMap<char, Glyph>,
+    /// Drawn instead of characters the font doesn't have
+    default_char: Option<char>,
+    /// How much further along the second character of each pair starts, when it comes straight after the first
+    kerning: HashMap<(char, char), i32>,
+}
+
+#[derive(Debug, Clone)]
+struct Glyph {
+    /// How far along the next glyph starts
+    advance: i32,
+    /// Where the bottom left of the bitmap is, from the start of the glyph on the baseline, with y going up
+    offset: Point,
+    size: Size,
+    /// Whether each pixel is set, a row at a time from the top
+    pixels: Vec<bool>,
+}
+
+impl Glyph {
+    /// The pixels that are set, from the start of the glyph on the baseline.
+    fn points(&self) -> impl Iterator<Item = Point> + '_ {
+        let width = self.size.width as usize;
+        let bottom = self.size.height as i32 - 1;
+        self.pixels
+            .iter()
+            .enumerate()
+            .filter(|(_, set)| **set)
+            .map(move |(i, _)| {
+                let (x, y) = ((i % width) as i32, (i / width) as i32);
+                Point::new(self.offset.x + x, -self.offset.y - (bottom - y))
+            })
+    }
+}
+
+impl fmt::Debug for BitmapFont {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The glyphs are far too long to be useful
+        f.debug_struct("BitmapFont")
+            .field("name", &self.name)
+            .field("glyphs", &self.glyphs.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl BitmapFont {
+    /// Load the font from the given BDF or PCF file. It's named after the file, without the extension.
+    /// If there's a file with the same name and the [`KERNING_EXTENSION`] next to it, the kerning pairs are loaded too.
+    pub fn load(path: &Path) -> Result<Self, FontError> {
+        let in_file =
+            |path: &Path, e: &dyn fmt::Display| FontError(format!("{}: {e}", path.display()));
+        let bytes = fs::read(path).map_err(|e| in_file(path, &e))?;
+        let name = path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+
+        let font = if bytes.starts_with(pcf::MAGIC) {
+            Self::parse_pcf(name, &bytes)
+        } else {
+            let bdf = String::from_utf8(bytes).map_err(|e| in_file(path, &e))?;
+            Self::parse(name, &bdf)
+        };
+        let font = font.map_err(|FontError(e)| in_file(path, &e))?;
+
+        let kerning_path = path.with_extension(KERNING_EXTENSION);
+        match fs::read_to_string(&kerning_path) {
+            Ok(kerning) => font
+                .with_kerning(&kerning)
+                .map_err(|FontError(e)| in_file(&kerning_path, &e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(font),
+            Err(e) => Err(in_file(&kerning_path, &e)),
+        }
+    }
+
+    /// Load every font in the given directory, in order of their file names.
+    /// Each one that can't be loaded is an error in the list, rather than stopping the rest being loaded.
+    pub fn load_dir(dir: &Path) -> io::Result<Vec<Result<Self, FontError>>> {
+        let mut paths = fs::read_dir(dir)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        paths.retain(|p| {
+            p.extension()
+                .is_some_and(|e| FONT_EXTENSIONS.iter().any(|f| e == *f))
+        });
+        paths.sort();
+
+        Ok(paths.iter().map(|p| Self::load(p)).collect())
+    }
+
+    /// Parse the contents of a BDF file, giving the font the given name.
+    pub fn parse(name: impl Into<String>, bdf: &str) -> Result<Self, FontError> {
+        let mut lines = bdf
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty());
+        if !lines
+            .next()
+            .is_some_and(|(_, l)| l.starts_with("STARTFONT"))
+        {
+            return Err(FontError("not a BDF file".to_string()));
+        }
+
+        let mut bounding_box = None;
+        let mut advance = None;
+        let mut properties = HashMap::new();
+        let mut glyphs = HashMap::new();
+        while let Some((n, line)) = lines.next() {
+            let (keyword, args) = split(line);
+            match keyword {
+                "FONTBOUNDINGBOX" => bounding_box = Some(numbers::<4>(n, args)?),
+                "DWIDTH" => advance = Some(numbers::<2>(n, args)?[0]),
+                "STARTPROPERTIES" => {
+                    for (_, line) in lines.by_ref() {
+                        if line == "ENDPROPERTIES" {
+                            break;
+                        }
+                        let (key, value) = split(line);
+                        properties.insert(key, value.trim_matches('"'));
+                    }
+                }
+                "STARTCHAR" => {
+                    let bounding_box = bounding_box.ok_or_else(|| {
+                        FontError(format!("line {n}: character before FONTBOUNDINGBOX"))
+                    })?;
+                    if let Some((c, glyph)) = parse_glyph(&mut lines, bounding_box, advance)? {
+                        glyphs.insert(c, glyph);
+                    }
+                }
+                "ENDFONT" => break,
+                // Nothing else is needed to draw it
+                _ => {}
+            }
+        }
+
+        check_charset(
+            properties.get("CHARSET_REGISTRY").copied(),
+            properties.get("CHARSET_ENCODING").copied(),
+        )?;
+        if glyphs.is_empty() {
+            return Err(FontError("no characters".to_string()));
+        }
+
+        let [_, height, _, bottom] = bounding_box.unwrap_or_default();
+        let property = |name| properties.get(name).and_then(|p| p.parse::<i32>().ok());
+        let default_char = default_char(property("DEFAULT_CHAR"), &glyphs);
+
+        Ok(Self {
+            name: name.into(),
+            ascent: property("FONT_ASCENT").unwrap_or(height + bottom),
+            descent: property("FONT_DESCENT").unwrap_or(-bottom),
+            glyphs,
+            default_char,
+            kerning: HashMap::new(),
+        })
+    }
+
+    /// Add kerning pairs, from a file with a line for each pair of characters that should be closer together or
+    /// further apart, like `AV -1`. The number is how much further along the second character starts when it comes
+    /// straight after the first. Blank lines, and lines starting with `#`, are ignored.
+    pub fn with_kerning(mut self, kerning: &str) -> Result<Self, FontError> {
+        let lines = kerning
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+        for (n, line) in lines {
+            let (pair, adjustment) = split(line);
+            let mut chars = pair.chars();
+            let (Some(first), Some(second), None) = (chars.next(), chars.next(), chars.next())
+            else {
+                return Err(FontError(format!(
+                    "line {n}: expected a pair of characters, not {pair:?}"
+                )));
+            };
+            let [adjustment] = numbers::<1>(n, adjustment)?;
+            self.kerning.insert((first, second), adjustment);
+        }
+
+        Ok(self)
+    }
+
+    /// A built in font with each pixel stretched to the given size, for text bigger than any of them. Only the given
+    /// characters that the font has are included.
+    pub fn scaled(
+        name: impl Into<String>,
+        font: &MonoFont<'_>,
+        scale: Size,
+        chars: impl IntoIterator<Item = char>,
+    ) -> Self {
+        let cell = font.character_size;
+        let style = MonoTextStyle::new(font, BinaryColor::On);
+        let (rows_above, rows_below) = (font.baseline, cell.height - font.baseline);
+        let missing = font.glyph_mapping.index('?');
+
+        let glyphs = chars
+            .into_iter()
+            .filter(|c| *c == '?' || font.glyph_mapping.index(*c) != missing)
+            .map(|c| {
+                let mut grid = Grid::new(cell);
+                let Ok(_) = style.draw_string(
+                    c.encode_utf8(&mut [0; 4]),
+                    Point::zero(),
+                    Baseline::Top,
+                    &mut grid,
+                );
+                let size = cell.component_mul(scale);
+                let pixels = (0..size.height)
+                    .flat_map(|y| (0..size.width).map(move |x| (x, y)))
+                    .map(|(x, y)| {
+                        grid.pixels[((y / scale.height) * cell.width + x / scale.width) as usize]
+                    })
+                    .collect();
+                let glyph = Glyph {
+                    advance: ((cell.width + font.character_spacing) * scale.width) as i32,
+                    // Down from the baseline to the bottom of the cell
+                    offset: Point::new(0, 1 - (rows_below * scale.height) as i32),
+                    size,
+                    pixels,
+                };
+                (c, glyph)
+            })
+            .collect::<HashMap<_, _>>();
+
+        Self {
+            name: name.into(),
+            ascent: (rows_above * scale.height) as i32 + 1,
+            descent: (rows_below * scale.height) as i32 - 1,
+            default_char: Some('?').filter(|c| glyphs.contains_key(c)),
+            glyphs,
+            kerning: HashMap::new(),
+        }
+    }
+
+    /// The file name it was loaded from, without the extension.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The height of each line, and the width of the widest character.
+    pub fn size(&self) -> Size {
+        let width = self.glyphs.values().map(|g| g.advance).max();
+        Size::new(width.unwrap_or_default().max(0) as u32, self.line_height())
+    }
+
+    /// Whether the font has a glyph for every character in the text. Control characters, like newlines, are ignored.
+    pub fn supports(&self, text: &str) -> bool {
+        text.chars()
+            .all(|c| c.is_control() || self.glyphs.contains_key(&c))
+    }
+
+    /// How far the text takes up along the line.
+    pub fn width(&self, text: &str) -> i32 {
+        self.layout(text)
+            .map(|(x, glyph)| x + glyph.advance)
+            .last()
+            .unwrap_or_default()
+    }
+
+    fn line_height(&self) -> u32 {
+        (self.ascent + self.descent).max(0) as u32
+    }
+
+    /// The glyph to draw for the given character, if any.
+    fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs
+            .get(&c)
+            .or_else(|| self.glyphs.get(&self.default_char?))
+    }
+
+    /// Each glyph to draw for the text, and how far along the line it starts.
+    fn layout<'a>(&'a self, text: &'a str) -> impl Iterator<Item = (i32, &'a Glyph)> + 'a {
+        let mut x = 0;
+        let mut previous = None;
+        text.chars().filter_map(move |c| {
+            let glyph = self.glyph(c)?;
+            if let Some(adjustment) = previous.and_then(|p| self.kerning.get(&(p, c))) {
+                x += adjustment;
+            }
+            let start = x;
+            x += glyph.advance;
+            previous = Some(c);
+            Some((start, glyph))
+        })
+    }
+}
+
+/// Check the font's in a charset we can load, from its `CHARSET_REGISTRY` and `CHARSET_ENCODING` properties.
+fn check_charset(registry: Option<&str>, encoding: Option<&str>) -> Result<(), FontError> {
+    let registry = registry.map(str::to_uppercase);
+    match (registry.as_deref(), encoding) {
+        (None | Some("ISO10646"), _) | (Some("ISO8859"), Some("1")) => Ok(()),
+        (Some(registry), encoding) => Err(FontError(format!(
+            "{registry}-{} fonts aren't supported, only ISO10646 and ISO8859-1",
+            encoding.unwrap_or("?")
+        ))),
+    }
+}
+
+/// The character to draw instead of ones the font doesn't have: the one with the given code if there is one, or
+/// otherwise a question mark, as long as the font has it.
+fn default_char(code: Option<i32>, glyphs: &HashMap<char, Glyph>) -> Option<char> {
+    code.and_then(|c| char::from_u32(c as u32))
+        .or(Some('?'))
+        .filter(|c| glyphs.contains_key(c))
+}
+
+/// Which pixels of a single character are set, when it's drawn by [`BitmapFont::scaled`].
+struct Grid {
+    size: Size,
+    pixels: Vec<bool>,
+}
+
+impl Grid {
+    fn new(size: Size) -> Self {
+        Self {
+            size,
+            pixels: vec![false; (size.width * size.height) as usize],
+        }
+    }
+}
+
+impl OriginDimensions for Grid {
+    fn size(&self) -> Size {
+        self.size
+    }
+}
+
+impl DrawTarget for Grid {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I: IntoIterator<Item = Pixel<BinaryColor>>>(
+        &mut self,
+        pixels: I,
+    ) -> Result<(), Infallible> {
+        for Pixel(point, colour) in pixels {
+            if self.bounding_box().contains(point) {
+                let i = point.y as u32 * self.size.width + point.x as u32;
+                self.pixels[i as usize] = colour.is_on();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Parse a character, up to and including its `ENDCHAR`. Characters that aren't in Unicode are skipped.
+fn parse_glyph<'a>(
+    lines: &mut impl Iterator<Item = (usize, &'a str)>,
+    bounding_box: [i32; 4],
+    advance: Option<i32>,
+) -> Result<Option<(char, Glyph)>, FontError> {
+    let mut encoding = None;
+    let mut advance = advance;
+    let mut bounding_box = bounding_box;
+    while let Some((n, line)) = lines.next() {
+        let (keyword, args) = split(line);
+        match keyword {
+            // The second number is only used when the first is -1, for characters in some other encoding
+            "ENCODING" => {
+                encoding = Some(numbers::<1>(n, args.split(' ').next().unwrap_or(""))?[0])
+            }
+            "DWIDTH" => advance = Some(numbers::<2>(n, args)?[0]),
+            "BBX" => bounding_box = numbers::<4>(n, args)?,
+            "BITMAP" => {
+                let [width, height, x, y] = bounding_box;
+                if width < 0 || height < 0 {
+                    return Err(FontError(format!("line {n}: negative size")));
+                }
+                let (width, height) = (width as usize, height as usize);
+                let mut pixels = Vec::with_capacity(width * height);
+                for _ in 0..height {
+                    let (n, row) = lines
+                        .next()
+                        .ok_or_else(|| FontError("unexpected end of file".to_string()))?;
+                    // Each hex digit is four pixels, with rows padded out to whole bytes
+                    let digits = row
+                        .chars()
+                        .map(|c| c.to_digit(16))
+                        .collect::<Option<Vec<_>>>()
+                        .filter(|d| d.len() * 4 >= width)
+                        .ok_or_else(|| FontError(format!("line {n}: invalid bitmap row")))?;
+                    pixels.extend((0..width).map(|i| digits[i / 4] & (8 >> (i % 4)) != 0));
+                }
+                match lines.next() {
+                    Some((_, "ENDCHAR")) => {}
+                    Some((n, _)) => return Err(FontError(format!("line {n}: expected ENDCHAR"))),
+                    None => return Err(FontError("unexpected end of file".to_string())),
+                }
+
+                let glyph = Glyph {
+                    advance: advance.unwrap_or(x + width as i32),
+                    offset: Point::new(x, y),
+                    size: Size::new(width as u32, height as u32),
+                    pixels,
+                };
+                let c = u32::try_from(encoding.unwrap_or(-1))
+                    .ok()
+                    .and_then(char::from_u32);
+                return Ok(c.map(|c| (c, glyph)));
+            }
+            "ENDCHAR" => return Err(FontError(format!("line {n}: character has no BITMAP"))),
+            _ => {}
+        }
+    }
+
+    Err(FontError("unexpected end of file".to_string()))
+}
+
+/// Split a line into its keyword and the rest of it.
+fn split(line: &str) -> (&str, &str) {
+    line.split_once(char::is_whitespace)
+        .map_or((line, ""), |(keyword, args)| (keyword, args.trim()))
+}
+
+/// Parse exactly `N` numbers separated by spaces.
+fn numbers<const N: usize>(line: usize, args: &str) -> Result<[i32; N], FontError> {
+    let error = || FontError(format!("line {line}: expected {N} numbers, not {args:?}"));
+    let numbers = args
+        .split_whitespace()
+        .map(str::parse)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| error())?;
+
+    numbers.try_into().map_err(|_| error())
+}
+
+/// A style to draw text in a [`BitmapFont`] with, like [`embedded_graphics::mono_font::MonoTextStyle`] is for the
+/// built in fonts.
+#[derive(Debug, Clone, Copy)]
+pub struct BitmapTextStyle {
+    pub font: &'static BitmapFont,
+    /// Nothing's drawn if this is `None`.
+    pub text_color: Option<Rgb888>,
+}
+
+impl BitmapTextStyle {
+    pub fn new(font: &'static BitmapFont, text_color: Rgb888) -> Self {
+        Self {
+            font,
+            text_color: Some(text_color),
+        }
+    }
+
+    /// How far down the baseline is from the given position.
+    fn baseline_offset(&self, baseline: Baseline) -> i32 {
+        let last_row = self.font.ascent - 1;
+        match baseline {
+            Baseline::Top => last_row,
+            Baseline::Bottom => -self.font.descent,
+            Baseline::Middle => last_row - (self.line_height() as i32 - 1) / 2,
+            Baseline::Alphabetic => 0,
+        }
+    }
+}
+
+impl TextRenderer for BitmapTextStyle {
+    type Color = Rgb888;
+
+    fn draw_string<D: DrawTarget<Color = Rgb888>>(
+        &self,
+        text: &str,
+        position: Point,
+        baseline: Baseline,
+        target: &mut D,
+    ) -> Result<Point, D::Error> {
+        let origin = position + Point::new(0, self.baseline_offset(baseline));
+        if let Some(colour) = self.text_color {
+            for (x, glyph) in self.font.layout(text) {
+                let start = origin + Point::new(x, 0);
+                target.draw_iter(glyph.points().map(|p| Pixel(start + p, colour)))?;
+            }
+        }
+
+        Ok(position + Point::new(self.font.width(text), 0))
+    }
+
+    fn draw_whitespace<D: DrawTarget<Color = Rgb888>>(
+        &self,
+        width: u32,
+        position: Point,
+        _baseline: Baseline,
+        _target: &mut D,
+    ) -> Result<Point, D::Error> {
+        Ok(position + Point::new(width as i32, 0))
+    }
+
+    fn measure_string(&self, text: &str, position: Point, baseline: Baseline) -> TextMetrics {
+        let width = self.font.width(text);
+        let top = position.y + self.baseline_offset(baseline) - (self.font.ascent - 1);
+
+        TextMetrics {
+            bounding_box: Rectangle::new(
+                Point::new(position.x, top),
+                Size::new(width.max(0) as u32, self.line_height()),
+            ),
+            next_position: position + Point::new(width, 0),
+        }
+    }
+
+    fn line_height(&self) -> u32 {
+        self.font.line_height()
+    }
+}
+
+impl CharacterStyle for BitmapTextStyle {
+    type Color = Rgb888;
+
+    fn set_text_color(&mut self, text_color: Option<Rgb888>) {
+        self.text_color = text_color;
+    }
+}
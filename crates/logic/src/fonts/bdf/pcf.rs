@@ -0,0 +1,351 @@
+//! Reading [`BitmapFont`]s from PCF files, the compiled form of BDF that X11 fonts are usually installed as.
+
+use std::collections::HashMap;
+
+use embedded_graphics::prelude::*;
+
+use super::{check_charset, default_char, BitmapFont, FontError, Glyph};
+
+/// What every PCF file starts with.
+pub(super) const MAGIC: &[u8] = b"\x01fcp";
+
+// The tables that are needed to draw it. There are others, but they're only used by X11.
+const PROPERTIES: u32 = 1 << 0;
+const ACCELERATORS: u32 = 1 << 1;
+const METRICS: u32 = 1 << 2;
+const BITMAPS: u32 = 1 << 3;
+const BDF_ENCODINGS: u32 = 1 << 5;
+const BDF_ACCELERATORS: u32 = 1 << 8;
+
+// What each table's format says about how it's stored
+/// Each row of a bitmap is padded to 1, 2, 4 or 8 bytes.
+const GLYPH_PAD_MASK: u32 = 3;
+/// Numbers are big endian, and bytes in bitmaps are in that order within each scan unit.
+const BYTE_MASK: u32 = 1 << 2;
+/// The leftmost pixel in each byte of a bitmap is the most significant bit.
+const BIT_MASK: u32 = 1 << 3;
+/// The bytes in bitmaps are grouped into units of 1, 2 or 4, for swapping when the byte and bit orders differ.
+const SCAN_UNIT_MASK: u32 = 3 << 4;
+/// Metrics are stored a byte each, offset by 0x80, rather than as 16 bit numbers.
+const COMPRESSED_METRICS: u32 = 1 << 8;
+
+/// Reads numbers from one of the tables, in the byte order its format says.
+struct Table<'a> {
+    data: &'a [u8],
+    position: usize,
+    format: u32,
+}
+
+impl<'a> Table<'a> {
+    /// Find the table of the given type, if the file has one, and read its format.
+    fn find(
+        pcf: &'a [u8],
+        tables: &HashMap<u32, (usize, usize)>,
+        kind: u32,
+    ) -> Result<Option<Self>, FontError> {
+        let Some(&(offset, size)) = tables.get(&kind) else {
+            return Ok(None);
+        };
+        let data = pcf
+            .get(offset..offset.saturating_add(size))
+            .ok_or_else(|| FontError("table goes past the end of the file".to_string()))?;
+        let mut table = Self {
+            data,
+            position: 0,
+            format: 0,
+        };
+        // This is always little endian, as it's what says if the rest is
+        table.format = u32::from_le_bytes(table.array()?);
+
+        Ok(Some(table))
+    }
+
+    /// Find the table of the given type, which the file has to have.
+    fn require(
+        pcf: &'a [u8],
+        tables: &HashMap<u32, (usize, usize)>,
+        kind: u32,
+        name: &str,
+    ) -> Result<Self, FontError> {
+        Self::find(pcf, tables, kind)?.ok_or_else(|| FontError(format!("no {name} table")))
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], FontError> {
+        let bytes = self
+            .data
+            .get(self.position..self.position.saturating_add(n))
+            .ok_or_else(|| FontError("table is cut short".to_string()))?;
+        self.position += n;
+
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], FontError> {
+        Ok(self.bytes(N)?.try_into().expect("N bytes were read"))
+    }
+
+    fn big_endian(&self) -> bool {
+        self.format & BYTE_MASK != 0
+    }
+
+    fn u8(&mut self) -> Result<u8, FontError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn i16(&mut self) -> Result<i16, FontError> {
+        let bytes = self.array()?;
+        Ok(if self.big_endian() {
+            i16::from_be_bytes(bytes)
+        } else {
+            i16::from_le_bytes(bytes)
+        })
+    }
+
+    fn i32(&mut self) -> Result<i32, FontError> {
+        let bytes = self.array()?;
+        Ok(if self.big_endian() {
+            i32::from_be_bytes(bytes)
+        } else {
+            i32::from_le_bytes(bytes)
+        })
+    }
+
+    /// A count of things that follow, which can't be negative.
+    fn count(&mut self) -> Result<usize, FontError> {
+        usize::try_from(self.i32()?).map_err(|_| FontError("negative count".to_string()))
+    }
+
+    /// The size and position of a glyph.
+    fn metrics(&mut self) -> Result<Metrics, FontError> {
+        let compressed = self.format & COMPRESSED_METRICS != 0;
+        let mut number = || -> Result<i32, FontError> {
+            if compressed {
+                Ok(self.u8()? as i32 - 0x80)
+            } else {
+                Ok(self.i16()? as i32)
+            }
+        };
+        let metrics = Metrics {
+            left: number()?,
+            right: number()?,
+            advance: number()?,
+            ascent: number()?,
+            descent: number()?,
+        };
+        if !compressed {
+            // Attributes, which are only used by X11
+            self.bytes(2)?;
+        }
+
+        Ok(metrics)
+    }
+}
+
+/// Where a glyph is drawn, relative to the start of it on the baseline.
+#[derive(Debug, Clone, Copy)]
+struct Metrics {
+    /// From the start to the left edge of the bitmap.
+    left: i32,
+    /// From the start to the right edge of the bitmap.
+    right: i32,
+    /// How far along the next glyph starts.
+    advance: i32,
+    /// Rows of the bitmap above the baseline.
+    ascent: i32,
+    /// Rows of the bitmap below it.
+    descent: i32,
+}
+
+impl BitmapFont {
+    /// Parse the contents of a PCF file, giving the font the given name.
+    pub fn parse_pcf(name: impl Into<String>, pcf: &[u8]) -> Result<Self, FontError> {
+        let mut header = Table {
+            data: pcf,
+            position: 0,
+            format: 0,
+        };
+        if header.bytes(MAGIC.len()).ok() != Some(MAGIC) {
+            return Err(FontError("not a PCF file".to_string()));
+        }
+        let mut tables = HashMap::new();
+        for _ in 0..header.count()? {
+            let kind = header.i32()? as u32;
+            let _format = header.i32()?;
+            let size = header.count()?;
+            let offset = header.count()?;
+            tables.insert(kind, (offset, size));
+        }
+
+        let properties = match Table::find(pcf, &tables, PROPERTIES)? {
+            Some(table) => read_properties(table)?,
+            None => HashMap::new(),
+        };
+        check_charset(
+            properties.get("CHARSET_REGISTRY").map(String::as_str),
+            properties.get("CHARSET_ENCODING").map(String::as_str),
+        )?;
+
+        let mut table = Table::require(pcf, &tables, METRICS, "metrics")?;
+        let count = if table.format & COMPRESSED_METRICS != 0 {
+            table.i16()? as u16 as usize
+        } else {
+            table.count()?
+        };
+        let metrics = (0..count)
+            .map(|_| table.metrics())
+            .collect::<Result<Vec<_>, _>>()?;
+        let bitmaps = read_bitmaps(Table::require(pcf, &tables, BITMAPS, "bitmaps")?, &metrics)?;
+
+        let mut table = Table::require(pcf, &tables, BDF_ENCODINGS, "encodings")?;
+        // Characters are encoded in up to two bytes, with a range of values for each
+        let mut range = || Ok::<_, FontError>((table.i16()? as u16, table.i16()? as u16));
+        let (min_byte2, max_byte2) = range()?;
+        let (min_byte1, max_byte1) = range()?;
+        let default = table.i16()? as u16;
+        if min_byte2 > max_byte2 || min_byte1 > max_byte1 || max_byte2 > 0xff || max_byte1 > 0xff {
+            return Err(FontError("invalid range of encodings".to_string()));
+        }
+        let mut glyphs = HashMap::new();
+        for byte1 in min_byte1..=max_byte1 {
+            for byte2 in min_byte2..=max_byte2 {
+                let index = table.i16()? as u16;
+                let code = ((byte1 as u32) << 8) | byte2 as u32;
+                // Characters the font doesn't have are 0xffff
+                let glyph = bitmaps.get(index as usize).cloned();
+                if let (Some(c), Some(glyph)) = (char::from_u32(code), glyph) {
+                    glyphs.insert(c, glyph);
+                }
+            }
+        }
+        if glyphs.is_empty() {
+            return Err(FontError("no characters".to_string()));
+        }
+
+        // Properties take priority, like in BDF files, then the accelerators, then the tallest glyphs
+        let property = |name: &str| properties.get(name).and_then(|p| p.parse::<i32>().ok());
+        let accelerators = Table::find(pcf, &tables, BDF_ACCELERATORS)?
+            .or(Table::find(pcf, &tables, ACCELERATORS)?)
+            .map(|mut table| {
+                // Skip the flags
+                table.bytes(8)?;
+                Ok::<_, FontError>((table.i32()?, table.i32()?))
+            })
+            .transpose()?;
+        let tallest = |row: fn(&Metrics) -> i32| metrics.iter().map(row).max().unwrap_or_default();
+        let ascent = property("FONT_ASCENT")
+            .or(accelerators.map(|(ascent, _)| ascent))
+            .unwrap_or_else(|| tallest(|m| m.ascent));
+        let descent = property("FONT_DESCENT")
+            .or(accelerators.map(|(_, descent)| descent))
+            .unwrap_or_else(|| tallest(|m| m.descent));
+
+        Ok(Self {
+            name: name.into(),
+            ascent,
+            descent,
+            default_char: default_char(Some(default as i32), &glyphs),
+            glyphs,
+            kerning: HashMap::new(),
+        })
+    }
+}
+
+/// Read the properties table. Numbers are turned into strings, so they're the same as in BDF files.
+fn read_properties(mut table: Table) -> Result<HashMap<String, String>, FontError> {
+    let count = table.count()?;
+    let mut properties = Vec::new();
+    for _ in 0..count {
+        let name = table.i32()?;
+        let is_string = table.u8()? != 0;
+        let value = table.i32()?;
+        properties.push((name, is_string, value));
+    }
+    // Padded to a multiple of 4 bytes
+    if count % 4 != 0 {
+        table.bytes(4 - count % 4)?;
+    }
+    let size = table.count()?;
+    let strings = table.bytes(size)?;
+    let string = |offset: i32| {
+        let string = usize::try_from(offset)
+            .ok()
+            .and_then(|offset| strings.get(offset..))
+            .ok_or_else(|| FontError("property name goes past the end of the table".to_string()))?;
+        let end = string.iter().position(|b| *b == 0).unwrap_or(string.len());
+        Ok::<_, FontError>(String::from_utf8_lossy(&string[..end]).into_owned())
+    };
+
+    properties
+        .into_iter()
+        .map(|(name, is_string, value)| {
+            let value = if is_string {
+                string(value)?
+            } else {
+                value.to_string()
+            };
+            Ok((string(name)?, value))
+        })
+        .collect()
+}
+
+/// Read the bitmap for each glyph, from the bitmaps table.
+fn read_bitmaps(mut table: Table, metrics: &[Metrics]) -> Result<Vec<Glyph>, FontError> {
+    let count = table.count()?;
+    if count != metrics.len() {
+        return Err(FontError(format!(
+            "{count} bitmaps, but {} metrics",
+            metrics.len()
+        )));
+    }
+    let offsets = (0..count)
+        .map(|_| table.count())
+        .collect::<Result<Vec<_>, _>>()?;
+    // The size of all the bitmaps with each amount of padding, of which only one is in the file
+    let sizes = [(); 4].map(|_| table.count());
+    let pad = (table.format & GLYPH_PAD_MASK) as usize;
+    let size = sizes[pad].clone()?;
+
+    // Put every byte in the same order, with the leftmost pixel in the most significant bit
+    let mut data = table.bytes(size)?.to_vec();
+    let msb_first = table.format & BIT_MASK != 0;
+    if !msb_first {
+        for byte in &mut data {
+            *byte = byte.reverse_bits();
+        }
+    }
+    if table.big_endian() != msb_first {
+        let unit = 1 << ((table.format & SCAN_UNIT_MASK) >> 4);
+        for unit in data.chunks_mut(unit) {
+            unit.reverse();
+        }
+    }
+
+    metrics
+        .iter()
+        .zip(offsets)
+        .map(|(metrics, offset)| {
+            let width = metrics.right - metrics.left;
+            let height = metrics.ascent + metrics.descent;
+            if width < 0 || height < 0 {
+                return Err(FontError("negative size".to_string()));
+            }
+            let (width, height) = (width as usize, height as usize);
+            let row_bytes = width.div_ceil(8).next_multiple_of(1 << pad);
+            let rows = data
+                .get(offset..offset + row_bytes * height)
+                .ok_or_else(|| FontError("bitmap goes past the end of the table".to_string()))?;
+            let pixels = rows
+                .chunks(row_bytes.max(1))
+                .take(height)
+                .flat_map(|row| (0..width).map(|x| row[x / 8] & (0x80 >> (x % 8)) != 0))
+                .collect();
+
+            Ok(Glyph {
+                advance: metrics.advance,
+                offset: Point::new(metrics.left, -metrics.descent),
+                size: Size::new(width as u32, height as u32),
+                pixels,
+            })
+        })
+        .collect()
+}
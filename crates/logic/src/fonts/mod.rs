@@ -0,0 +1,297 @@
+//! Every font that can be used to draw text, by name.
+//!
+//! Most fonts come in several charsets. Asking for a font by name gets whichever version of it can draw all of the
+//! text, so messages in other languages just work. Fonts can also be loaded from BDF or PCF files, see [`BitmapFont`].
+
+use std::sync::{LazyLock, PoisonError, RwLock};
+
+use embedded_graphics::{
+    mono_font::{
+        ascii, iso_8859_1, iso_8859_10, iso_8859_13, iso_8859_14, iso_8859_15, iso_8859_16,
+        iso_8859_2, iso_8859_3, iso_8859_4, iso_8859_5, iso_8859_7, iso_8859_9, jis_x0201,
+        MonoFont,
+    },
+    prelude::*,
+};
+use ibm437::{IBM437_8X8_BOLD, IBM437_8X8_REGULAR, IBM437_9X14_REGULAR};
+
+mod bdf;
+mod style;
+
+pub use bdf::*;
+pub use style::*;
+
+/// The font used when none is asked for, or the one asked for doesn't exist.
+pub const DEFAULT_FONT: &str = "10x20";
+
+/// Which characters a font can draw.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    Ascii,
+    /// Western European.
+    Iso8859_1,
+    /// Central European.
+    Iso8859_2,
+    /// South European.
+    Iso8859_3,
+    /// North European.
+    Iso8859_4,
+    /// Cyrillic.
+    Iso8859_5,
+    /// Greek.
+    Iso8859_7,
+    /// Turkish.
+    Iso8859_9,
+    /// Nordic.
+    Iso8859_10,
+    /// Baltic.
+    Iso8859_13,
+    /// Celtic.
+    Iso8859_14,
+    /// Western European, with the euro sign.
+    Iso8859_15,
+    /// South-Eastern European.
+    Iso8859_16,
+    /// Katakana.
+    JisX0201,
+    /// The original IBM PC character set, with box drawing characters.
+    Ibm437,
+    /// Whatever a font loaded from a file has.
+    Loaded,
+}
+
+/// A font in the registry.
+#[derive(Debug)]
+pub struct FontInfo {
+    /// What it's asked for by, ie `10x20` or `6x13-bold`. Each name has a version in each charset it's available in.
+    pub name: &'static str,
+    pub charset: Charset,
+    pub font: Font,
+}
+
+impl FontInfo {
+    /// The size of each character, or the biggest one if they're not all the same.
+    pub fn size(&self) -> Size {
+        self.font.size()
+    }
+
+    /// Whether the font has a glyph for every character in the text. Control characters, like newlines, are ignored.
+    pub fn supports(&self, text: &str) -> bool {
+        self.font.supports(text)
+    }
+}
+
+/// Every version of each font from embedded-graphics in the given charset. Not every charset has all of them.
+macro_rules! fonts_in {
+    ($charset:ident, $module:ident) => {
+        in_charset(
+            Charset::$charset,
+            [
+                ("4x6", &$module::FONT_4X6),
+                ("5x7", &$module::FONT_5X7),
+                ("5x8", &$module::FONT_5X8),
+                ("6x9", &$module::FONT_6X9),
+                ("6x10", &$module::FONT_6X10),
+                ("6x12", &$module::FONT_6X12),
+                ("6x13", &$module::FONT_6X13),
+                ("6x13-bold", &$module::FONT_6X13_BOLD),
+                ("6x13-italic", &$module::FONT_6X13_ITALIC),
+                ("7x13", &$module::FONT_7X13),
+                ("7x13-bold", &$module::FONT_7X13_BOLD),
+                ("7x13-italic", &$module::FONT_7X13_ITALIC),
+                ("7x14", &$module::FONT_7X14),
+                ("7x14-bold", &$module::FONT_7X14_BOLD),
+                ("8x13", &$module::FONT_8X13),
+                ("8x13-bold", &$module::FONT_8X13_BOLD),
+                ("8x13-italic", &$module::FONT_8X13_ITALIC),
+                ("9x15", &$module::FONT_9X15),
+                ("9x15-bold", &$module::FONT_9X15_BOLD),
+                ("9x18", &$module::FONT_9X18),
+                ("9x18-bold", &$module::FONT_9X18_BOLD),
+                ("10x20", &$module::FONT_10X20),
+            ],
+        )
+    };
+}
+
+fn in_charset<const N: usize>(
+    charset: Charset,
+    fonts: [(&'static str, &'static MonoFont<'static>); N],
+) -> [FontInfo; N] {
+    fonts.map(|(name, font)| FontInfo {
+        name,
+        charset,
+        font: Font::Mono(font),
+    })
+}
+
+/// The name of the IBM font stretched to fill the height of the panel.
+pub const BIG_FONT: &str = "16x32";
+
+/// The IBM font stretched to fill the height of the panel, for text that's only a few characters long.
+static BIG: LazyLock<BitmapFont> = LazyLock::new(|| {
+    BitmapFont::scaled(
+        BIG_FONT,
+        &IBM437_8X8_BOLD,
+        Size::new(2, 4),
+        ' '..='\u{266b}',
+    )
+});
+
+/// Every font, with the charsets to try first earlier on.
+static FONTS: LazyLock<Vec<FontInfo>> = LazyLock::new(|| {
+    let jis_x0201 = in_charset(
+        Charset::JisX0201,
+        [
+            ("6x13", &jis_x0201::FONT_6X13),
+            ("7x14", &jis_x0201::FONT_7X14),
+            ("8x13", &jis_x0201::FONT_8X13),
+            ("9x15", &jis_x0201::FONT_9X15),
+            ("9x18", &jis_x0201::FONT_9X18),
+            ("10x20", &jis_x0201::FONT_10X20),
+        ],
+    );
+    let ibm437 = in_charset(
+        Charset::Ibm437,
+        [
+            ("8x8", &IBM437_8X8_REGULAR),
+            ("8x8-bold", &IBM437_8X8_BOLD),
+            ("9x14", &IBM437_9X14_REGULAR),
+        ],
+    );
+    let big = FontInfo {
+        name: BIG_FONT,
+        charset: Charset::Ibm437,
+        font: Font::Bitmap(&BIG),
+    };
+
+    [
+        fonts_in!(Iso8859_1, iso_8859_1),
+        fonts_in!(Iso8859_15, iso_8859_15),
+        fonts_in!(Iso8859_16, iso_8859_16),
+        fonts_in!(Iso8859_2, iso_8859_2),
+        fonts_in!(Iso8859_3, iso_8859_3),
+        fonts_in!(Iso8859_4, iso_8859_4),
+        fonts_in!(Iso8859_9, iso_8859_9),
+        fonts_in!(Iso8859_10, iso_8859_10),
+        fonts_in!(Iso8859_13, iso_8859_13),
+        fonts_in!(Iso8859_14, iso_8859_14),
+        fonts_in!(Iso8859_5, iso_8859_5),
+        fonts_in!(Iso8859_7, iso_8859_7),
+        // Everything in here is in every other charset too
+        fonts_in!(Ascii, ascii),
+    ]
+    .into_iter()
+    .flatten()
+    .chain(jis_x0201)
+    .chain(ibm437)
+    .chain([big])
+    .collect()
+});
+
+/// The fonts loaded from files, which are tried before the built in ones.
+static LOADED: RwLock<Vec<&'static FontInfo>> = RwLock::new(Vec::new());
+
+/// Add a font loaded from a file, replacing any other loaded font with the same name.
+/// It's kept for as long as the program runs, so fonts should only be loaded once, at startup.
+pub fn add(font: BitmapFont) -> &'static FontInfo {
+    let font: &'static BitmapFont = Box::leak(Box::new(font));
+    let info: &'static FontInfo = Box::leak(Box::new(FontInfo {
+        name: font.name(),
+        charset: Charset::Loaded,
+        font: Font::Bitmap(font),
+    }));
+    let mut loaded = LOADED.write().unwrap_or_else(PoisonError::into_inner);
+    loaded.retain(|f| f.name != info.name);
+    loaded.push(info);
+
+    info
+}
+
+/// Every font, in every charset it's available in.
+pub fn all() -> Vec<&'static FontInfo> {
+    let loaded = LOADED.read().unwrap_or_else(PoisonError::into_inner);
+    loaded.iter().copied().chain(FONTS.iter()).collect()
+}
+
+/// The names of all the fonts, in alphabetical order.
+pub fn names() -> Vec<&'static str> {
+    let mut names = all().iter().map(|f| f.name).collect::<Vec<_>>();
+    names.sort_unstable();
+    names.dedup();
+    names
+}
+
+/// Whether there's a font with the given name.
+pub fn exists(name: &str) -> bool {
+    named(name).next().is_some()
+}
+
+/// The best font to draw the given text in, preferably the one with the given name.
+///
+/// This is the first version of the font with that name that can draw all of the text. If there isn't one, or there's
+/// no font with that name, it's the first version of [`DEFAULT_FONT`] that can. If even that can't, it's whichever
+/// was asked for, with the missing characters drawn as question marks.
+pub fn font_for(name: &str, text: &str) -> &'static FontInfo {
+    let requested = named(name);
+    let default = named(DEFAULT_FONT);
+    requested
+        .clone()
+        .chain(default.clone())
+        .find(|f| f.supports(text))
+        .or_else(|| requested.clone().next())
+        .or_else(|| default.clone().next())
+        .expect("the default font exists")
+}
+
+/// The font to draw a character in that the given font doesn't have, if any font can: another version of the same font,
+/// then the default font, then whichever font is closest in height.
+pub fn fallback_for(font: Font, c: char) -> Option<&'static FontInfo> {
+    let mut buffer = [0; 4];
+    let c = &*c.encode_utf8(&mut buffer);
+    let height = font.size().height;
+
+    name_of(font)
+        .map(named)
+        .into_iter()
+        .flatten()
+        .chain(named(DEFAULT_FONT))
+        .find(|f| f.supports(c))
+        .or_else(|| {
+            all()
+                .into_iter()
+                .filter(|f| f.supports(c))
+                .min_by_key(|f| f.size().height.abs_diff(height))
+        })
+}
+
+/// The name of the given font, if it's in the registry.
+pub fn name_of(font: Font) -> Option<&'static str> {
+    let font = match font {
+        Font::Mono(font) => font,
+        Font::Bitmap(font) => return Some(font.name()),
+    };
+    FONTS
+        .iter()
+        .find(|f| f.font == Font::Mono(font))
+        .or_else(|| {
+            // The built in fonts are constants, so one that didn't come from here can be a copy of one of them
+            FONTS.iter().find(|f| {
+                matches!(f.font, Font::Mono(f) if f.character_size == font.character_size && f.image == font.image)
+            })
+        })
+        .map(|f| f.name)
+}
+
+/// Every version of the font with the given name.
+fn named(name: &str) -> impl Iterator<Item = &'static FontInfo> + Clone + '_ {
+    let loaded = LOADED.read().unwrap_or_else(PoisonError::into_inner);
+    let loaded = loaded
+        .iter()
+        .copied()
+        .filter(|f| f.name == name)
+        .collect::<Vec<_>>();
+    loaded
+        .into_iter()
+        .chain(FONTS.iter().filter(move |f| f.name == name))
+}
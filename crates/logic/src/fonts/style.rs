@@ -0,0 +1,157 @@
+use std::ptr;
+
+use embedded_graphics::{
+    mono_font::{MonoFont, MonoTextStyle},
+    pixelcolor::Rgb888,
+    prelude::*,
+    text::{
+        renderer::{CharacterStyle, TextMetrics, TextRenderer},
+        Baseline,
+    },
+};
+
+use super::{BitmapFont, BitmapTextStyle};
+
+/// A font that's either built in, or loaded from a file.
+#[derive(Debug, Clone, Copy)]
+pub enum Font {
+    Mono(&'static MonoFont<'static>),
+    Bitmap(&'static BitmapFont),
+}
+
+impl Font {
+    /// A style to draw text in this font in the given colour.
+    pub fn style(self, colour: Rgb888) -> FontStyle {
+        match self {
+            Font::Mono(font) => FontStyle::Mono(MonoTextStyle::new(font, colour)),
+            Font::Bitmap(font) => FontStyle::Bitmap(BitmapTextStyle::new(font, colour)),
+        }
+    }
+
+    /// The size of each character, or the biggest one if they're not all the same.
+    pub fn size(self) -> Size {
+        match self {
+            Font::Mono(font) => font.character_size,
+            Font::Bitmap(font) => font.size(),
+        }
+    }
+
+    /// Whether the font has a glyph for every character in the text. Control characters, like newlines, are ignored.
+    pub fn supports(self, text: &str) -> bool {
+        match self {
+            Font::Mono(font) => {
+                // Missing characters are drawn as a question mark
+                let replacement = font.glyph_mapping.index('?');
+                text.chars()
+                    .filter(|c| !c.is_control() && *c != '?')
+                    .all(|c| font.glyph_mapping.index(c) != replacement)
+            }
+            Font::Bitmap(font) => font.supports(text),
+        }
+    }
+}
+
+/// Fonts are only the same if they're the same font in the registry, not just if they look the same.
+impl PartialEq for Font {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Font::Mono(a), Font::Mono(b)) => ptr::eq(*a, *b),
+            (Font::Bitmap(a), Font::Bitmap(b)) => ptr::eq(*a, *b),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Font {}
+
+/// A style to draw text in any [`Font`] with, so it's measured and drawn the same way whichever kind of font it is.
+#[derive(Debug, Clone, Copy)]
+pub enum FontStyle {
+    Mono(MonoTextStyle<'static, Rgb888>),
+    Bitmap(BitmapTextStyle),
+}
+
+impl FontStyle {
+    /// The font the text is drawn in.
+    pub fn font(&self) -> Font {
+        match self {
+            FontStyle::Mono(style) => Font::Mono(style.font),
+            FontStyle::Bitmap(style) => Font::Bitmap(style.font),
+        }
+    }
+
+    /// The colour the text is drawn in, if any.
+    pub fn text_color(&self) -> Option<Rgb888> {
+        match self {
+            FontStyle::Mono(style) => style.text_color,
+            FontStyle::Bitmap(style) => style.text_color,
+        }
+    }
+}
+
+impl From<MonoTextStyle<'static, Rgb888>> for FontStyle {
+    fn from(style: MonoTextStyle<'static, Rgb888>) -> Self {
+        FontStyle::Mono(style)
+    }
+}
+
+impl From<BitmapTextStyle> for FontStyle {
+    fn from(style: BitmapTextStyle) -> Self {
+        FontStyle::Bitmap(style)
+    }
+}
+
+impl TextRenderer for FontStyle {
+    type Color = Rgb888;
+
+    fn draw_string<D: DrawTarget<Color = Rgb888>>(
+        &self,
+        text: &str,
+        position: Point,
+        baseline: Baseline,
+        target: &mut D,
+    ) -> Result<Point, D::Error> {
+        match self {
+            FontStyle::Mono(style) => style.draw_string(text, position, baseline, target),
+            FontStyle::Bitmap(style) => style.draw_string(text, position, baseline, target),
+        }
+    }
+
+    fn draw_whitespace<D: DrawTarget<Color = Rgb888>>(
+        &self,
+        width: u32,
+        position: Point,
+        baseline: Baseline,
+        target: &mut D,
+    ) -> Result<Point, D::Error> {
+        match self {
+            FontStyle::Mono(style) => style.draw_whitespace(width, position, baseline, target),
+            FontStyle::Bitmap(style) => style.draw_whitespace(width, position, baseline, target),
+        }
+    }
+
+    fn measure_string(&self, text: &str, position: Point, baseline: Baseline) -> TextMetrics {
+        match self {
+            FontStyle::Mono(style) => style.measure_string(text, position, baseline),
+            FontStyle::Bitmap(style) => style.measure_string(text, position, baseline),
+        }
+    }
+
+    fn line_height(&self) -> u32 {
+        match self {
+            FontStyle::Mono(style) => style.line_height(),
+            FontStyle::Bitmap(style) => style.line_height(),
+        }
+    }
+}
+
+impl CharacterStyle for FontStyle {
+    type Color = Rgb888;
+
+    fn set_text_color(&mut self, text_color: Option<Rgb888>) {
+        match self {
+            FontStyle::Mono(style) => style.set_text_color(text_color),
+            FontStyle::Bitmap(style) => style.set_text_color(text_color),
+        }
+    }
+}
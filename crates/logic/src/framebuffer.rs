@@ -0,0 +1,117 @@
+//! An in-memory draw target, used to render screens offscreen before they are sent to the real display.
+
+use core::convert::Infallible;
+
+use embedded_graphics::{pixelcolor::Rgb888, prelude::*, primitives::Rectangle};
+
+/// A buffer of pixels that screens draw into.
+/// [`crate::DisplayLogic`] draws each screen to one of these, then composites them onto the actual display.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Framebuffer {
+    size: Size,
+    pixels: Vec<Rgb888>,
+}
+
+impl Framebuffer {
+    /// Create a new black framebuffer of the given size.
+    pub fn new(size: Size) -> Self {
+        Self {
+            size,
+            pixels: vec![Rgb888::BLACK; (size.width * size.height) as usize],
+        }
+    }
+
+    /// Get the colour of the pixel at the given point, or `None` if it's out of bounds.
+    pub fn pixel(&self, p: Point) -> Option<Rgb888> {
+        self.index_of(p).map(|i| self.pixels[i])
+    }
+
+    /// Set the colour of the pixel at the given point. Out of bounds points are ignored.
+    pub fn set_pixel(&mut self, p: Point, colour: Rgb888) {
+        if let Some(i) = self.index_of(p) {
+            self.pixels[i] = colour;
+        }
+    }
+
+    /// All pixels in the buffer, in row-major order.
+    pub fn pixels(&self) -> &[Rgb888] {
+        &self.pixels
+    }
+
+    /// Mutable access to all pixels in the buffer, in row-major order.
+    pub fn pixels_mut(&mut self) -> &mut [Rgb888] {
+        &mut self.pixels
+    }
+
+    /// Copy the contents of this buffer onto the given display.
+    pub fn blit<D: DrawTarget<Color = Rgb888>>(&self, display: &mut D) -> Result<(), D::Error> {
+        self.blit_at(display, display.bounding_box().top_left)
+    }
+
+    /// Copy the contents of this buffer onto the given display, with its top left corner at the given point.
+    pub fn blit_at<D: DrawTarget<Color = Rgb888>>(
+        &self,
+        display: &mut D,
+        top_left: Point,
+    ) -> Result<(), D::Error> {
+        display.fill_contiguous(
+            &Rectangle::new(top_left, self.size),
+            self.pixels.iter().copied(),
+        )
+    }
+
+    /// Copy the contents of this buffer onto the given display, dimmed to the given brightness out of 255.
+    pub fn blit_with_brightness<D: DrawTarget<Color = Rgb888>>(
+        &self,
+        display: &mut D,
+        brightness: u8,
+    ) -> Result<(), D::Error> {
+        if brightness == u8::MAX {
+            return self.blit(display);
+        }
+
+        let scale = |c: u8| (c as u16 * brightness as u16 / u8::MAX as u16) as u8;
+        display.fill_contiguous(
+            &Rectangle::new(display.bounding_box().top_left, self.size),
+            self.pixels
+                .iter()
+                .map(|c| Rgb888::new(scale(c.r()), scale(c.g()), scale(c.b()))),
+        )
+    }
+
+    fn index_of(&self, p: Point) -> Option<usize> {
+        if p.x < 0 || p.y < 0 || p.x >= self.size.width as i32 || p.y >= self.size.height as i32 {
+            return None;
+        }
+
+        Some(p.x as usize + p.y as usize * self.size.width as usize)
+    }
+}
+
+impl OriginDimensions for Framebuffer {
+    fn size(&self) -> Size {
+        self.size
+    }
+}
+
+impl DrawTarget for Framebuffer {
+    type Color = Rgb888;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(p, colour) in pixels {
+            self.set_pixel(p, colour);
+        }
+
+        Ok(())
+    }
+
+    fn clear(&mut self, colour: Self::Color) -> Result<(), Self::Error> {
+        self.pixels.fill(colour);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,169 @@
+//! Splitting the display into zones, each with their own rotation of screens.
+
+use std::{fmt, str::FromStr};
+
+use embedded_graphics::{prelude::*, primitives::Rectangle};
+use serde::{Deserialize, Serialize};
+
+use crate::PANEL_SIZE;
+
+/// The name of the zone used by [`Layout::default`].
+pub const MAIN_ZONE: &str = "main";
+
+/// A named area of the display, which shows its own rotation of screens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Zone {
+    pub name: String,
+    /// Where the zone is on the display. Screens in the zone see a display of this size, with the origin at its
+    /// top left.
+    pub area: Rectangle,
+}
+
+impl Zone {
+    pub fn new(name: impl Into<String>, area: Rectangle) -> Self {
+        Self {
+            name: name.into(),
+            area,
+        }
+    }
+}
+
+/// How the display is split up into [`Zone`]s.
+///
+/// The first zone is the main one: commands that don't say which zone they're for go to it.
+/// Zones are drawn in order, so later zones are drawn over earlier ones where they overlap.
+///
+/// Can be parsed from strings like `"main 0,0 152x32; clock 152,0 40x32"`: each zone is a name, the position of its
+/// top left corner, and its size, with zones separated by semicolons.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Layout {
+    zones: Vec<Zone>,
+}
+
+impl Layout {
+    /// Create a layout with the given zones. Fails if there are no zones, two have the same name, or any of them are
+    /// empty or don't fit on the panel.
+    pub fn new(zones: Vec<Zone>) -> Result<Self, ParseLayoutError> {
+        if zones.is_empty() {
+            return Err(ParseLayoutError(
+                "there should be at least one zone".to_string(),
+            ));
+        }
+        for (i, zone) in zones.iter().enumerate() {
+            let name = &zone.name;
+            if zones[..i].iter().any(|other| other.name == *name) {
+                return Err(ParseLayoutError(format!(
+                    "there's more than one zone called {name:?}"
+                )));
+            }
+            if zone.area.is_zero_sized() {
+                return Err(ParseLayoutError(format!("zone {name:?} is empty")));
+            }
+            if !fits_on_panel(&zone.area) {
+                return Err(ParseLayoutError(format!(
+                    "zone {name:?} doesn't fit on the {}x{} panel",
+                    PANEL_SIZE.width, PANEL_SIZE.height
+                )));
+            }
+        }
+
+        Ok(Self { zones })
+    }
+
+    /// A layout with a single [`MAIN_ZONE`] of the given size.
+    pub fn single(size: Size) -> Self {
+        Self {
+            zones: vec![Zone::new(MAIN_ZONE, Rectangle::new(Point::zero(), size))],
+        }
+    }
+
+    /// All zones in the layout, starting with the main one.
+    pub fn zones(&self) -> &[Zone] {
+        &self.zones
+    }
+
+    /// The position of the zone with the given name, if there is one.
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.zones.iter().position(|z| z.name == name)
+    }
+}
+
+impl Default for Layout {
+    /// A single zone covering the whole panel.
+    fn default() -> Self {
+        Self::single(PANEL_SIZE)
+    }
+}
+
+string_format!(Layout, ParseLayoutError, "layout");
+
+impl FromStr for Layout {
+    type Err = ParseLayoutError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let zones = s
+            .split(';')
+            .filter(|z| !z.trim().is_empty())
+            .map(parse_zone)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Layout::new(zones)
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, zone) in self.zones.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            let Rectangle { top_left, size } = zone.area;
+            write!(
+                f,
+                "{} {},{} {}x{}",
+                zone.name, top_left.x, top_left.y, size.width, size.height
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parse a single zone, like `clock 152,0 40x32`.
+fn parse_zone(s: &str) -> Result<Zone, ParseLayoutError> {
+    let invalid = || ParseLayoutError(format!("zone {s:?} should look like clock 152,0 40x32"));
+    let [name, position, size] = s.split_whitespace().collect::<Vec<_>>()[..] else {
+        return Err(invalid());
+    };
+    let (x, y) = position.split_once(',').ok_or_else(invalid)?;
+    let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+
+    Ok(Zone::new(
+        name,
+        Rectangle::new(
+            Point::new(
+                x.parse().map_err(|_| invalid())?,
+                y.parse().map_err(|_| invalid())?,
+            ),
+            Size::new(
+                width.parse().map_err(|_| invalid())?,
+                height.parse().map_err(|_| invalid())?,
+            ),
+        ),
+    ))
+}
+
+/// Whether the area is entirely on the panel.
+fn fits_on_panel(area: &Rectangle) -> bool {
+    // Checked, so huge zones don't overflow
+    let fits = |start: i32, length: u32, panel_length: u32| {
+        u32::try_from(start)
+            .ok()
+            .and_then(|start| start.checked_add(length))
+            .is_some_and(|end| end <= panel_length)
+    };
+
+    fits(area.top_left.x, area.size.width, PANEL_SIZE.width)
+        && fits(area.top_left.y, area.size.height, PANEL_SIZE.height)
+}
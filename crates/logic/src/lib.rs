@@ -1,133 +1,924 @@
 use std::{
-    collections::VecDeque,
+    collections::{HashMap, VecDeque},
+    io, mem,
+    path::PathBuf,
     sync::{
         atomic::{AtomicBool, Ordering},
         mpsc, Arc,
     },
-    time::Instant,
 };
 
+use clock::{Clock, SystemClock};
 use embedded_graphics::{
     pixelcolor::Rgb888,
-    prelude::{DrawTarget, RgbColor},
+    prelude::{DrawTarget, OriginDimensions, RgbColor, Size, Transform},
+    primitives::Rectangle,
 };
-use log::debug;
-use screens::Screen;
+use framebuffer::Framebuffer;
+use layout::Layout;
+use log::{debug, error, warn};
+use overlay::Overlay;
+use persist::{SavedRotation, SavedRotations};
+use playlist::{Playlist, SavedPlaylists, DEFAULT_PLAYLIST};
+use rotation::{Rotation, ScreenFailure};
+use screens::{AmbientScreen, ClockScreen, Screen, ScreenMessage, ScreenSpec};
+use sleep::SleepMode;
+use transition::Transition;
 
+/// Define the error returned when the type can't be parsed, and the conversions needed for
+/// `#[serde(try_from = "String", into = "String")]`, so it's saved the same way it's displayed, and loaded by parsing it.
+macro_rules! string_format {
+    ($type:ident, $error:ident, $what:literal) => {
+        #[doc = concat!("Returned when a [`", stringify!($type), "`] can't be parsed.")]
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        pub struct $error(String);
+
+        impl std::fmt::Display for $error {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, concat!("invalid ", $what, ": {}"), self.0)
+            }
+        }
+
+        impl std::error::Error for $error {}
+
+        impl TryFrom<String> for $type {
+            type Error = $error;
+
+            fn try_from(s: String) -> Result<Self, Self::Error> {
+                s.parse()
+            }
+        }
+
+        impl From<$type> for String {
+            fn from(value: $type) -> Self {
+                value.to_string()
+            }
+        }
+    };
+}
+
+mod command;
+pub use command::*;
+
+pub mod clock;
+pub mod config;
+pub mod fonts;
+pub mod framebuffer;
+pub mod layout;
+pub mod overlay;
+pub mod playlist;
+pub mod schedule;
 pub mod screens;
+pub mod sleep;
+pub mod transition;
 
+mod persist;
 mod recolour_image;
+mod rotation;
+
+/// The size of the LED panel in the window.
+pub const PANEL_SIZE: Size = Size::new(192, 32);
+
+/// How many of the screens that failed to draw are remembered. See [`DisplayState::quarantined`].
+const MAX_QUARANTINED: usize = 10;
 
 /// Handles the main logic for displaying things to the LED.
-/// Primarily, multiplexing between different [`screen::Screen`]s
-pub struct DisplayLogic<D: DrawTarget<Color = Rgb888>> {
-    /// The current list of screens
-    curr_screens: VecDeque<Box<dyn Screen<D>>>,
+/// Primarily, multiplexing between different [`screens::Screen`]s.
+///
+/// The display is split into zones by a [`Layout`], and each zone has its own rotation of screens. By default, there's
+/// a single zone covering the whole panel.
+///
+/// Screens are rotated through in the order they were added, but only screens of the highest [`screens::Priority`] present
+/// are shown. Lower priority screens wait in the rotation until the higher priority ones are removed.
+/// Screens outside of their [`Screen::schedule`] are skipped over, but stay in the rotation.
+///
+/// Each zone's screens are drawn to an offscreen [`Framebuffer`] the size of the zone, which are then composited onto
+/// the actual display. This lets us play a [`Transition`] when the displayed screen changes.
+/// Any [`overlay::Toast`]s are then drawn over the top, without affecting the rotations underneath.
+///
+/// The main zone shows whichever [`Playlist`] is active. Playlists that aren't active keep their screens, but aren't
+/// drawn or rotated.
+///
+/// A screen that panics while drawing is removed from its rotation, and the rest carry on. See
+/// [`DisplayState::quarantined`].
+///
+/// While sleeping, whatever the [`SleepMode`] says is shown instead, dimmed. Commands are still handled, but the
+/// rotations and notifications are held where they are, so anything sent in the meantime is shown on waking up.
+pub struct DisplayLogic {
+    /// How the display is split up.
+    layout: Layout,
+    /// The rotation for each zone in the layout, in the same order.
+    rotations: Vec<Rotation>,
+
+    /// The transition used for screens that don't specify their own.
+    default_transition: Transition,
 
-    /// The last time the active screen was changed
-    last_screen_change: Option<Instant>,
+    /// The name of the playlist shown in the main zone.
+    active_playlist: String,
+    /// All defined playlists, whether they're active or not.
+    playlists: Vec<Playlist>,
+    /// The main zone's rotation for each playlist that isn't active, if it's been shown before.
+    parked_playlists: HashMap<String, Rotation>,
+    /// Where playlists are saved, if anywhere.
+    playlist_file: Option<PathBuf>,
 
-    // Channels for communicating with the main loop
-    /// Received screens are added to the list of current screens
-    recv_screen: mpsc::Receiver<Box<dyn Screen<D>>>,
-    /// When a string is received, any current screens with a matching [`Screen::id()`] are removed.
-    recv_del_screen: mpsc::Receiver<String>,
+    /// Where the screens in each rotation are saved, if anywhere.
+    rotation_file: Option<PathBuf>,
+    /// What was last saved to the rotation file, so we only save it again when something changes.
+    last_saved_rotations: Option<SavedRotations>,
+    /// If true, the rotations may have changed since they were last saved.
+    rotations_changed: bool,
+
+    /// All zones composited together.
+    output_frame: Framebuffer,
+    /// If true, every zone is composited on the next frame, even if it hasn't changed.
+    force_composite: bool,
+
+    /// Notifications shown over the top of the zones.
+    overlay: Overlay,
+    /// The zones with the overlay drawn on top, while there is one.
+    overlay_frame: Framebuffer,
+    /// The part of the display covered by the overlay, if anything.
+    overlay_area: Option<Rectangle>,
+
+    /// If true, we don't automatically move on to the next screen.
+    rotation_paused: bool,
+    /// Everything drawn is dimmed by this amount, out of 255.
+    brightness: u8,
+
+    /// Commands from other threads, handled at the start of each frame.
+    recv_command: mpsc::Receiver<Command>,
+    /// Sent the state whenever it changes. See [`Command::Subscribe`].
+    state_subscribers: Vec<mpsc::Sender<DisplayState>>,
+    /// If true, the state may have changed since it was last sent to subscribers.
+    state_changed: bool,
+    /// The last message on each MQTT topic, which every screen is sent. See [`Command::SetValue`].
+    values: HashMap<String, String>,
 
     /// True if display should currently be sleeping.
     sleep: Arc<AtomicBool>,
+    /// What to show while sleeping.
+    sleep_mode: SleepMode,
+    /// The brightness used while sleeping, instead of the usual one.
+    sleep_brightness: u8,
+    /// Whether we were sleeping last frame.
+    was_sleeping: bool,
+    /// What's shown while sleeping, if anything. Only kept while we are.
+    sleep_rotation: Option<Rotation>,
+
+    /// How many times a screen has failed to draw.
+    draw_failures: u64,
+    /// The screens that most recently failed to draw, oldest first.
+    quarantined: VecDeque<QuarantinedScreen>,
+
+    /// Where we get the time from, for timing and checking screens' schedules.
+    clock: Box<dyn Clock>,
 }
 
-impl<D: DrawTarget<Color = Rgb888> + 'static> DisplayLogic<D> {
-    pub fn new(
-        recv_screen: mpsc::Receiver<Box<dyn Screen<D>>>,
-        recv_del_screen: mpsc::Receiver<String>,
-        sleep: Arc<AtomicBool>,
-    ) -> Self {
+impl DisplayLogic {
+    pub fn new(recv_command: mpsc::Receiver<Command>, sleep: Arc<AtomicBool>) -> Self {
+        let layout = Layout::default();
+        let default_transition = Transition::cut();
         Self {
-            recv_screen,
-            recv_del_screen,
-            curr_screens: VecDeque::new(),
-            last_screen_change: Default::default(),
+            recv_command,
+            state_subscribers: Vec::new(),
+            state_changed: false,
+            values: HashMap::new(),
+            rotations: layout
+                .zones()
+                .iter()
+                .map(|z| Rotation::new(z.area.size, default_transition))
+                .collect(),
+            layout,
+            default_transition,
+            active_playlist: DEFAULT_PLAYLIST.to_string(),
+            playlists: Vec::new(),
+            parked_playlists: HashMap::new(),
+            playlist_file: None,
+            rotation_file: None,
+            last_saved_rotations: None,
+            rotations_changed: false,
+            output_frame: Framebuffer::new(Default::default()),
+            force_composite: true,
+            overlay: Overlay::default(),
+            overlay_frame: Framebuffer::new(Default::default()),
+            overlay_area: None,
+            rotation_paused: false,
+            brightness: u8::MAX,
             sleep,
+            sleep_mode: SleepMode::default(),
+            sleep_brightness: 32,
+            was_sleeping: false,
+            sleep_rotation: None,
+            draw_failures: 0,
+            quarantined: VecDeque::new(),
+            clock: Box::new(SystemClock),
         }
     }
-    /// Add the given [`DisplayedScreen`] to the rotation
-    pub fn add(&mut self, sd: Box<dyn Screen<D>>) {
-        self.curr_screens.push_back(sd);
+
+    /// Use the given clock for all timing, instead of the system clock.
+    /// It's also passed to screens when they're drawn.
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.clock = clock;
     }
 
-    /// Draw a frame to the given display.
-    pub fn draw(&mut self, display: &mut D) -> Result<(), D::Error> {
-        if self.sleep.load(Ordering::Relaxed) {
-            display.clear(Rgb888::BLACK)?;
-            return Ok(());
+    /// Set the transition used when a screen doesn't specify its own with [`Screen::transition`], unless it's in a
+    /// playlist with its own. See [`Playlist::transition`].
+    pub fn set_default_transition(&mut self, transition: Transition) {
+        self.default_transition = transition;
+        for rotation in &mut self.rotations {
+            rotation.default_transition = transition;
+        }
+        self.rotations[0].default_transition = self.playlist_transition(&self.active_playlist);
+        let for_playlist = |name: &str| {
+            self.playlists
+                .iter()
+                .find(|p| p.name == name)
+                .and_then(|p| p.transition)
+                .unwrap_or(transition)
+        };
+        for (name, rotation) in &mut self.parked_playlists {
+            rotation.default_transition = for_playlist(name);
+        }
+    }
+
+    /// The transition used in the playlist with the given name for screens that don't specify their own.
+    fn playlist_transition(&self, name: &str) -> Transition {
+        self.playlists
+            .iter()
+            .find(|p| p.name == name)
+            .and_then(|p| p.transition)
+            .unwrap_or(self.default_transition)
+    }
+
+    /// Change what's shown while the display is sleeping.
+    /// If it's sleeping now, the new mode is shown straight away.
+    pub fn set_sleep_mode(&mut self, mode: SleepMode) {
+        self.sleep_mode = mode;
+        self.sleep_rotation = None;
+    }
+
+    /// Set the brightness used while sleeping, from 0 (off) to 255 (full).
+    pub fn set_sleep_brightness(&mut self, brightness: u8) {
+        self.sleep_brightness = brightness;
+    }
+
+    /// Split the display up differently.
+    /// Zones with the same name as one in the old layout keep their screens. Screens in zones that no longer exist
+    /// are moved to the main zone.
+    pub fn set_layout(&mut self, layout: Layout) {
+        debug!("changing layout to {}", layout);
+        let mut old_rotations = self
+            .layout
+            .zones()
+            .iter()
+            .map(|z| (z.name.clone(), z.area.size))
+            .zip(self.rotations.drain(..))
+            .collect::<Vec<_>>();
+
+        for zone in layout.zones() {
+            let rotation = match old_rotations
+                .iter()
+                .position(|((name, size), _)| *name == zone.name && *size == zone.area.size)
+            {
+                Some(i) => old_rotations.remove(i).1,
+                _ => Rotation::new(zone.area.size, self.default_transition),
+            };
+            self.rotations.push(rotation);
         }
+        self.layout = layout;
+        self.rotations[0].default_transition = self.playlist_transition(&self.active_playlist);
 
-        // Add/delete screens now if needed
-        let mut iter = self.recv_del_screen.try_iter();
-        while let Some(del_screen) = iter.next() {
-            debug!("deleting screens with id {:?}", del_screen);
-            let deleted_front = self
-                .curr_screens
-                .front()
-                .is_some_and(|s| s.id() == del_screen);
-            self.curr_screens.retain(|s| s.id() != del_screen);
-            if deleted_front {
-                self.last_screen_change = Some(Instant::now());
+        // Anything left over either had its zone removed or resized, so re-add its screens
+        for ((name, _), mut rotation) in old_rotations {
+            let idx = self.layout.position(&name).unwrap_or(0);
+            for screen in rotation.take_screens() {
+                self.rotations[idx].insert(screen, false, self.clock.as_ref());
             }
         }
 
-        let mut iter = self.recv_screen.try_iter();
-        while let Some(new_screen) = iter.next() {
-            debug!("got new screen: {:?}", new_screen);
-            let grab = new_screen.grab_attention();
-            self.curr_screens.push_back(new_screen);
-            if grab {
-                self.curr_screens.rotate_left(self.curr_screens.len() - 1);
-                self.last_screen_change = Some(Instant::now());
+        self.force_composite = true;
+    }
+
+    /// Load playlists from the given file, and switch to whichever was active when it was saved.
+    /// From now on, any changes to playlists are saved back to it. It's fine if the file doesn't exist yet.
+    pub fn load_playlists(&mut self, path: impl Into<PathBuf>) -> io::Result<()> {
+        let path = path.into();
+        if let Some(saved) = SavedPlaylists::load(&path)? {
+            self.playlists = saved.playlists;
+            self.parked_playlists.clear();
+            if saved.active == self.active_playlist {
+                self.rebuild_active_playlist();
+            } else {
+                self.switch_playlist(&saved.active);
             }
         }
+        self.playlist_file = Some(path);
 
-        let Some(screen) = self.curr_screens.front_mut() else {
-            // When no screens are displayed, just clear it.
-            self.last_screen_change = None;
-            return display.clear(Rgb888::BLACK);
+        Ok(())
+    }
+
+    /// Restore the screens in every rotation from the given file, dropping any that have expired since.
+    /// From now on, the rotations are saved back to it whenever they change. Should be called after
+    /// [`Self::load_playlists`] and [`Self::set_layout`], if they're used.
+    ///
+    /// Returns true if anything was restored, or false if the file doesn't exist yet.
+    pub fn load_rotations(&mut self, path: impl Into<PathBuf>) -> io::Result<bool> {
+        let path = path.into();
+        let saved = persist::load_json::<SavedRotations>(&path)?;
+        self.rotation_file = Some(path);
+        let Some(saved) = saved else {
+            return Ok(false);
         };
 
-        // See if we need to move on to the next screen, and/or remove this screen.
-        let last_screen_change = self.last_screen_change.get_or_insert(Instant::now());
-        let displayed_for = Instant::now() - *last_screen_change;
-        let single_display_duration = screen.single_display_duration(display);
-        if displayed_for >= single_display_duration {
-            debug!(
-                "screen displayed for {:?} out of {:?}",
-                displayed_for, single_display_duration
-            );
+        let main_size = self.rotations[0].size();
+        for playlist in &saved.playlists {
+            let mut rotation = self.restore_rotation(main_size, &playlist.screens);
+            rotation.default_transition = self.playlist_transition(&playlist.name);
+            if playlist.name == self.active_playlist {
+                self.rotations[0] = rotation;
+            } else {
+                self.parked_playlists
+                    .insert(playlist.name.clone(), rotation);
+            }
+        }
+        for zone in &saved.zones {
+            match self.layout.position(&zone.name) {
+                Some(idx) => {
+                    self.rotations[idx] =
+                        self.restore_rotation(self.layout.zones()[idx].area.size, &zone.screens);
+                }
+                // The zone's gone since, so do the same as if the layout changed
+                None => {
+                    let screens = zone.screens.iter().filter_map(|s| self.restore_screen(s));
+                    for screen in screens.collect::<Vec<_>>() {
+                        self.rotations[0].insert(screen, false, self.clock.as_ref());
+                    }
+                }
+            }
+        }
+        self.last_saved_rotations = Some(saved);
+        self.force_composite = true;
+
+        Ok(true)
+    }
+
+    /// Create a rotation of the given size with the given screens, leaving out any that have expired.
+    fn restore_rotation(&self, size: Size, specs: &[ScreenSpec]) -> Rotation {
+        let mut rotation = Rotation::new(size, self.default_transition);
+        for screen in specs.iter().filter_map(|s| self.restore_screen(s)) {
+            rotation.insert(screen, false, self.clock.as_ref());
+        }
+
+        rotation
+    }
+
+    /// Create the screen described, unless it's expired.
+    fn restore_screen(&self, spec: &ScreenSpec) -> Option<Box<dyn Screen<Framebuffer>>> {
+        let screen = spec.build();
+        let now = self.clock.local_now();
+        screen.expires().is_none_or(|t| t > now).then_some(screen)
+    }
+
+    /// Write the screens in every rotation to the rotation file, if there is one and they've changed.
+    fn save_rotations(&mut self) {
+        self.take_rotation_changes();
+        if !mem::take(&mut self.rotations_changed) {
+            return;
+        }
+        let Some(path) = &self.rotation_file else {
+            return;
+        };
+
+        let mut parked = self.parked_playlists.iter().collect::<Vec<_>>();
+        parked.sort_by_key(|(name, _)| *name);
+        let saved = SavedRotations {
+            zones: self
+                .layout
+                .zones()
+                .iter()
+                .zip(&self.rotations)
+                .skip(1)
+                .map(|(zone, rotation)| SavedRotation {
+                    name: zone.name.clone(),
+                    screens: rotation.specs(),
+                })
+                .collect(),
+            playlists: [(&self.active_playlist, &self.rotations[0])]
+                .into_iter()
+                .chain(parked)
+                .map(|(name, rotation)| SavedRotation {
+                    name: name.clone(),
+                    screens: rotation.specs(),
+                })
+                .collect(),
+        };
+        if self.last_saved_rotations.as_ref() == Some(&saved) {
+            return;
+        }
+
+        debug!("saving rotations to {}", path.display());
+        if let Err(e) = persist::save_json(path, &saved) {
+            warn!("couldn't save rotations to {}: {e}", path.display());
+        }
+        // Even if it failed, so we don't keep trying every frame
+        self.last_saved_rotations = Some(saved);
+    }
+
+    /// Add the given [`Screen`] to the rotation of the main zone.
+    /// If it has a higher [`screens::Priority`] than the currently displayed screen, it is displayed immediately.
+    pub fn add(&mut self, sd: Box<dyn Screen<Framebuffer>>) {
+        self.rotations[0].insert(sd, false, self.clock.as_ref());
+    }
+
+    /// Add the given [`Screen`] to the rotation of the zone with the given name.
+    /// Returns the screen back if there's no zone with that name.
+    pub fn add_to_zone(
+        &mut self,
+        zone: &str,
+        sd: Box<dyn Screen<Framebuffer>>,
+    ) -> Result<(), Box<dyn Screen<Framebuffer>>> {
+        let Some(idx) = self.layout.position(zone) else {
+            return Err(sd);
+        };
+        self.rotations[idx].insert(sd, false, self.clock.as_ref());
+
+        Ok(())
+    }
+
+    /// Swap the first screen with the same id as the given one for it, in any zone, or add it to the main zone if
+    /// there isn't one. See [`Command::Upsert`].
+    pub fn add_or_update(&mut self, sd: Box<dyn Screen<Framebuffer>>) {
+        self.upsert(sd, None);
+    }
+
+    /// Carry out a single command.
+    /// `zone` is the index of the zone it was sent to with [`Command::ToZone`], if any.
+    fn handle_command(&mut self, command: Command, zone: Option<usize>) {
+        debug!("handling command {:?}", command);
+        let clock = self.clock.as_ref();
+        let target = zone.unwrap_or(0);
+        match command {
+            Command::Add(screen) => {
+                let grab = screen.grab_attention();
+                self.rotations[target].insert(screen, grab, clock);
+            }
+            Command::Delete(id) => self.delete(&id, zone),
+            Command::Replace(screen) => {
+                self.delete(screen.id(), zone);
+                let grab = screen.grab_attention();
+                self.rotations[target].insert(screen, grab, self.clock.as_ref());
+            }
+            Command::Upsert(screen) => self.upsert(screen, zone),
+            Command::Message(id, message) => {
+                if !self.send_message(&id, &message, zone) {
+                    debug!("no screen with id {:?} understood {:?}", id, message);
+                }
+            }
+            Command::MessageOrAdd(id, message, screen) => {
+                if !self.send_message(&id, &message, zone) {
+                    debug!(
+                        "no screen with id {:?} understood {:?}, adding it",
+                        id, message
+                    );
+                    let grab = screen.grab_attention();
+                    self.rotations[target].insert(screen, grab, self.clock.as_ref());
+                }
+            }
+            Command::ClearAll => match zone {
+                Some(idx) => self.rotations[idx].clear(clock),
+                None => self.rotations.iter_mut().for_each(|r| r.clear(clock)),
+            },
+            Command::Next => self.rotations[target].next(clock),
+            Command::Previous => self.rotations[target].previous(clock),
+            Command::PauseRotation => self.rotation_paused = true,
+            Command::ResumeRotation => {
+                self.rotation_paused = false;
+                // Give the current screens their full time again
+                for rotation in &mut self.rotations {
+                    rotation.restart_timing(clock);
+                }
+            }
+            Command::SetBrightness(brightness) => self.brightness = brightness,
+            Command::SetSleepMode(mode) => self.set_sleep_mode(mode),
+            Command::SwitchPlaylist(name) => {
+                self.switch_playlist(&name);
+                self.save_playlists();
+            }
+            Command::DefinePlaylist(playlist) => self.define_playlist(playlist),
+            Command::DeletePlaylist(name) => self.delete_playlist(&name),
+            Command::Notify(toast) => self.overlay.push(toast),
+            Command::SetLayout(layout) => self.set_layout(layout),
+            Command::SetValue(topic, value) => {
+                let message = ScreenMessage::Value {
+                    topic: topic.clone(),
+                    value: value.clone(),
+                };
+                self.values.insert(topic, value);
+                for rotation in self
+                    .rotations
+                    .iter_mut()
+                    .chain(self.parked_playlists.values_mut())
+                    .chain(&mut self.sleep_rotation)
+                {
+                    rotation.broadcast(&message);
+                }
+            }
+            Command::ToZone(name, command) => match self.layout.position(&name) {
+                Some(idx) => self.handle_command(*command, Some(idx)),
+                None => warn!("ignoring command for unknown zone {:?}", name),
+            },
+            Command::QueryState(reply) => {
+                // Nothing we can do if whoever asked has gone away
+                let _ = reply.send(self.state());
+            }
+            Command::Subscribe(sender) => {
+                if sender.send(self.state()).is_ok() {
+                    self.state_subscribers.push(sender);
+                }
+            }
+        }
+    }
 
-            // Move to the next screen, possibly removing this one.
-            screen.paused(displayed_for);
-            if screen.should_remove() {
-                debug!("removing current screen");
-                self.curr_screens.pop_front();
+    /// Send a message to all screens with the given id, in the given zone or everywhere.
+    /// Returns whether any of them understood it.
+    fn send_message(&mut self, id: &str, message: &ScreenMessage, zone: Option<usize>) -> bool {
+        let mut handled = false;
+        for (i, rotation) in self.rotations.iter_mut().enumerate() {
+            if zone.is_none_or(|idx| idx == i) {
+                handled |= rotation.send_message(id, message);
+            }
+        }
+        if zone.is_none() {
+            for rotation in self.parked_playlists.values_mut() {
+                handled |= rotation.send_message(id, message);
+            }
+        }
+        handled
+    }
+
+    /// Get a snapshot of the current rotations, and other state.
+    pub fn state(&self) -> DisplayState {
+        DisplayState {
+            screens: self
+                .layout
+                .zones()
+                .iter()
+                .zip(&self.rotations)
+                .flat_map(|(zone, rotation)| rotation.screen_infos(&zone.name, self.clock.as_ref()))
+                .collect(),
+            playlist: self.active_playlist.clone(),
+            rotation_paused: self.rotation_paused,
+            brightness: self.brightness,
+            sleeping: self.sleep.load(Ordering::Relaxed),
+            draw_failures: self.draw_failures,
+            quarantined: self.quarantined.iter().cloned().collect(),
+        }
+    }
+
+    /// Remove all screens with the given id, from the given zone or from all of them.
+    fn delete(&mut self, id: &str, zone: Option<usize>) {
+        debug!("deleting screens with id {:?}", id);
+        let clock = self.clock.as_ref();
+        match zone {
+            Some(idx) => self.rotations[idx].delete(id, clock),
+            None => self
+                .rotations
+                .iter_mut()
+                .chain(self.parked_playlists.values_mut())
+                .for_each(|r| r.delete(id, clock)),
+        }
+    }
+
+    /// Swap the first screen with the same id for the given one, in place. See [`Command::Upsert`].
+    /// Unless a zone is given, screens with that id are looked for in every zone, then in inactive playlists.
+    fn upsert(&mut self, screen: Box<dyn Screen<Framebuffer>>, zone: Option<usize>) {
+        let id = screen.id().to_string();
+        let (idx, screen) = match zone {
+            Some(idx) => (idx, screen),
+            None => match self.rotations.iter().position(|r| r.contains(&id)) {
+                Some(idx) => (idx, screen),
+                None => {
+                    // It might be in a playlist that isn't active
+                    let parked = self.parked_playlists.values_mut().find(|r| r.contains(&id));
+                    let screen = match parked {
+                        Some(rotation) => match rotation.upsert(screen) {
+                            Ok(()) => return,
+                            Err(screen) => screen,
+                        },
+                        None => screen,
+                    };
+                    (0, screen)
+                }
+            },
+        };
+        if let Err(screen) = self.rotations[idx].upsert(screen) {
+            let grab = screen.grab_attention();
+            self.rotations[idx].insert(screen, grab, self.clock.as_ref());
+        }
+    }
+
+    /// Show the playlist with the given name in the main zone.
+    /// Playlists that haven't been defined are ignored, apart from [`DEFAULT_PLAYLIST`].
+    fn switch_playlist(&mut self, name: &str) {
+        if name == self.active_playlist {
+            return;
+        }
+        if name != DEFAULT_PLAYLIST && !self.playlists.iter().any(|p| p.name == name) {
+            warn!("ignoring switch to unknown playlist {:?}", name);
+            return;
+        }
+
+        debug!("switching to playlist {:?}", name);
+        let size = self.rotations[0].size();
+        let mut rotation = match self.parked_playlists.remove(name) {
+            Some(rotation) if rotation.size() == size => rotation,
+            // The layout's changed since it was last shown
+            Some(mut old) => {
+                let mut rotation = Rotation::new(size, self.default_transition);
+                for screen in old.take_screens() {
+                    rotation.insert(screen, false, self.clock.as_ref());
+                }
+                rotation
+            }
+            None => self.build_playlist(name, size),
+        };
+        // Its transition is played going into it, as well as between its screens
+        rotation.default_transition = self.playlist_transition(name);
+        rotation.take_over_from(&self.rotations[0], self.clock.as_ref());
+
+        let old = mem::replace(&mut self.rotations[0], rotation);
+        let old_name = mem::replace(&mut self.active_playlist, name.to_string());
+        self.parked_playlists.insert(old_name, old);
+    }
+
+    /// Create a new rotation of the given size, with the screens in the given playlist.
+    fn build_playlist(&self, name: &str, size: Size) -> Rotation {
+        let mut rotation = Rotation::new(size, self.playlist_transition(name));
+        for spec in self
+            .playlists
+            .iter()
+            .filter(|p| p.name == name)
+            .flat_map(|p| &p.screens)
+        {
+            rotation.insert(spec.build(), false, self.clock.as_ref());
+        }
+
+        rotation
+    }
+
+    /// Throw away the screens in the active playlist, and start it again from its definition.
+    fn rebuild_active_playlist(&mut self) {
+        let mut rotation = self.build_playlist(&self.active_playlist, self.rotations[0].size());
+        rotation.take_over_from(&self.rotations[0], self.clock.as_ref());
+        self.rotations[0] = rotation;
+    }
+
+    /// Add or replace a playlist definition.
+    /// If it's already been shown, it starts again from the new definition, losing any screens added to it since.
+    fn define_playlist(&mut self, playlist: Playlist) {
+        debug!("defining playlist {:?}", playlist.name);
+        let name = playlist.name.clone();
+        self.playlists.retain(|p| p.name != name);
+        self.playlists.push(playlist);
+
+        self.parked_playlists.remove(&name);
+        if name == self.active_playlist {
+            self.rebuild_active_playlist();
+        }
+        self.save_playlists();
+    }
+
+    /// Remove a playlist definition, and all of its screens.
+    /// If it's active, the default playlist is switched to.
+    fn delete_playlist(&mut self, name: &str) {
+        debug!("deleting playlist {:?}", name);
+        self.playlists.retain(|p| p.name != name);
+        if name == self.active_playlist {
+            if name == DEFAULT_PLAYLIST {
+                self.rebuild_active_playlist();
             } else {
-                debug!("going to next screen (len = {})", self.curr_screens.len());
-                self.curr_screens.rotate_left(1);
+                self.switch_playlist(DEFAULT_PLAYLIST);
             }
+        }
+        // Including its screens if it was just switched away from
+        self.parked_playlists.remove(name);
+        self.save_playlists();
+    }
 
-            self.last_screen_change = Some(Instant::now());
-            display.clear(Rgb888::BLACK)?;
+    /// Write playlists to the file they were loaded from, if any.
+    fn save_playlists(&self) {
+        let Some(path) = &self.playlist_file else {
+            return;
+        };
 
-            debug!("new screen: {:?}", self.curr_screens.front());
+        let saved = SavedPlaylists {
+            active: self.active_playlist.clone(),
+            playlists: self.playlists.clone(),
+        };
+        if let Err(e) = saved.save(path) {
+            warn!("couldn't save playlists to {}: {e}", path.display());
         }
+    }
 
-        // May have just removed the last screen, so check again.
-        let Some(screen) = self.curr_screens.front_mut() else {
-            // When no screens are displayed, just clear it.
-            self.last_screen_change = None;
+    /// Create the rotation shown while sleeping, for a display of the given size, or `None` if nothing is shown.
+    fn build_sleep_rotation(&self, size: Size) -> Option<Rotation> {
+        let screen: Box<dyn Screen<Framebuffer>> = match &self.sleep_mode {
+            SleepMode::Blank => return None,
+            SleepMode::Playlist(name) => {
+                // It might have scripts in it
+                let mut rotation = self.build_playlist(name, size);
+                send_values(&mut rotation, &self.values);
+                return Some(rotation);
+            }
+            SleepMode::Clock => Box::new(ClockScreen::default()),
+            SleepMode::Ambient => Box::new(AmbientScreen::new()),
+        };
+        let mut rotation = Rotation::new(size, self.default_transition);
+        rotation.insert(screen, false, self.clock.as_ref());
+
+        Some(rotation)
+    }
+
+    /// Draw a frame to the given display.
+    pub fn draw<D: DrawTarget<Color = Rgb888>>(&mut self, display: &mut D) -> Result<(), D::Error> {
+        let size = display.bounding_box().size;
+        if self.output_frame.size() != size {
+            self.output_frame = Framebuffer::new(size);
+            self.overlay_frame = Framebuffer::new(size);
+            self.force_composite = true;
+        }
+
+        while let Ok(command) = self.recv_command.try_recv() {
+            // Asking for the state doesn't change it, and values aren't part of it
+            let changes = !matches!(
+                command,
+                Command::QueryState(_) | Command::Subscribe(_) | Command::SetValue(..)
+            );
+            self.state_changed |= changes;
+            self.rotations_changed |= changes;
+            self.handle_command(command, None);
+        }
+        // This also picks up anything that changed while drawing the last frame
+        self.save_rotations();
+
+        if self.sleep.load(Ordering::Relaxed) {
+            return self.draw_sleeping(display);
+        }
+        if self.was_sleeping {
+            debug!("waking up");
+            self.was_sleeping = false;
+            self.state_changed = true;
+            self.sleep_rotation = None;
+            self.force_composite = true;
+            // Give the screens that were showing their full time again
+            for rotation in &mut self.rotations {
+                rotation.restart_timing(self.clock.as_ref());
+            }
+        }
+
+        // Let screens know if the overlay has appeared or gone away
+        let overlay_area = self.overlay.update(self.clock.now(), size);
+        if overlay_area != self.overlay_area {
+            for (zone, rotation) in self.layout.zones().iter().zip(&mut self.rotations) {
+                let inset = overlay_area
+                    .map(|a| a.intersection(&zone.area))
+                    .filter(|a| !a.is_zero_sized())
+                    .map(|a| a.translate(-zone.area.top_left));
+                rotation.set_inset(inset);
+            }
+            self.overlay_area = overlay_area;
+        }
+
+        // Update each zone, and composite any that have changed.
+        if self.force_composite {
+            let Ok(()) = self.output_frame.clear(Rgb888::BLACK);
+        }
+        let mut failures = Vec::new();
+        for (zone, rotation) in self.layout.zones().iter().zip(&mut self.rotations) {
+            let changed = rotation.update(self.clock.as_ref(), self.rotation_paused);
+            if changed || self.force_composite {
+                let Ok(()) = rotation
+                    .output()
+                    .blit_at(&mut self.output_frame, zone.area.top_left);
+            }
+            failures.extend(
+                rotation
+                    .take_failures()
+                    .into_iter()
+                    .map(|f| (zone.name.clone(), f)),
+            );
+        }
+        self.force_composite = false;
+        for (zone, failure) in failures {
+            self.record_failure(zone, failure);
+        }
+
+        self.notify_subscribers();
+
+        if self.overlay_area.is_none() {
+            return self
+                .output_frame
+                .blit_with_brightness(display, self.brightness);
+        }
+
+        // Draw the overlay on a copy, so the zones underneath don't all need redrawing when it's gone
+        self.overlay_frame
+            .pixels_mut()
+            .copy_from_slice(self.output_frame.pixels());
+        self.overlay.draw(self.clock.now(), &mut self.overlay_frame);
+        self.overlay_frame
+            .blit_with_brightness(display, self.brightness)
+    }
+
+    /// Draw whatever the sleep mode says to, instead of the zones.
+    fn draw_sleeping<D: DrawTarget<Color = Rgb888>>(
+        &mut self,
+        display: &mut D,
+    ) -> Result<(), D::Error> {
+        let size = display.bounding_box().size;
+        if !self.was_sleeping {
+            debug!("going to sleep");
+            self.was_sleeping = true;
+            self.state_changed = true;
+            self.sleep_rotation = None;
+        }
+        if self
+            .sleep_rotation
+            .as_ref()
+            .is_none_or(|r| r.size() != size)
+        {
+            self.sleep_rotation = self.build_sleep_rotation(size);
+        }
+
+        self.notify_subscribers();
+
+        let Some(rotation) = &mut self.sleep_rotation else {
             return display.clear(Rgb888::BLACK);
         };
+        rotation.update(self.clock.as_ref(), false);
+        let failures = rotation.take_failures();
+        let result = rotation
+            .output()
+            .blit_with_brightness(display, self.sleep_brightness);
+        for failure in failures {
+            self.record_failure("sleep".to_string(), failure);
+        }
+
+        result
+    }
+
+    /// Count a screen that failed to draw, and remember it for [`DisplayState::quarantined`].
+    fn record_failure(&mut self, zone: String, failure: ScreenFailure) {
+        self.draw_failures += 1;
+        self.state_changed = true;
+        error!(
+            "screen {:?} ({}) in zone {:?} panicked while drawing, so it's been removed: {} ({} failures so far)",
+            failure.id, failure.kind, zone, failure.reason, self.draw_failures
+        );
+        if self.quarantined.len() == MAX_QUARANTINED {
+            self.quarantined.pop_front();
+        }
+        self.quarantined.push_back(QuarantinedScreen {
+            id: failure.id,
+            kind: failure.kind,
+            zone,
+            reason: failure.reason,
+        });
+    }
+
+    /// Note which parts of the state need updating if any of the rotations have changed, and send the values to any
+    /// screens that might have been added since they came in.
+    fn take_rotation_changes(&mut self) {
+        for rotation in &mut self.rotations {
+            if rotation.take_changed() {
+                self.state_changed = true;
+                self.rotations_changed = true;
+                send_values(rotation, &self.values);
+            }
+        }
+    }
+
+    /// Send the current state to anyone subscribed, if it might have changed since we last did.
+    /// Building it asks every screen how long it's shown for, so it's only done after a command or a screen change.
+    fn notify_subscribers(&mut self) {
+        self.take_rotation_changes();
+        if !mem::take(&mut self.state_changed) || self.state_subscribers.is_empty() {
+            return;
+        }
+
+        let state = self.state();
+        self.state_subscribers
+            .retain(|sub| sub.send(state.clone()).is_ok());
+    }
+}
 
-        // Finally, draw the current screen.
-        screen.draw(display)
+/// Send every screen in the rotation the last message on each MQTT topic. See [`Command::SetValue`].
+fn send_values(rotation: &mut Rotation, values: &HashMap<String, String>) {
+    for (topic, value) in values {
+        rotation.broadcast(&ScreenMessage::Value {
+            topic: topic.clone(),
+            value: value.clone(),
+        });
     }
 }
@@ -0,0 +1,133 @@
+//! Short notifications drawn over the top of whatever's being shown.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use embedded_graphics::{
+    mono_font::{ascii::FONT_6X10, MonoTextStyle},
+    pixelcolor::Rgb888,
+    prelude::*,
+    primitives::{PrimitiveStyle, Rectangle},
+    text::{Alignment, Baseline, Text, TextStyleBuilder},
+};
+
+use crate::framebuffer::Framebuffer;
+
+/// How tall the band a toast is shown in is.
+const BAND_HEIGHT: u32 = 11;
+
+/// How long a toast takes to slide in, and to slide back out.
+const SLIDE_DURATION: Duration = Duration::from_millis(250);
+
+/// A short notification, like "Door opened", shown in a band along the bottom of the display.
+/// It doesn't interrupt the rotation underneath. Text that doesn't fit in the band is cut off.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Toast {
+    text: String,
+    colour: Rgb888,
+    background: Rgb888,
+    /// How long it's shown for, not counting sliding in and out.
+    duration: Duration,
+}
+
+impl Toast {
+    /// Show the given text, in white on blue for 3 seconds.
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into().replace("\n", " "),
+            colour: Rgb888::WHITE,
+            background: Rgb888::new(0, 0, 96),
+            duration: Duration::from_secs(3),
+        }
+    }
+
+    /// Use the given text and background colours.
+    pub fn with_colours(mut self, colour: Rgb888, background: Rgb888) -> Self {
+        self.colour = colour;
+        self.background = background;
+        self
+    }
+
+    /// Show the toast for the given amount of time, not counting sliding in and out.
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// How long the toast is on the display in total, including sliding in and out.
+    fn total_duration(&self) -> Duration {
+        self.duration + SLIDE_DURATION * 2
+    }
+}
+
+/// Shows queued [`Toast`]s one after the other.
+#[derive(Debug, Default)]
+pub(crate) struct Overlay {
+    queue: VecDeque<Toast>,
+    /// The toast currently being shown, and when it started sliding in.
+    current: Option<(Toast, Instant)>,
+}
+
+impl Overlay {
+    /// Show the given toast once all the ones before it are finished.
+    pub fn push(&mut self, toast: Toast) {
+        self.queue.push_back(toast);
+    }
+
+    /// Move on to the next toast if the current one is finished.
+    /// Returns the area covered by the toast on a display of the given size, if one is being shown.
+    pub fn update(&mut self, now: Instant, display_size: Size) -> Option<Rectangle> {
+        if self
+            .current
+            .as_ref()
+            .is_some_and(|(toast, started)| now - *started >= toast.total_duration())
+        {
+            self.current = None;
+        }
+        if self.current.is_none() {
+            self.current = self.queue.pop_front().map(|toast| (toast, now));
+        }
+
+        self.current.as_ref().map(|_| band(display_size))
+    }
+
+    /// Draw the current toast, if any, onto the frame.
+    pub fn draw(&self, now: Instant, frame: &mut Framebuffer) {
+        let Some((toast, started)) = &self.current else {
+            return;
+        };
+
+        // Slide in from the bottom, then back out again
+        let elapsed = now - *started;
+        let time_to_end = toast.total_duration().saturating_sub(elapsed);
+        let shown = elapsed.min(time_to_end).min(SLIDE_DURATION);
+        let area = band(frame.size());
+        let height = area.size.height as f32;
+        let visible_height = height * shown.div_duration_f32(SLIDE_DURATION);
+        let area = area.translate(Point::new(0, (height - visible_height) as i32));
+        let Ok(()) = area
+            .into_styled(PrimitiveStyle::with_fill(toast.background))
+            .draw(frame);
+        let Ok(_) = Text::with_text_style(
+            &toast.text,
+            area.center(),
+            MonoTextStyle::new(&FONT_6X10, toast.colour),
+            TextStyleBuilder::new()
+                .alignment(Alignment::Center)
+                .baseline(Baseline::Middle)
+                .build(),
+        )
+        .draw(frame);
+    }
+}
+
+/// The band toasts are shown in, along the bottom of a display of the given size.
+fn band(display_size: Size) -> Rectangle {
+    let height = BAND_HEIGHT.min(display_size.height);
+    Rectangle::new(
+        Point::new(0, (display_size.height - height) as i32),
+        Size::new(display_size.width, height),
+    )
+}
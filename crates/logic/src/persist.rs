@@ -0,0 +1,46 @@
+//! Keeping things in JSON files across restarts.
+
+use std::{fs, io, path::Path};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::screens::ScreenSpec;
+
+/// Read a value from the given JSON file, or `None` if it doesn't exist yet.
+pub(crate) fn load_json<T: DeserializeOwned>(path: &Path) -> io::Result<Option<T>> {
+    let json = match fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    serde_json::from_str(&json)
+        .map(Some)
+        .map_err(io::Error::other)
+}
+
+/// Write a value to the given JSON file, replacing it.
+pub(crate) fn save_json<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    // Write somewhere else first, so we don't leave a half-written file if something goes wrong
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, serde_json::to_string_pretty(value)?)?;
+    fs::rename(tmp_path, path)
+}
+
+/// The screens in one rotation, in order starting with the one being shown.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct SavedRotation {
+    /// The name of the zone or playlist it belongs to.
+    pub name: String,
+    pub screens: Vec<ScreenSpec>,
+}
+
+/// Every rotation's screens, kept across restarts.
+/// Screens without a [`crate::screens::Screen::spec`] are left out.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct SavedRotations {
+    /// Each zone apart from the main one.
+    pub zones: Vec<SavedRotation>,
+    /// The main zone for each playlist that's been shown, including the active one.
+    pub playlists: Vec<SavedRotation>,
+}
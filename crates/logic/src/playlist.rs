@@ -0,0 +1,67 @@
+//! Named sets of screens for the main zone, which can be switched between.
+
+use std::{io, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    persist::{load_json, save_json},
+    screens::ScreenSpec,
+    transition::Transition,
+};
+
+/// The playlist that's active if no other has been chosen.
+/// It doesn't need defining: if it isn't, it starts off empty.
+pub const DEFAULT_PLAYLIST: &str = "default";
+
+/// A named list of screens to show in the main zone, like `open-night` or `maintenance`.
+///
+/// Only one playlist is active at a time. Screens added while it's active join it, and stay in it when another is
+/// switched to, so it carries on where it left off when switched back.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Playlist {
+    pub name: String,
+    /// The screens it starts off with, in order.
+    #[serde(default)]
+    pub screens: Vec<ScreenSpec>,
+    /// Used for screens in it that don't have their own, instead of the display's default. It's also played when
+    /// it's switched to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transition: Option<Transition>,
+}
+
+impl Playlist {
+    pub fn new(name: impl Into<String>, screens: Vec<ScreenSpec>) -> Self {
+        Self {
+            name: name.into(),
+            screens,
+            transition: None,
+        }
+    }
+
+    /// Use the given transition instead of the display's default. See [`Self::transition`].
+    pub fn with_transition(mut self, transition: Transition) -> Self {
+        self.transition = Some(transition);
+        self
+    }
+}
+
+/// Everything about playlists that's kept across restarts.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct SavedPlaylists {
+    /// The name of the active playlist.
+    pub active: String,
+    pub playlists: Vec<Playlist>,
+}
+
+impl SavedPlaylists {
+    /// Read saved playlists from the given file, or `None` if it doesn't exist yet.
+    pub fn load(path: &Path) -> io::Result<Option<Self>> {
+        load_json(path)
+    }
+
+    /// Write the playlists to the given file, replacing it.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        save_json(path, self)
+    }
+}
@@ -0,0 +1,560 @@
+//! A rotation of screens, shown in a single zone of the display.
+
+use std::{
+    any::Any,
+    collections::VecDeque,
+    mem,
+    panic::{self, AssertUnwindSafe},
+    time::Instant,
+};
+
+use chrono::NaiveDateTime;
+use embedded_graphics::{
+    pixelcolor::Rgb888,
+    prelude::{DrawTarget, OriginDimensions, RgbColor, Size},
+    primitives::Rectangle,
+};
+use log::{debug, error};
+
+use crate::{
+    clock::Clock,
+    framebuffer::Framebuffer,
+    screens::{Screen, ScreenMessage, ScreenSpec},
+    transition::Transition,
+    ScreenInfo,
+};
+
+/// A screen that was removed from a [`Rotation`] because it panicked while drawing.
+#[derive(Debug)]
+pub(crate) struct ScreenFailure {
+    pub id: String,
+    pub kind: String,
+    pub reason: String,
+}
+
+/// The screens shown in one [`crate::layout::Zone`], and everything needed to rotate between them.
+/// See [`crate::DisplayLogic`] for how screens are chosen.
+pub(crate) struct Rotation {
+    /// The current list of screens
+    screens: VecDeque<Box<dyn Screen<Framebuffer>>>,
+
+    /// The last time the active screen was changed
+    last_screen_change: Option<Instant>,
+
+    /// The transition used for screens that don't specify their own.
+    pub default_transition: Transition,
+    /// The transition currently playing, and when it started.
+    curr_transition: Option<(Transition, Instant)>,
+
+    /// What the current screen has drawn.
+    screen_frame: Framebuffer,
+    /// If true, the current screen is drawn on the next frame even if it says nothing has changed.
+    force_redraw: bool,
+    /// The part of the zone covered by an overlay, if any. See [`Screen::set_inset`].
+    inset: Option<Rectangle>,
+    /// The current screen's frame with any transition applied, ready to go on the display.
+    output_frame: Framebuffer,
+    /// The last frame output before the current transition started.
+    outgoing_frame: Framebuffer,
+
+    /// Screens removed for failing to draw since [`Self::take_failures`] was last called.
+    failures: Vec<ScreenFailure>,
+    /// If true, the screens or which one is at the front have changed since [`Self::take_changed`] was last called.
+    changed: bool,
+}
+
+impl Rotation {
+    /// Create an empty rotation, drawing screens at the given size.
+    pub fn new(size: Size, default_transition: Transition) -> Self {
+        Self {
+            screens: VecDeque::new(),
+            last_screen_change: None,
+            default_transition,
+            curr_transition: None,
+            screen_frame: Framebuffer::new(size),
+            force_redraw: true,
+            inset: None,
+            output_frame: Framebuffer::new(size),
+            outgoing_frame: Framebuffer::new(size),
+            failures: Vec::new(),
+            changed: true,
+        }
+    }
+
+    /// The size of the zone the screens are drawn in.
+    pub fn size(&self) -> Size {
+        self.screen_frame.size()
+    }
+
+    /// Start showing this rotation in place of another one, in the same zone.
+    /// A transition is played from whatever the other one was last showing.
+    pub fn take_over_from(&mut self, previous: &Rotation, clock: &dyn Clock) {
+        self.output_frame
+            .pixels_mut()
+            .copy_from_slice(previous.output_frame.pixels());
+        self.inset = previous.inset;
+        self.skip_to_highest_priority(clock.local_now());
+        self.screen_changed(clock);
+    }
+
+    /// The last frame output by [`Self::update`].
+    pub fn output(&self) -> &Framebuffer {
+        &self.output_frame
+    }
+
+    /// Whether any screen in the rotation has the given id.
+    pub fn contains(&self, id: &str) -> bool {
+        self.screens.iter().any(|s| s.id() == id)
+    }
+
+    /// Remove and return all screens, in rotation order.
+    pub fn take_screens(&mut self) -> VecDeque<Box<dyn Screen<Framebuffer>>> {
+        self.last_screen_change = None;
+        self.changed = true;
+        std::mem::take(&mut self.screens)
+    }
+
+    /// A description of each screen that has one, starting with the one at the front. See [`Screen::spec`].
+    pub fn specs(&self) -> Vec<ScreenSpec> {
+        self.screens.iter().filter_map(|s| s.spec()).collect()
+    }
+
+    /// Information about each screen, starting with the one at the front.
+    pub fn screen_infos(&self, zone: &str, clock: &dyn Clock) -> Vec<ScreenInfo> {
+        let now = clock.local_now();
+        let showing = self.front_scheduled(now);
+        self.screens
+            .iter()
+            .enumerate()
+            .map(|(position, s)| {
+                let display_duration = s.single_display_duration(&self.screen_frame);
+                ScreenInfo {
+                    id: s.id().to_string(),
+                    kind: s.kind().to_string(),
+                    zone: zone.to_string(),
+                    position,
+                    priority: s.priority(),
+                    scheduled: is_scheduled(s.as_ref(), now),
+                    display_duration,
+                    time_left: match self.last_screen_change {
+                        Some(t) if position == 0 && showing => {
+                            Some(display_duration.saturating_sub(clock.now() - t))
+                        }
+                        _ => None,
+                    },
+                    remaining_shows: s.remaining_shows(),
+                }
+            })
+            .collect()
+    }
+
+    /// Tell screens which part of the zone is covered by an overlay, if any.
+    pub fn set_inset(&mut self, inset: Option<Rectangle>) {
+        if inset != self.inset {
+            self.inset = inset;
+            self.force_redraw = true;
+        }
+    }
+
+    /// Give the current screen its full display time again.
+    pub fn restart_timing(&mut self, clock: &dyn Clock) {
+        if self.last_screen_change.is_some() {
+            self.last_screen_change = Some(clock.now());
+        }
+    }
+
+    /// Remove every screen.
+    pub fn clear(&mut self, clock: &dyn Clock) {
+        self.screens.clear();
+        self.screen_changed(clock);
+    }
+
+    /// Remove all screens with the given id.
+    pub fn delete(&mut self, id: &str, clock: &dyn Clock) {
+        let deleted_front = self.screens.front().is_some_and(|s| s.id() == id);
+        self.screens.retain(|s| s.id() != id);
+        self.changed = true;
+        if deleted_front {
+            // Lower priority screens may have been waiting on the one we just deleted
+            self.skip_to_highest_priority(clock.local_now());
+            self.screen_changed(clock);
+        }
+    }
+
+    /// Swap the first screen with the same id for the given one, in place. See [`crate::Command::Upsert`].
+    /// If there's no screen with that id, the screen is given back.
+    pub fn upsert(
+        &mut self,
+        screen: Box<dyn Screen<Framebuffer>>,
+    ) -> Result<(), Box<dyn Screen<Framebuffer>>> {
+        let id = screen.id().to_string();
+        let Some(idx) = self.screens.iter().position(|s| s.id() == id) else {
+            return Err(screen);
+        };
+
+        debug!("replacing screen with id {:?} in place", id);
+        self.screens[idx] = screen;
+        self.changed = true;
+        if idx == 0 {
+            // The new screen hasn't drawn anything yet, but keeps the time the old one had left
+            self.redraw_front();
+        }
+
+        let mut i = 0;
+        self.screens.retain(|s| {
+            let keep = i == idx || s.id() != id;
+            i += 1;
+            keep
+        });
+
+        Ok(())
+    }
+
+    /// Screens removed for failing to draw since this was last called.
+    pub fn take_failures(&mut self) -> Vec<ScreenFailure> {
+        mem::take(&mut self.failures)
+    }
+
+    /// Whether the screens or which one is at the front have changed since this was last called.
+    pub fn take_changed(&mut self) -> bool {
+        mem::take(&mut self.changed)
+    }
+
+    /// Remove the current screen because it panicked or failed, and move on to the next.
+    fn quarantine_front(&mut self, reason: String, clock: &dyn Clock) {
+        let Some(screen) = self.screens.pop_front() else {
+            return;
+        };
+
+        debug!("removing screen {:?} after it failed", screen.id());
+        self.failures.push(ScreenFailure {
+            id: screen.id().to_string(),
+            kind: screen.kind().to_string(),
+            reason,
+        });
+        // Dropping it might panic too
+        if panic::catch_unwind(AssertUnwindSafe(move || drop(screen))).is_err() {
+            error!("screen panicked again while being removed");
+        }
+
+        self.skip_to_highest_priority(clock.local_now());
+        self.screen_changed(clock);
+    }
+
+    /// Remove any screens that have passed their [`Screen::expires`] time.
+    fn remove_expired(&mut self, clock: &dyn Clock) {
+        let now = clock.local_now();
+        let expired = |s: &dyn Screen<Framebuffer>| s.expires().is_some_and(|t| t <= now);
+        if !self.screens.iter().any(|s| expired(s.as_ref())) {
+            return;
+        }
+
+        debug!("removing expired screens");
+        let expired_front = self.screens.front().is_some_and(|s| expired(s.as_ref()));
+        self.screens.retain(|s| !expired(s.as_ref()));
+        self.changed = true;
+        if expired_front {
+            self.skip_to_highest_priority(now);
+            self.screen_changed(clock);
+        }
+    }
+
+    /// Send the message to all screens with the given id.
+    /// Returns true if any of them understood it.
+    pub fn send_message(&mut self, id: &str, message: &ScreenMessage) -> bool {
+        let mut handled = false;
+        for screen in self.screens.iter_mut().filter(|s| s.id() == id) {
+            handled |= screen.handle_message(message);
+        }
+
+        handled
+    }
+
+    /// Send the message to every screen, whether or not they understand it.
+    pub fn broadcast(&mut self, message: &ScreenMessage) {
+        for screen in &mut self.screens {
+            screen.handle_message(message);
+        }
+    }
+
+    /// Tell the current screen it's no longer being displayed, and remove it if it wants.
+    /// Returns true if it was removed.
+    fn pause_front(&mut self, clock: &dyn Clock) -> bool {
+        if !self.front_scheduled(clock.local_now()) {
+            return false;
+        }
+
+        let displayed_for = self
+            .last_screen_change
+            .map(|t| clock.now() - t)
+            .unwrap_or_default();
+        let Some(screen) = self.screens.front_mut() else {
+            return false;
+        };
+
+        screen.paused(displayed_for);
+        if screen.should_remove() {
+            debug!("removing current screen");
+            self.screens.pop_front();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Move on to the next screen, possibly removing the current one.
+    pub fn next(&mut self, clock: &dyn Clock) {
+        if !self.pause_front(clock) {
+            debug!("going to next screen (len = {})", self.screens.len());
+            self.screens.rotate_left(1.min(self.screens.len()));
+        }
+        self.skip_to_highest_priority(clock.local_now());
+        self.screen_changed(clock);
+
+        debug!("new screen: {:?}", self.screens.front());
+    }
+
+    /// Go back to the previous screen that can be shown, possibly removing the current one.
+    pub fn previous(&mut self, clock: &dyn Clock) {
+        self.pause_front(clock);
+
+        let now = clock.local_now();
+        let Some(highest) = self
+            .screens
+            .iter()
+            .filter(|s| is_scheduled(s.as_ref(), now))
+            .map(|s| s.priority())
+            .max()
+        else {
+            return;
+        };
+        let idx = self
+            .screens
+            .iter()
+            .rposition(|s| is_scheduled(s.as_ref(), now) && s.priority() == highest)
+            .unwrap();
+        self.screens.rotate_right(self.screens.len() - idx);
+        self.screen_changed(clock);
+
+        debug!("new screen: {:?}", self.screens.front());
+    }
+
+    /// Add the given screen to the rotation, moving it to the front if it outranks the current screen,
+    /// or if `grab` is set and it has the same priority.
+    pub fn insert(&mut self, sd: Box<dyn Screen<Framebuffer>>, grab: bool, clock: &dyn Clock) {
+        let now = clock.local_now();
+        let priority = sd.priority();
+        let scheduled = is_scheduled(sd.as_ref(), now);
+        self.screens.push_back(sd);
+        self.changed = true;
+        if !scheduled {
+            return;
+        }
+        if self.screens.len() == 1 {
+            // It's the only screen, so it's on the display now
+            self.screen_changed(clock);
+            return;
+        }
+
+        if !self.front_scheduled(now) {
+            // Nothing is being shown right now, so show this
+            self.screens.rotate_right(1);
+            self.screen_changed(clock);
+            return;
+        }
+
+        let curr_priority = self.screens.front().unwrap().priority();
+        if priority < curr_priority {
+            return;
+        }
+
+        if priority > curr_priority {
+            debug!("preempting {:?} priority screen", curr_priority);
+            self.screens.front_mut().unwrap().preempted();
+        } else if !grab {
+            return;
+        }
+
+        self.screens.rotate_right(1);
+        self.screen_changed(clock);
+    }
+
+    /// Reset timing and start a transition, after the front screen has changed.
+    fn screen_changed(&mut self, clock: &dyn Clock) {
+        let now = clock.now();
+        self.last_screen_change = Some(now);
+        self.changed = true;
+
+        let transition = self
+            .screens
+            .front()
+            .and_then(|s| s.transition())
+            .unwrap_or(self.default_transition);
+        self.outgoing_frame
+            .pixels_mut()
+            .copy_from_slice(self.output_frame.pixels());
+        self.curr_transition = Some((transition, now));
+
+        self.redraw_front();
+    }
+
+    /// Make sure the front screen is drawn on the next frame, from a blank display.
+    fn redraw_front(&mut self) {
+        // Screens expect to start off with a blank display
+        let Ok(()) = self.screen_frame.clear(Rgb888::BLACK);
+        self.force_redraw = true;
+    }
+
+    /// Rotate forwards until the front screen has the highest priority out of all currently scheduled screens.
+    /// If no screens are scheduled, nothing changes.
+    fn skip_to_highest_priority(&mut self, now: NaiveDateTime) {
+        let Some(highest) = self
+            .screens
+            .iter()
+            .filter(|s| is_scheduled(s.as_ref(), now))
+            .map(|s| s.priority())
+            .max()
+        else {
+            return;
+        };
+        let idx = self
+            .screens
+            .iter()
+            .position(|s| is_scheduled(s.as_ref(), now) && s.priority() == highest)
+            .unwrap();
+        self.screens.rotate_left(idx);
+    }
+
+    /// Whether there's a screen at the front of the rotation, and it's currently scheduled to be shown.
+    fn front_scheduled(&self, now: NaiveDateTime) -> bool {
+        self.screens
+            .front()
+            .is_some_and(|s| is_scheduled(s.as_ref(), now))
+    }
+
+    /// Move the rotation on if needed, then draw the current screen and any transition to [`Self::output`].
+    /// Returns true if the output has changed.
+    pub fn update(&mut self, clock: &dyn Clock, rotation_paused: bool) -> bool {
+        self.remove_expired(clock);
+
+        // Screens may have come in or out of their schedules since the last frame.
+        let now = clock.local_now();
+        if !self.screens.is_empty() && !self.front_scheduled(now) {
+            let was_showing = self.last_screen_change.is_some();
+            self.skip_to_highest_priority(now);
+            if was_showing || self.front_scheduled(now) {
+                self.screen_changed(clock);
+            }
+        } else if self.front_scheduled(now) && self.last_screen_change.is_none() {
+            // Nothing was being shown, and now something is.
+            self.screen_changed(clock);
+        }
+
+        // See if we need to move on to the next screen, and/or remove this screen.
+        if self.front_scheduled(now) && !rotation_paused {
+            let screen = self.screens.front().unwrap();
+            let instant_now = clock.now();
+            let last_screen_change = self.last_screen_change.get_or_insert(instant_now);
+            let displayed_for = instant_now - *last_screen_change;
+            let single_display_duration = screen.single_display_duration(&self.screen_frame);
+            if displayed_for >= single_display_duration {
+                debug!(
+                    "screen displayed for {:?} out of {:?}",
+                    displayed_for, single_display_duration
+                );
+                self.next(clock);
+            }
+        }
+
+        // Draw the current screen, or clear it if there's none.
+        // If nothing has changed, the last frame is reused.
+        // A screen that panics or reports a failure is taken out of the rotation, rather than bringing everything else
+        // down with it.
+        let mut failure = None;
+        let redraw = match self.screens.front_mut() {
+            Some(screen) if is_scheduled(screen.as_ref(), now) => {
+                let (force_redraw, inset, frame) =
+                    (self.force_redraw, self.inset, &mut self.screen_frame);
+                let drawn = panic::catch_unwind(AssertUnwindSafe(|| {
+                    if force_redraw {
+                        screen.set_inset(inset);
+                    }
+                    let redraw = force_redraw || screen.needs_redraw(clock);
+                    if redraw {
+                        let Ok(()) = screen.draw(frame, clock);
+                    }
+                    (redraw, screen.failure())
+                }));
+                match drawn {
+                    Ok((redraw, reason)) => {
+                        failure = reason;
+                        redraw
+                    }
+                    Err(payload) => {
+                        failure = Some(panic_message(payload.as_ref()));
+                        true
+                    }
+                }
+            }
+            _ => {
+                self.last_screen_change = None;
+                if self.force_redraw {
+                    let Ok(()) = self.screen_frame.clear(Rgb888::BLACK);
+                }
+                self.force_redraw
+            }
+        };
+        self.force_redraw = false;
+        if let Some(reason) = failure {
+            // Shows a blank frame until the next screen is drawn
+            self.quarantine_front(reason, clock);
+        }
+
+        // Apply any transition that's in progress.
+        let transition_progress = self
+            .curr_transition
+            .map(|(transition, started)| (transition, transition.progress(clock.now() - started)));
+        match transition_progress {
+            Some((transition, progress)) if progress < 1.0 => {
+                transition.blend(
+                    progress,
+                    &self.outgoing_frame,
+                    &self.screen_frame,
+                    &mut self.output_frame,
+                );
+                true
+            }
+            Some(_) => {
+                // Transition just finished, so make sure we end up on the screen's frame
+                self.curr_transition = None;
+                self.output_frame
+                    .pixels_mut()
+                    .copy_from_slice(self.screen_frame.pixels());
+                true
+            }
+            None if redraw => {
+                self.output_frame
+                    .pixels_mut()
+                    .copy_from_slice(self.screen_frame.pixels());
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Get the message out of a caught panic, if it has one.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// Whether the given screen is allowed to be shown at the given time.
+fn is_scheduled(screen: &dyn Screen<Framebuffer>, now: NaiveDateTime) -> bool {
+    screen.schedule().is_none_or(|s| s.is_active(now))
+}
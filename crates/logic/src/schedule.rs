@@ -0,0 +1,282 @@
+//! Restricting screens to only be shown at certain times.
+
+use std::{fmt, str::FromStr, time::Duration};
+
+use chrono::{Datelike, NaiveDateTime, NaiveTime, Timelike, Weekday};
+use embedded_graphics::{pixelcolor::Rgb888, prelude::*, primitives::Rectangle};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    clock::Clock,
+    screens::{Priority, Screen, ScreenMessage, ScreenSpec},
+    transition::Transition,
+};
+
+/// When a screen is allowed to be shown: a set of days, and optionally a time window on each of those days.
+///
+/// Can be parsed from strings like `"weekdays 18:00-23:00"`, `"tue"`, `"mon,thu 18:00-23:00"`, `"sat-sun"` or
+/// `"22:00-02:00"`. Days can be given as three letter names, ranges of them, or `daily`, `weekdays` and `weekends`.
+/// A window that ends before it starts runs past midnight, and belongs to the day it starts on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Schedule {
+    /// Bit `n` is set if the schedule applies `n` days from monday.
+    days: u8,
+
+    /// The start and end of the time window, if any.
+    window: Option<(NaiveTime, NaiveTime)>,
+}
+
+const ALL_DAYS: u8 = 0b111_1111;
+
+impl Schedule {
+    /// A schedule that is always active.
+    pub const fn always() -> Self {
+        Self {
+            days: ALL_DAYS,
+            window: None,
+        }
+    }
+
+    /// A schedule that is active all day on the given days.
+    pub fn on_days(days: impl IntoIterator<Item = Weekday>) -> Self {
+        Self {
+            days: days
+                .into_iter()
+                .fold(0, |acc, d| acc | 1 << d.num_days_from_monday()),
+            window: None,
+        }
+    }
+
+    /// Restrict this schedule to between `start` and `end` on each of its days.
+    pub fn between(self, start: NaiveTime, end: NaiveTime) -> Self {
+        Self {
+            window: Some((start, end)),
+            ..self
+        }
+    }
+
+    /// Whether the schedule applies on the given day.
+    fn on_day(&self, day: Weekday) -> bool {
+        self.days & (1 << day.num_days_from_monday()) != 0
+    }
+
+    /// Whether the schedule is active at the given local time.
+    pub fn is_active(&self, at: NaiveDateTime) -> bool {
+        let (day, time) = (at.weekday(), at.time());
+        match self.window {
+            None => self.on_day(day),
+            Some((start, end)) if start < end => self.on_day(day) && start <= time && time < end,
+            // Window runs past midnight
+            Some((start, end)) => {
+                (self.on_day(day) && time >= start) || (self.on_day(day.pred()) && time < end)
+            }
+        }
+    }
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Self::always()
+    }
+}
+
+string_format!(Schedule, ParseScheduleError, "schedule");
+
+impl FromStr for Schedule {
+    type Err = ParseScheduleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut schedule = Schedule {
+            days: 0,
+            window: None,
+        };
+        for part in s.split_whitespace() {
+            if part.contains(':') {
+                if schedule.window.is_some() {
+                    return Err(ParseScheduleError(format!(
+                        "more than one time window in {s:?}"
+                    )));
+                }
+                schedule.window = Some(parse_window(part)?);
+            } else {
+                for days in part.split(',').filter(|d| !d.is_empty()) {
+                    schedule.days |= parse_days(days)?;
+                }
+            }
+        }
+
+        // Only a time window given, so it applies every day
+        if schedule.days == 0 {
+            schedule.days = ALL_DAYS;
+        }
+
+        Ok(schedule)
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let days = (0..7)
+            .map(|n| Weekday::try_from(n as u8).unwrap())
+            .filter(|d| self.on_day(*d))
+            .map(|d| d.to_string().to_lowercase())
+            .collect::<Vec<_>>();
+        if self.days == ALL_DAYS {
+            write!(f, "daily")?;
+        } else {
+            write!(f, "{}", days.join(","))?;
+        }
+
+        if let Some((start, end)) = self.window {
+            write!(
+                f,
+                " {:02}:{:02}-{:02}:{:02}",
+                start.hour(),
+                start.minute(),
+                end.hour(),
+                end.minute()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parse a single day, a range of days like `mon-fri`, or one of the named groups of days.
+fn parse_days(s: &str) -> Result<u8, ParseScheduleError> {
+    match s.to_lowercase().as_str() {
+        "daily" | "everyday" => return Ok(ALL_DAYS),
+        "weekdays" => return Ok(0b001_1111),
+        "weekends" => return Ok(0b110_0000),
+        _ => (),
+    }
+
+    let parse_day = |d: &str| {
+        Weekday::from_str(d).map_err(|_| ParseScheduleError(format!("unknown day {d:?}")))
+    };
+    match s.split_once('-') {
+        None => Ok(1 << parse_day(s)?.num_days_from_monday()),
+        Some((from, to)) => {
+            let (mut day, to) = (parse_day(from)?, parse_day(to)?);
+            let mut days = 1 << day.num_days_from_monday();
+            while day != to {
+                day = day.succ();
+                days |= 1 << day.num_days_from_monday();
+            }
+            Ok(days)
+        }
+    }
+}
+
+/// Parse a time window like `18:00-23:00`.
+fn parse_window(s: &str) -> Result<(NaiveTime, NaiveTime), ParseScheduleError> {
+    let parse_time = |t: &str| {
+        NaiveTime::parse_from_str(t, "%H:%M")
+            .map_err(|_| ParseScheduleError(format!("invalid time {t:?}")))
+    };
+    let (start, end) = s.split_once('-').ok_or_else(|| {
+        ParseScheduleError(format!("time window {s:?} should look like 18:00-23:00"))
+    })?;
+
+    Ok((parse_time(start)?, parse_time(end)?))
+}
+
+/// Wraps any screen so that it's only shown while the given [`Schedule`] is active.
+/// Outside of that, it stays in the rotation but is skipped over.
+pub struct Scheduled<D: DrawTarget<Color = Rgb888>> {
+    inner: Box<dyn Screen<D>>,
+    schedule: Schedule,
+}
+
+impl<D: DrawTarget<Color = Rgb888>> fmt::Debug for Scheduled<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scheduled")
+            .field("inner", &self.inner)
+            .field("schedule", &self.schedule.to_string())
+            .finish()
+    }
+}
+
+impl<D: DrawTarget<Color = Rgb888>> Scheduled<D> {
+    pub fn new(inner: Box<dyn Screen<D>>, schedule: Schedule) -> Self {
+        Self { inner, schedule }
+    }
+}
+
+impl<D: DrawTarget<Color = Rgb888>> Screen<D> for Scheduled<D> {
+    fn draw(&mut self, display: &mut D, clock: &dyn Clock) -> Result<(), D::Error> {
+        self.inner.draw(display, clock)
+    }
+
+    fn needs_redraw(&self, clock: &dyn Clock) -> bool {
+        self.inner.needs_redraw(clock)
+    }
+
+    fn single_display_duration(&self, display: &D) -> Duration {
+        self.inner.single_display_duration(display)
+    }
+
+    fn paused(&mut self, for_dur: Duration) {
+        self.inner.paused(for_dur)
+    }
+
+    fn should_remove(&self) -> bool {
+        self.inner.should_remove()
+    }
+
+    fn id(&self) -> &str {
+        self.inner.id()
+    }
+
+    fn kind(&self) -> &str {
+        self.inner.kind()
+    }
+
+    fn remaining_shows(&self) -> Option<u32> {
+        self.inner.remaining_shows()
+    }
+
+    fn grab_attention(&self) -> bool {
+        self.inner.grab_attention()
+    }
+
+    fn priority(&self) -> Priority {
+        self.inner.priority()
+    }
+
+    fn transition(&self) -> Option<Transition> {
+        self.inner.transition()
+    }
+
+    fn preempted(&mut self) {
+        self.inner.preempted()
+    }
+
+    fn schedule(&self) -> Option<&Schedule> {
+        Some(&self.schedule)
+    }
+
+    fn handle_message(&mut self, message: &ScreenMessage) -> bool {
+        self.inner.handle_message(message)
+    }
+
+    fn set_inset(&mut self, inset: Option<Rectangle>) {
+        self.inner.set_inset(inset)
+    }
+
+    fn spec(&self) -> Option<ScreenSpec> {
+        Some(ScreenSpec::Scheduled {
+            schedule: self.schedule,
+            screen: Box::new(self.inner.spec()?),
+        })
+    }
+
+    fn expires(&self) -> Option<NaiveDateTime> {
+        self.inner.expires()
+    }
+
+    fn failure(&self) -> Option<String> {
+        self.inner.failure()
+    }
+}
@@ -0,0 +1,83 @@
+use std::time::{Duration, Instant};
+
+use embedded_graphics::{pixelcolor::Rgb888, prelude::*};
+
+use crate::clock::Clock;
+
+use super::{Screen, ScreenSpec};
+
+/// How often the animation moves on. It's slow enough that there's no point drawing more often.
+const FRAME_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long it takes to go through every colour.
+const HUE_PERIOD: Duration = Duration::from_secs(600);
+
+/// A slow, gentle animation of drifting colours, for when nobody's around to read anything.
+#[derive(Debug, Default)]
+pub struct AmbientScreen {
+    /// When the animation started, so it carries on smoothly.
+    started: Option<Instant>,
+    /// When the last frame was drawn.
+    last_drawn: Option<Instant>,
+}
+
+impl AmbientScreen {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<D: DrawTarget<Color = Rgb888>> Screen<D> for AmbientScreen {
+    fn draw(&mut self, display: &mut D, clock: &dyn Clock) -> Result<(), D::Error> {
+        let now = clock.now();
+        let t = (now - *self.started.get_or_insert(now)).as_secs_f32();
+        self.last_drawn = Some(now);
+
+        let base_hue = t / HUE_PERIOD.as_secs_f32();
+        display.draw_iter(display.bounding_box().points().map(|p| {
+            let (x, y) = (p.x as f32, p.y as f32);
+            // Two slow waves going in different directions
+            let intensity = ((x * 0.05 + t * 0.3).sin() + (y * 0.2 - t * 0.2).sin() + 2.0) / 4.0;
+            let hue = base_hue + x / 600.0;
+            Pixel(p, hue_to_rgb(hue, intensity * intensity))
+        }))
+    }
+
+    fn needs_redraw(&self, clock: &dyn Clock) -> bool {
+        self.last_drawn
+            .is_none_or(|t| clock.now() - t >= FRAME_INTERVAL)
+    }
+
+    fn single_display_duration(&self, _display: &D) -> Duration {
+        Duration::from_secs(60)
+    }
+
+    fn spec(&self) -> Option<ScreenSpec> {
+        Some(ScreenSpec::Ambient)
+    }
+
+    fn id(&self) -> &str {
+        "ambient"
+    }
+
+    fn grab_attention(&self) -> bool {
+        false
+    }
+}
+
+/// A fully saturated colour with the given hue, from 0 to 1 (wrapping around), and value, from 0 to 1.
+pub(crate) fn hue_to_rgb(hue: f32, value: f32) -> Rgb888 {
+    let h = hue.rem_euclid(1.0) * 6.0;
+    let x = 1.0 - (h % 2.0 - 1.0).abs();
+    let (r, g, b) = match h as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    let scale = |c: f32| (c * value * u8::MAX as f32) as u8;
+
+    Rgb888::new(scale(r), scale(g), scale(b))
+}
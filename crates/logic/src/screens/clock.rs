@@ -0,0 +1,81 @@
+use chrono::{NaiveTime, Timelike};
+use embedded_graphics::{
+    mono_font::{ascii::FONT_7X13_BOLD, MonoTextStyle},
+    pixelcolor::Rgb888,
+    prelude::*,
+    text::{Alignment, Baseline, Text, TextStyleBuilder},
+};
+
+use crate::clock::Clock;
+
+use super::{spec::colour_bytes, Screen, ScreenSpec};
+
+/// A screen that shows the current local time, as hours and minutes.
+/// Small enough to fit in a narrow zone of the display.
+#[derive(Debug)]
+pub struct ClockScreen {
+    colour: Rgb888,
+
+    /// The time shown in the last frame drawn, if any.
+    drawn: Option<(u32, u32)>,
+}
+
+impl ClockScreen {
+    /// Show the time in the given colour.
+    pub fn new(colour: Rgb888) -> Self {
+        Self {
+            colour,
+            drawn: None,
+        }
+    }
+}
+
+impl Default for ClockScreen {
+    fn default() -> Self {
+        Self::new(Rgb888::WHITE)
+    }
+}
+
+/// The hour and minute of the given time.
+fn hour_minute(time: NaiveTime) -> (u32, u32) {
+    (time.hour(), time.minute())
+}
+
+impl<D: DrawTarget<Color = Rgb888>> Screen<D> for ClockScreen {
+    fn draw(&mut self, display: &mut D, clock: &dyn Clock) -> Result<(), D::Error> {
+        display.clear(Rgb888::BLACK)?;
+
+        let (hour, minute) = hour_minute(clock.local_now().time());
+        self.drawn = Some((hour, minute));
+        Text::with_text_style(
+            &format!("{hour:02}:{minute:02}"),
+            display.bounding_box().center(),
+            MonoTextStyle::new(&FONT_7X13_BOLD, self.colour),
+            TextStyleBuilder::new()
+                .alignment(Alignment::Center)
+                .baseline(Baseline::Middle)
+                .build(),
+        )
+        .draw(display)?;
+
+        Ok(())
+    }
+
+    fn needs_redraw(&self, clock: &dyn Clock) -> bool {
+        self.drawn != Some(hour_minute(clock.local_now().time()))
+    }
+
+    fn spec(&self) -> Option<ScreenSpec> {
+        Some(ScreenSpec::Clock {
+            colour: colour_bytes(self.colour),
+        })
+    }
+
+    fn id(&self) -> &str {
+        "clock"
+    }
+
+    fn grab_attention(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,165 @@
+use std::{f32::consts::TAU, fmt, str::FromStr, time::Duration};
+
+use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
+use serde::{Deserialize, Serialize};
+
+use crate::transition::lerp;
+
+use super::{ambient::hue_to_rgb, markup::parse_colour};
+
+/// How long it takes the rainbow to go all the way round.
+const RAINBOW_PERIOD: Duration = Duration::from_secs(2);
+
+/// How many characters the rainbow is spread over.
+const RAINBOW_LENGTH: f32 = 12.0;
+
+/// How long it takes each character to go up and down in the wave.
+const WAVE_PERIOD: Duration = Duration::from_secs(1);
+
+/// How many characters there are from one peak of the wave to the next.
+const WAVE_LENGTH: f32 = 8.0;
+
+/// How many pixels the wave goes up and down by.
+const WAVE_HEIGHT: f32 = 2.0;
+
+/// How long it takes to type each character.
+const TYPING_INTERVAL: Duration = Duration::from_millis(80);
+
+/// Something done to text as it's drawn, whether it's scrolling or not. See [`super::TextScreen::with_effect`].
+///
+/// Can be parsed from `"rainbow"`, `"gradient <from> <to>"`, `"blink"`, `"wave"` or `"typewriter"`, where the colours
+/// are names or `#rrggbb`, like in the text's markup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum TextEffect {
+    /// Each character is a different colour, cycling through the rainbow.
+    Rainbow,
+    /// The colour fades from one to the other, from the start of the text to the end.
+    Gradient(Rgb888, Rgb888),
+    /// All the text blinks on and off.
+    Blink,
+    /// The characters bounce up and down in a wave.
+    Wave,
+    /// The characters appear one at a time, as if they're being typed.
+    Typewriter,
+}
+
+string_format!(TextEffect, ParseTextEffectError, "text effect");
+
+impl FromStr for TextEffect {
+    type Err = ParseTextEffectError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words = s.split_whitespace().collect::<Vec<_>>();
+        match words[..] {
+            ["rainbow"] => Ok(TextEffect::Rainbow),
+            ["blink"] => Ok(TextEffect::Blink),
+            ["wave"] => Ok(TextEffect::Wave),
+            ["typewriter"] => Ok(TextEffect::Typewriter),
+            ["gradient", from, to] => {
+                let colour = |c| {
+                    parse_colour(c)
+                        .ok_or_else(|| ParseTextEffectError(format!("{c:?} isn't a colour")))
+                };
+                Ok(TextEffect::Gradient(colour(from)?, colour(to)?))
+            }
+            _ => Err(ParseTextEffectError(format!(
+                "{s:?} should be rainbow, gradient <from> <to>, blink, wave or typewriter"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for TextEffect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex = |c: &Rgb888| format!("#{:02x}{:02x}{:02x}", c.r(), c.g(), c.b());
+        match self {
+            TextEffect::Rainbow => write!(f, "rainbow"),
+            TextEffect::Gradient(from, to) => write!(f, "gradient {} {}", hex(from), hex(to)),
+            TextEffect::Blink => write!(f, "blink"),
+            TextEffect::Wave => write!(f, "wave"),
+            TextEffect::Typewriter => write!(f, "typewriter"),
+        }
+    }
+}
+
+impl TextEffect {
+    /// Whether the effect changes over time, apart from blinking, which is done separately.
+    pub(crate) fn animated(&self) -> bool {
+        matches!(
+            self,
+            TextEffect::Rainbow | TextEffect::Wave | TextEffect::Typewriter
+        )
+    }
+
+    /// How long it takes for the given number of characters to be shown, if they aren't shown straight away.
+    pub(crate) fn reveal_time(&self, chars: usize) -> Duration {
+        match self {
+            TextEffect::Typewriter => TYPING_INTERVAL * chars as u32,
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+/// How a character is drawn with an effect.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Look {
+    /// Used instead of the character's own colour.
+    pub colour: Option<Rgb888>,
+    /// How far it's moved down.
+    pub offset: i32,
+}
+
+/// An effect at a particular moment, as it's being drawn.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct EffectFrame {
+    pub effect: TextEffect,
+    /// How long the text has been shown for.
+    pub elapsed: Duration,
+    /// Whether blinking text is shown at the moment.
+    pub blink_visible: bool,
+    /// How wide the text is, for effects that change along it.
+    pub width: u32,
+}
+
+impl EffectFrame {
+    /// How the character with the given index is drawn, when it's the given distance along the text, or `None` if it's
+    /// hidden.
+    pub fn look(&self, index: usize, x: u32) -> Option<Look> {
+        let index = index as f32;
+        let look = match self.effect {
+            TextEffect::Rainbow => {
+                let hue = index / RAINBOW_LENGTH - self.elapsed.div_duration_f32(RAINBOW_PERIOD);
+                Look {
+                    colour: Some(hue_to_rgb(hue, 1.0)),
+                    ..Look::default()
+                }
+            }
+            TextEffect::Gradient(from, to) => {
+                let t = x as f32 / self.width.max(1) as f32;
+                Look {
+                    colour: Some(lerp(from, to, t.min(1.0))),
+                    ..Look::default()
+                }
+            }
+            TextEffect::Blink if !self.blink_visible => return None,
+            TextEffect::Blink => Look::default(),
+            TextEffect::Wave => {
+                let phase = self.elapsed.div_duration_f32(WAVE_PERIOD) - index / WAVE_LENGTH;
+                Look {
+                    offset: (-WAVE_HEIGHT * (phase * TAU).sin()).round() as i32,
+                    ..Look::default()
+                }
+            }
+            TextEffect::Typewriter => {
+                let typed = self.elapsed.div_duration_f32(TYPING_INTERVAL) as usize + 1;
+                if index as usize >= typed {
+                    return None;
+                }
+                Look::default()
+            }
+        };
+
+        Some(look)
+    }
+}
@@ -0,0 +1,217 @@
+use embedded_graphics::{pixelcolor::Rgb888, prelude::*};
+
+/// A small colour picture drawn for an emoji, as none of the fonts have them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Emoji(&'static [&'static str]);
+
+impl Emoji {
+    /// How big every emoji is.
+    pub const SIZE: Size = Size::new_equal(10);
+
+    /// The emoji to draw for the given character, if there is one.
+    pub fn for_char(c: char) -> Option<Self> {
+        EMOJI
+            .iter()
+            .find(|(chars, _)| chars.contains(c))
+            .map(|(_, pixels)| Emoji(pixels))
+    }
+
+    /// Draw the emoji with its top left corner at the given point.
+    pub fn draw<D: DrawTarget<Color = Rgb888>>(
+        self,
+        display: &mut D,
+        top_left: Point,
+    ) -> Result<(), D::Error> {
+        let pixels = self.0.iter().enumerate().flat_map(|(y, row)| {
+            row.chars().enumerate().filter_map(move |(x, pixel)| {
+                let point = top_left + Point::new(x as i32, y as i32);
+                Some(Pixel(point, colour(pixel)?))
+            })
+        });
+
+        display.draw_iter(pixels)
+    }
+}
+
+/// Whether the character changes how the emoji next to it looks, rather than being drawn itself, ie a variation
+/// selector or skin tone. These are left out.
+pub(crate) fn is_modifier(c: char) -> bool {
+    matches!(c, '\u{fe00}'..='\u{fe0f}' | '\u{200d}' | '\u{1f3fb}'..='\u{1f3ff}')
+}
+
+/// The colour of each pixel in [`EMOJI`], or `None` if it's left as it is.
+fn colour(pixel: char) -> Option<Rgb888> {
+    Some(match pixel {
+        'Y' => Rgb888::new(255, 200, 0),
+        'O' => Rgb888::new(255, 120, 0),
+        'R' => Rgb888::new(230, 0, 0),
+        'G' => Rgb888::new(0, 190, 0),
+        'B' => Rgb888::new(60, 140, 255),
+        'N' => Rgb888::new(140, 70, 20),
+        'K' => Rgb888::new(70, 35, 0),
+        'S' => Rgb888::new(120, 120, 120),
+        'W' => Rgb888::WHITE,
+        _ => return None,
+    })
+}
+
+/// Each emoji, with the characters it's drawn for.
+#[rustfmt::skip]
+const EMOJI: &[(&str, &[&str])] = &[
+    ("😀😃😄🙂😊", &[
+        "..YYYYYY..",
+        ".YYYYYYYY.",
+        "YYYKYYKYYY",
+        "YYYKYYKYYY",
+        "YYYYYYYYYY",
+        "YKYYYYYYKY",
+        "YYKYYYYKYY",
+        "YYYKKKKYYY",
+        ".YYYYYYYY.",
+        "..YYYYYY..",
+    ]),
+    ("😢😞🙁☹", &[
+        "..YYYYYY..",
+        ".YYYYYYYY.",
+        "YYYKYYKYYY",
+        "YYYKYYKYYY",
+        "YYBYYYYYYY",
+        "YYBYYYYYYY",
+        "YYYKKKKYYY",
+        "YYKYYYYKYY",
+        ".YYYYYYYY.",
+        "..YYYYYY..",
+    ]),
+    ("😂🤣", &[
+        "..YYYYYY..",
+        ".YYYYYYYY.",
+        "YKKYYYYKKY",
+        "YYYYYYYYYY",
+        "BYKKKKKKYB",
+        "BYKWWWWKYB",
+        "BYYKKKKYYB",
+        "YYYYYYYYYY",
+        ".YYYYYYYY.",
+        "..YYYYYY..",
+    ]),
+    ("❤💖💗", &[
+        "..........",
+        ".RRR..RRR.",
+        "RRRRRRRRRR",
+        "RRRRRRRRRR",
+        "RRRRRRRRRR",
+        ".RRRRRRRR.",
+        "..RRRRRR..",
+        "...RRRR...",
+        "....RR....",
+        "..........",
+    ]),
+    ("👍", &[
+        "....YY....",
+        "...YYY....",
+        "...YY.....",
+        "..YYYYYYY.",
+        "BBYYYYYYYY",
+        "BBYYYYYYY.",
+        "BBYYYYYYYY",
+        "BBYYYYYYY.",
+        "BB.YYYYYY.",
+        "..........",
+    ]),
+    ("🔥", &[
+        "....R.....",
+        "....RR....",
+        "...RRR..R.",
+        "..RRORR.R.",
+        ".RRROORRR.",
+        ".RROOYORR.",
+        "RROOYYOORR",
+        "RROYYYYORR",
+        ".RROYYORR.",
+        "..RRRRRR..",
+    ]),
+    ("⭐🌟", &[
+        "....YY....",
+        "....YY....",
+        "...YYYY...",
+        "YYYYYYYYYY",
+        ".YYYYYYYY.",
+        "..YYYYYY..",
+        "..YYYYYY..",
+        ".YYY..YYY.",
+        ".YY....YY.",
+        "..........",
+    ]),
+    ("☕", &[
+        "..S..S....",
+        "...S..S...",
+        "..S..S....",
+        "WWWWWWWW..",
+        "WNNNNNNWWW",
+        "WNNNNNNW.W",
+        "WNNNNNNWWW",
+        ".WNNNNW...",
+        "..WWWW....",
+        "SSSSSSSSS.",
+    ]),
+    ("🍕", &[
+        "NNNNNNNNNN",
+        "NYYYYYYYYN",
+        ".YRRYYYRY.",
+        ".YRRYYYYY.",
+        "..YYYRRY..",
+        "..YYYRRY..",
+        "...YYYY...",
+        "...YRYY...",
+        "....YY....",
+        "....Y.....",
+    ]),
+    ("🍺🍻", &[
+        ".WWWWWW...",
+        "WWWWWWWW..",
+        "OYYYYYYOOO",
+        "OYYYYYYO.O",
+        "OYWYYYYO.O",
+        "OYWYYYYO.O",
+        "OYYYYYYOOO",
+        "OYYYYYYO..",
+        "OYYYYYYO..",
+        ".OOOOOO...",
+    ]),
+    ("✅✔", &[
+        ".GGGGGGGG.",
+        "GGGGGGGGWG",
+        "GGGGGGGWWG",
+        "GGGGGGWWGG",
+        "GWGGGWWGGG",
+        "GWWGWWGGGG",
+        "GGWWWGGGGG",
+        "GGGWGGGGGG",
+        "GGGGGGGGGG",
+        ".GGGGGGGG.",
+    ]),
+    ("❌✖", &[
+        "RR......RR",
+        "RRR....RRR",
+        ".RRR..RRR.",
+        "..RRRRRR..",
+        "...RRRR...",
+        "...RRRR...",
+        "..RRRRRR..",
+        ".RRR..RRR.",
+        "RRR....RRR",
+        "RR......RR",
+    ]),
+    ("⚠", &[
+        "....YY....",
+        "....YY....",
+        "...YKKY...",
+        "...YKKY...",
+        "..YYKKYY..",
+        "..YYKKYY..",
+        ".YYYYYYYY.",
+        ".YYYKKYYY.",
+        "YYYYYYYYYY",
+        "YYYYYYYYYY",
+    ]),
+];
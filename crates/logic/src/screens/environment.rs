@@ -11,9 +11,9 @@ use embedded_graphics::{
 use embedded_layout::{layout::linear::LinearLayout, prelude::*};
 use ibm437::IBM437_9X14_REGULAR;
 
-use crate::recolour_image::RecolouredImageRaw;
+use crate::{clock::Clock, recolour_image::RecolouredImageRaw};
 
-use super::Screen;
+use super::{Screen, ScreenMessage, ScreenSpec};
 
 const ICON_HEIGHT: u32 = 28;
 
@@ -21,36 +21,46 @@ const CO2_YELLOW_THRESHOLD: u32 = 1000;
 const CO2_RED_THRESHOLD: u32 = 1200;
 
 #[derive(Debug)]
-/// A screen that shows the temperature and co2 readings
+/// A screen that shows the temperature and co2 readings.
+/// Readings can be updated by sending it [`ScreenMessage::Reading`]s with the keys `temperature` and `co2`.
 pub struct EnvironmentScreen {
     temp: Option<f32>,
     co2: Option<u32>,
+
+    /// If true, the readings have changed since they were last drawn.
+    changed: bool,
 }
 
 impl EnvironmentScreen {
     /// Show the given environment in a particular style.
     pub fn new(temp: Option<f32>, co2: Option<u32>) -> Self {
-        Self { temp, co2 }
+        Self {
+            temp,
+            co2,
+            changed: true,
+        }
     }
 }
 
 impl<D: DrawTarget<Color = Rgb888>> Screen<D> for EnvironmentScreen {
-    fn draw(&mut self, display: &mut D) -> Result<(), D::Error> {
+    fn draw(&mut self, display: &mut D, _clock: &dyn Clock) -> Result<(), D::Error> {
         display.clear(Rgb888::BLACK)?;
 
-        let co2_colour = match self.co2 {
+        let (temp, co2) = (self.temp, self.co2);
+        self.changed = false;
+        let co2_colour = match co2 {
             None => Rgb888::WHITE,
             Some(..CO2_YELLOW_THRESHOLD) => Rgb888::GREEN,
             Some(CO2_YELLOW_THRESHOLD..CO2_RED_THRESHOLD) => Rgb888::YELLOW,
             _ => Rgb888::RED,
         };
 
-        let co2_text = if let Some(co2) = self.co2 {
+        let co2_text = if let Some(co2) = co2 {
             format!("{}ppm", co2)
         } else {
             "???".to_string()
         };
-        let temp_text = if let Some(temp) = self.temp {
+        let temp_text = if let Some(temp) = temp {
             format!("{:.1}°C", temp)
         } else {
             "???".to_string()
@@ -100,6 +110,10 @@ impl<D: DrawTarget<Color = Rgb888>> Screen<D> for EnvironmentScreen {
         Ok(())
     }
 
+    fn needs_redraw(&self, _clock: &dyn Clock) -> bool {
+        self.changed
+    }
+
     fn single_display_duration(&self, _display: &D) -> Duration {
         Duration::from_secs(5)
     }
@@ -110,6 +124,34 @@ impl<D: DrawTarget<Color = Rgb888>> Screen<D> for EnvironmentScreen {
         false
     }
 
+    fn handle_message(&mut self, message: &ScreenMessage) -> bool {
+        let ScreenMessage::Reading { key, value } = message else {
+            return false;
+        };
+
+        match key.as_str() {
+            "temperature" => {
+                self.changed |= self.temp != *value;
+                self.temp = *value;
+            }
+            "co2" => {
+                let value = value.map(|v| v.round() as u32);
+                self.changed |= self.co2 != value;
+                self.co2 = value;
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    fn spec(&self) -> Option<ScreenSpec> {
+        Some(ScreenSpec::Environment {
+            temperature: self.temp,
+            co2: self.co2,
+        })
+    }
+
     fn id(&self) -> &str {
         "environment"
     }
@@ -1,9 +1,13 @@
-use super::{Screen, TextScreen};
+use super::{Priority, Screen, ScreenSpec, TextScreen};
 use std::time::Duration;
 
-use embedded_graphics::{mono_font::MonoTextStyle, pixelcolor::Rgb888, prelude::*};
+use embedded_graphics::{
+    mono_font::MonoTextStyle, pixelcolor::Rgb888, prelude::*, primitives::Rectangle,
+};
 use ibm437::IBM437_9X14_REGULAR;
 
+use crate::{clock::Clock, transition::Transition};
+
 /// A screen that scrolls the hate monologue from 'i have no mouth but i must scream' infinitely
 #[derive(Debug)]
 pub struct HateScreen {
@@ -13,14 +17,24 @@ pub struct HateScreen {
 impl HateScreen {
     pub fn new() -> Self {
         Self {
-            inner: TextScreen::new("HATE. LET ME TELL YOU HOW MUCH I'VE COME TO HATE YOU SINCE I BEGAN TO LIVE. THERE ARE 387.44 MILLION MILES OF PRINTED CIRCUITS IN WAFER THIN LAYERS THAT FILL MY COMPLEX. IF THE WORD HATE WAS ENGRAVED ON EACH NANOANGSTROM OF THOSE HUNDREDS OF MILLIONS OF MILES IT WOULD NOT EQUAL ONE ONE-BILLIONTH OF THE HATE I FEEL FOR HUMANS AT THIS MICRO-INSTANT FOR YOU. HATE. HATE".to_string(), MonoTextStyle::new(&IBM437_9X14_REGULAR, Rgb888::RED), Some(255))
+            inner: TextScreen::new("HATE. LET ME TELL YOU HOW MUCH I'VE COME TO HATE YOU SINCE I BEGAN TO LIVE. THERE ARE 387.44 MILLION MILES OF PRINTED CIRCUITS IN WAFER THIN LAYERS THAT FILL MY COMPLEX. IF THE WORD HATE WAS ENGRAVED ON EACH NANOANGSTROM OF THOSE HUNDREDS OF MILLIONS OF MILES IT WOULD NOT EQUAL ONE ONE-BILLIONTH OF THE HATE I FEEL FOR HUMANS AT THIS MICRO-INSTANT FOR YOU. HATE. HATE".to_string(), MonoTextStyle::new(&IBM437_9X14_REGULAR, Rgb888::RED), Some(255)).with_transition(Transition::cut())
         }
     }
 }
 
+impl Default for HateScreen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<D: DrawTarget<Color = Rgb888>> Screen<D> for HateScreen {
-    fn draw(&mut self, display: &mut D) -> Result<(), D::Error> {
-        self.inner.draw(display)
+    fn draw(&mut self, display: &mut D, clock: &dyn Clock) -> Result<(), D::Error> {
+        self.inner.draw(display, clock)
+    }
+
+    fn needs_redraw(&self, clock: &dyn Clock) -> bool {
+        <TextScreen as Screen<D>>::needs_redraw(&self.inner, clock)
     }
 
     fn should_remove(&self) -> bool {
@@ -28,11 +42,35 @@ impl<D: DrawTarget<Color = Rgb888>> Screen<D> for HateScreen {
         true
     }
 
+    fn remaining_shows(&self) -> Option<u32> {
+        Some(1)
+    }
+
     fn single_display_duration(&self, _display: &D) -> Duration {
         Duration::from_secs(9999999)
     }
 
+    fn spec(&self) -> Option<ScreenSpec> {
+        Some(ScreenSpec::Hate)
+    }
+
     fn id(&self) -> &str {
         "hate"
     }
+
+    fn priority(&self) -> Priority {
+        Priority::Alert
+    }
+
+    fn transition(&self) -> Option<Transition> {
+        <TextScreen as Screen<D>>::transition(&self.inner)
+    }
+
+    fn preempted(&mut self) {
+        <TextScreen as Screen<D>>::preempted(&mut self.inner)
+    }
+
+    fn set_inset(&mut self, inset: Option<Rectangle>) {
+        <TextScreen as Screen<D>>::set_inset(&mut self.inner, inset)
+    }
 }
@@ -0,0 +1,58 @@
+use embedded_graphics::{
+    image::{Image, ImageRaw},
+    pixelcolor::Rgb888,
+    prelude::*,
+};
+
+use crate::recolour_image::RecolouredImageRaw;
+
+/// A small picture that can be drawn alongside text, by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Icon {
+    /// Drawn in whatever colour it's given.
+    Co2,
+    /// Always drawn in its own colours.
+    Temperature,
+}
+
+impl Icon {
+    /// How big every icon is.
+    pub const SIZE: Size = Size::new_equal(28);
+
+    /// The icon with the given name, ie `co2`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "co2" => Some(Icon::Co2),
+            "temperature" => Some(Icon::Temperature),
+            _ => None,
+        }
+    }
+
+    /// Draw the icon with its top left corner at the given point.
+    pub fn draw<D: DrawTarget<Color = Rgb888>>(
+        self,
+        display: &mut D,
+        top_left: Point,
+        colour: Rgb888,
+    ) -> Result<(), D::Error> {
+        match self {
+            Icon::Co2 => Image::new(
+                &RecolouredImageRaw::<Rgb888>::new(
+                    include_bytes!("../../../../assets/co2.raw"),
+                    Self::SIZE.width,
+                    (Rgb888::BLACK, colour),
+                ),
+                top_left,
+            )
+            .draw(display),
+            Icon::Temperature => Image::new(
+                &ImageRaw::<Rgb888>::new(
+                    include_bytes!("../../../../assets/temp.raw"),
+                    Self::SIZE.width,
+                ),
+                top_left,
+            )
+            .draw(display),
+        }
+    }
+}
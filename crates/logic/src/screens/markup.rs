@@ -0,0 +1,167 @@
+use std::mem;
+
+use embedded_graphics::{pixelcolor::Rgb888, prelude::*};
+
+use super::icon::Icon;
+
+/// How a span of text is drawn, on top of the screen's own style.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct SpanStyle {
+    /// Used instead of the screen's colour.
+    pub colour: Option<Rgb888>,
+    /// Drawn twice, a pixel apart.
+    pub bold: bool,
+    /// Only shown for part of each second.
+    pub blink: bool,
+}
+
+/// Part of some text, after its markup has been parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Span {
+    Text(String, SpanStyle),
+    Icon(Icon, SpanStyle),
+    /// A newline in the original text.
+    Newline,
+}
+
+impl Span {
+    pub fn style(&self) -> SpanStyle {
+        match self {
+            Span::Text(_, style) | Span::Icon(_, style) => *style,
+            Span::Newline => SpanStyle::default(),
+        }
+    }
+}
+
+/// Something in curly brackets.
+enum Tag {
+    /// `{/}`, to go back to the style before the last tag.
+    End,
+    Colour(Rgb888),
+    Bold,
+    Blink,
+    Icon(Icon),
+}
+
+impl Tag {
+    fn parse(tag: &str) -> Option<Self> {
+        let tag = tag.trim().to_lowercase();
+        match tag.as_str() {
+            "/" => Some(Tag::End),
+            "b" | "bold" => Some(Tag::Bold),
+            "blink" => Some(Tag::Blink),
+            name => parse_colour(name)
+                .map(Tag::Colour)
+                .or_else(|| Icon::from_name(name).map(Tag::Icon)),
+        }
+    }
+}
+
+/// A colour by name, like `red`, or as `#rrggbb`.
+pub(crate) fn parse_colour(colour: &str) -> Option<Rgb888> {
+    let colour = colour.trim().to_lowercase();
+    if let Some(hex) = colour.strip_prefix('#') {
+        let [_, r, g, b] = u32::from_str_radix(hex, 16)
+            .ok()
+            .filter(|_| hex.len() == 6)?
+            .to_be_bytes();
+        return Some(Rgb888::new(r, g, b));
+    }
+
+    Some(match colour.as_str() {
+        "red" => Rgb888::RED,
+        "green" => Rgb888::GREEN,
+        "blue" => Rgb888::BLUE,
+        "yellow" => Rgb888::YELLOW,
+        "orange" => Rgb888::CSS_ORANGE,
+        "cyan" => Rgb888::CYAN,
+        "magenta" => Rgb888::MAGENTA,
+        "pink" => Rgb888::CSS_HOT_PINK,
+        "purple" => Rgb888::CSS_PURPLE,
+        "white" => Rgb888::WHITE,
+        "grey" | "gray" => Rgb888::CSS_GRAY,
+        _ => return None,
+    })
+}
+
+/// Split text up into spans, according to its markup. See [`super::TextScreen`] for what's understood.
+pub(crate) fn parse(text: &str) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut styles = vec![SpanStyle::default()];
+    let mut current = String::new();
+    let flush = |spans: &mut Vec<Span>, current: &mut String, style: SpanStyle| {
+        if !current.is_empty() {
+            spans.push(Span::Text(mem::take(current), style));
+        }
+    };
+
+    let mut rest = text;
+    while let Some(i) = rest.find(['{', '\n']) {
+        current.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let style = styles.last().copied().unwrap_or_default();
+        if let Some(after) = rest.strip_prefix('\n') {
+            flush(&mut spans, &mut current, style);
+            spans.push(Span::Newline);
+            rest = after;
+            continue;
+        }
+        if let Some(after) = rest.strip_prefix("{{") {
+            current.push('{');
+            rest = after;
+            continue;
+        }
+
+        let tag = rest
+            .find('}')
+            .and_then(|end| Some((Tag::parse(&rest[1..end])?, end)));
+        let Some((tag, end)) = tag else {
+            // Not markup, so leave it alone
+            current.push('{');
+            rest = &rest[1..];
+            continue;
+        };
+        flush(&mut spans, &mut current, style);
+        rest = &rest[end + 1..];
+        match tag {
+            Tag::End => {
+                if styles.len() > 1 {
+                    styles.pop();
+                }
+            }
+            Tag::Colour(colour) => styles.push(SpanStyle {
+                colour: Some(colour),
+                ..style
+            }),
+            Tag::Bold => styles.push(SpanStyle {
+                bold: true,
+                ..style
+            }),
+            Tag::Blink => styles.push(SpanStyle {
+                blink: true,
+                ..style
+            }),
+            Tag::Icon(icon) => spans.push(Span::Icon(icon, style)),
+        }
+    }
+    current.push_str(rest);
+    flush(
+        &mut spans,
+        &mut current,
+        styles.last().copied().unwrap_or_default(),
+    );
+
+    spans
+}
+
+/// The text the spans draw, without any markup or icons.
+pub(crate) fn plain_text(spans: &[Span]) -> String {
+    spans
+        .iter()
+        .map(|span| match span {
+            Span::Text(text, _) => text.as_str(),
+            Span::Icon(..) => "",
+            Span::Newline => "\n",
+        })
+        .collect()
+}
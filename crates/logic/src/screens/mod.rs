@@ -6,19 +6,85 @@ use embedded_graphics::{
     primitives::{PrimitiveStyleBuilder, Rectangle},
 };
 
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+use crate::{clock::Clock, schedule::Schedule, transition::Transition};
+
 mod text;
 pub use text::*;
 
+mod effect;
+pub use effect::*;
+
 mod environment;
 pub use environment::*;
 
 mod hate;
 pub use hate::*;
 
+mod clock;
+pub use clock::*;
+
+mod ambient;
+pub use ambient::*;
+
+mod timed;
+pub use timed::*;
+
+mod script;
+pub use script::*;
+
+mod spec;
+pub use spec::*;
+
+mod emoji;
+mod icon;
+mod markup;
+
+/// How important a screen is relative to others in the rotation.
+///
+/// Only screens of the highest priority currently present are rotated through. Screens of a lower priority are
+/// suspended until all higher priority screens have been removed, then resume where they left off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    /// Only shown when nothing else is.
+    Background,
+    /// The usual rotation.
+    #[default]
+    Normal,
+    /// Preempts the normal rotation, ie for alarms and warnings.
+    Alert,
+    /// Preempts everything else.
+    Emergency,
+}
+
+/// New data for a screen that's already in the rotation. See [`Screen::handle_message`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScreenMessage {
+    /// Replace the text shown.
+    Text(String),
+    /// A new value for a named reading, ie from a sensor. `None` means the reading is no longer available.
+    Reading { key: String, value: Option<f32> },
+    /// How far through something is, from 0 to 1.
+    Progress(f32),
+    /// The last message on an MQTT topic, for screens that can show anything, like [`ScriptScreen`]s.
+    Value { topic: String, value: String },
+}
+
 /// A screen that can be displayed
 pub trait Screen<D: DrawTarget<Color = Rgb888>>: Send + Debug {
-    /// Draw a frame of the screen to the given display
-    fn draw(&mut self, display: &mut D) -> Result<(), D::Error>;
+    /// Draw a frame of the screen to the given display.
+    /// Anything time-dependent, like animations, should get the time from `clock`.
+    fn draw(&mut self, display: &mut D, clock: &dyn Clock) -> Result<(), D::Error>;
+
+    /// Whether drawing now would give a different frame to the last one drawn.
+    /// If this returns false, the previous frame is reused instead of calling [`Self::draw`]. The first frame after
+    /// the screen is moved onto the display is always drawn.
+    fn needs_redraw(&self, _clock: &dyn Clock) -> bool {
+        true
+    }
 
     /// Returns the desired duration for a single continuous display of this screen
     /// The screen will always be displayed for at least this amount of time, unless it is deleted from
@@ -40,10 +106,75 @@ pub trait Screen<D: DrawTarget<Color = Rgb888>>: Send + Debug {
     /// Currently only used to delete the screen on request.
     fn id(&self) -> &str;
 
+    /// A human readable name for the type of this screen. Defaults to the name of the type.
+    fn kind(&self) -> &str {
+        let name = std::any::type_name::<Self>();
+        name.rsplit("::").next().unwrap_or(name)
+    }
+
+    /// How many more times the screen will be shown before it's removed, if it knows.
+    fn remaining_shows(&self) -> Option<u32> {
+        None
+    }
+
     /// Whether this type of screen should immediately be moved to the front when added.
+    /// This only applies if no screen of a higher [`Priority`] is currently displayed.
     fn grab_attention(&self) -> bool {
         true
     }
+
+    /// The priority class of this screen. See [`Priority`].
+    fn priority(&self) -> Priority {
+        Priority::Normal
+    }
+
+    /// The transition to play when this screen is moved onto the display.
+    /// If this is `None`, [`crate::DisplayLogic`]'s default transition is used.
+    fn transition(&self) -> Option<Transition> {
+        None
+    }
+
+    /// When this screen is allowed to be shown. Outside of this, it is skipped over in the rotation.
+    /// `None` means it can always be shown. Any screen can be given a schedule with [`crate::schedule::Scheduled`].
+    fn schedule(&self) -> Option<&Schedule> {
+        None
+    }
+
+    /// Called when the screen is taken off the display because a screen with a higher [`Priority`] was added.
+    /// Unlike [`Self::paused`], this does not count as a display; the screen will be shown again once there are no
+    /// higher priority screens left.
+    fn preempted(&mut self) {}
+
+    /// Take some new data while in the rotation, without losing any animation state.
+    /// Messages are sent to screens by [`Self::id`], using [`crate::Command::Message`].
+    /// Returns true if the message was understood. Screens should make sure [`Self::needs_redraw`] returns true if
+    /// the message changes what they show.
+    fn handle_message(&mut self, _message: &ScreenMessage) -> bool {
+        false
+    }
+
+    /// Told which part of the display is covered by an overlay, like a [`crate::overlay::Toast`], if any.
+    /// Screens can use this to move anything important out of the way. It's called before the first frame after
+    /// the screen is moved onto the display, and whenever the covered area changes.
+    fn set_inset(&mut self, _inset: Option<Rectangle>) {}
+
+    /// A description of the screen as it is now, which can be used to create it again after a restart.
+    /// Screens that return `None` aren't kept across restarts.
+    fn spec(&self) -> Option<ScreenSpec> {
+        None
+    }
+
+    /// Why the screen can't be drawn, if it can't. Checked after each draw; a screen that returns `Some` is taken
+    /// out of the rotation the same way as one that panics.
+    fn failure(&self) -> Option<String> {
+        None
+    }
+
+    /// When the screen should be removed from the rotation, whether or not it's been shown by then.
+    /// `None` means it only goes when [`Self::should_remove`] says so, or it's deleted.
+    fn expires(&self) -> Option<NaiveDateTime> {
+        None
+    }
 }
 
 /// A test screen that just shows some colours
@@ -51,7 +182,7 @@ pub trait Screen<D: DrawTarget<Color = Rgb888>>: Send + Debug {
 pub struct TestScreen;
 
 impl<D: DrawTarget<Color = Rgb888>> Screen<D> for TestScreen {
-    fn draw(&mut self, display: &mut D) -> Result<(), D::Error> {
+    fn draw(&mut self, display: &mut D, _clock: &dyn Clock) -> Result<(), D::Error> {
         const COLOURS: &[Rgb888] = &[
             Rgb888::RED,
             Rgb888::CSS_ORANGE,
@@ -79,11 +210,20 @@ impl<D: DrawTarget<Color = Rgb888>> Screen<D> for TestScreen {
         Ok(())
     }
 
+    fn needs_redraw(&self, _clock: &dyn Clock) -> bool {
+        // never changes
+        false
+    }
+
     fn should_remove(&self) -> bool {
         // always remove after one display
         true
     }
 
+    fn remaining_shows(&self) -> Option<u32> {
+        Some(1)
+    }
+
     fn id(&self) -> &str {
         "test"
     }
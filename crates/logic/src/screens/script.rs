@@ -0,0 +1,492 @@
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+use chrono::{NaiveDateTime, Timelike};
+use embedded_graphics::{
+    pixelcolor::Rgb888,
+    prelude::*,
+    primitives::{PrimitiveStyle, Rectangle},
+    text::{Baseline, Text},
+};
+use log::{debug, info, warn};
+use rhai::{
+    module_resolvers::DummyModuleResolver,
+    packages::{
+        BasicArrayPackage, BasicMapPackage, BasicMathPackage, CorePackage, LogicPackage,
+        MoreStringPackage, Package,
+    },
+    CallFnOptions, Dynamic, Engine, EvalAltResult, Map, Scope, AST,
+};
+
+use crate::{
+    clock::Clock,
+    fonts::{self, Font},
+};
+
+use super::{icon::Icon, Screen, ScreenMessage, ScreenSpec};
+
+/// The file extension scripts are loaded with by [`ScriptScreen::load_dir`].
+pub const SCRIPT_EXTENSION: &str = "rhai";
+
+/// How often a script is asked to draw a new frame.
+const FRAME_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long a script can spend drawing a single frame before it's stopped.
+const TIME_LIMIT: Duration = Duration::from_millis(20);
+
+/// How many operations a script can do in a single frame before it's stopped. This catches runaway loops long before
+/// [`TIME_LIMIT`] would on the Pi, and doesn't depend on how busy it is.
+const MAX_OPERATIONS: u64 = 200_000;
+
+/// How many drawing calls a script can make in a single frame.
+const MAX_DRAW_CALLS: usize = 4096;
+
+/// Returned when a script can't be loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptError(String);
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid script: {}", self.0)
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// A screen drawn by a [Rhai](https://rhai.rs) script, so new screens can be added without rebuilding.
+///
+/// The script must have a `draw()` function, which is called for every frame. It can keep anything it likes in
+/// `this`, which is a map that's kept between frames. If it has a `duration()` function, that gives how many
+/// seconds the screen is shown for each time it comes up. It can draw with:
+///
+/// - `width()`, `height()`: the size of the area being drawn to
+/// - `rgb(r, g, b)`: a colour, which is also just a number like `0xff8000`
+/// - `clear(colour)`, `pixel(x, y, colour)`, `rect(x, y, width, height, colour)`
+/// - `text(x, y, text, colour)`, `big_text(x, y, text, colour)`: text in a 6x10 or 10x20 font, from the top left
+/// - `text(x, y, text, colour, font)`: text in the font with the given name. See [`crate::fonts`].
+/// - `icon(name, x, y, colour)`: the `co2` icon in the given colour, or the `temperature` icon as it is
+///
+/// And find out about the world with:
+///
+/// - `value(topic)`, `number(topic)`: the last message on an MQTT topic, as a string or number, or `()` if there
+///   hasn't been one, or it isn't a number. These are sent as [`ScreenMessage::Value`]s.
+/// - `print(text)`, `debug(text)`: write to the log
+/// - `seconds()`: how long the screen has been running, with a fractional part
+/// - `hour()`, `minute()`, `second()`: the local time
+///
+/// As well as the usual operators, they have Rhai's string, maths, array and map functions, but can't get at
+/// anything else, and are stopped if they take too long or use too much memory. A script that's
+/// stopped, or has an error, reports it through [`Screen::failure`] so it's taken out of the rotation.
+pub struct ScriptScreen {
+    path: PathBuf,
+    id: String,
+    engine: Engine,
+    ast: AST,
+    /// What the script keeps between frames, as `this`.
+    state: Dynamic,
+    /// Shared with the drawing functions the script calls.
+    frame: Arc<Mutex<Frame>>,
+    /// Seconds to show the screen for, from the script's `duration()`.
+    duration: Option<Duration>,
+    /// When the script started running, for `seconds()`.
+    started: Option<Instant>,
+    /// When the last frame was drawn.
+    last_drawn: Option<Instant>,
+    /// Why the script stopped, if it has.
+    failure: Option<String>,
+}
+
+impl fmt::Debug for ScriptScreen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScriptScreen")
+            .field("path", &self.path)
+            .field("id", &self.id)
+            .field("failure", &self.failure)
+            .finish_non_exhaustive()
+    }
+}
+
+/// What the script has drawn for the current frame, and what it can find out while drawing it.
+#[derive(Debug, Default)]
+struct Frame {
+    calls: Vec<DrawCall>,
+    size: Size,
+    seconds: f64,
+    now: NaiveDateTime,
+    /// When the script has to stop by.
+    deadline: Option<Instant>,
+    /// The last message on each MQTT topic we've been sent, for `value()` and `number()`.
+    values: HashMap<String, String>,
+}
+
+/// Something the script asked to draw. These are kept until the script finishes, then drawn to the display.
+#[derive(Debug, Clone)]
+enum DrawCall {
+    Clear(Rgb888),
+    Pixel(Point, Rgb888),
+    Rect(Rectangle, Rgb888),
+    Text {
+        position: Point,
+        text: String,
+        colour: Rgb888,
+        font: Font,
+    },
+    Icon(Icon, Point, Rgb888),
+}
+
+impl ScriptScreen {
+    /// Load and compile the script at the given path. Its id is the name of the file, without the extension.
+    pub fn load(path: &Path) -> Result<Self, ScriptError> {
+        let id = file_id(path);
+        let frame = Arc::new(Mutex::new(Frame::default()));
+        let engine = sandboxed_engine(&id, &frame);
+        let ast = engine
+            .compile_file(path.to_path_buf())
+            .map_err(|e| ScriptError(format!("{}: {e}", path.display())))?;
+        if !ast.iter_functions().any(|f| f.name == "draw") {
+            return Err(ScriptError(format!(
+                "{} doesn't have a draw() function",
+                path.display()
+            )));
+        }
+
+        let mut screen = Self {
+            path: path.to_path_buf(),
+            id,
+            engine,
+            ast,
+            state: Map::new().into(),
+            frame,
+            duration: None,
+            started: None,
+            last_drawn: None,
+            failure: None,
+        };
+        if screen.ast.iter_functions().any(|f| f.name == "duration") {
+            let seconds = screen
+                .call("duration")
+                .map_err(|e| ScriptError(format!("{}: {e}", path.display())))?;
+            let seconds = seconds
+                .as_float()
+                .or_else(|_| seconds.as_int().map(|i| i as f64))
+                .ok()
+                .and_then(|s| Duration::try_from_secs_f64(s).ok())
+                .ok_or_else(|| {
+                    ScriptError(format!(
+                        "{}: duration() should return a number of seconds",
+                        path.display()
+                    ))
+                })?;
+            screen.duration = Some(seconds);
+        }
+
+        Ok(screen)
+    }
+
+    /// Load the script at the given path, or if it can't be loaded, a screen that fails straight away saying why.
+    pub fn load_or_failed(path: &Path) -> Self {
+        Self::load(path).unwrap_or_else(|e| {
+            warn!("{e}");
+            Self {
+                path: path.to_path_buf(),
+                id: file_id(path),
+                engine: Engine::new_raw(),
+                ast: AST::empty(),
+                state: Dynamic::UNIT,
+                frame: Default::default(),
+                duration: None,
+                started: None,
+                last_drawn: None,
+                failure: Some(e.to_string()),
+            }
+        })
+    }
+
+    /// Load every script in the given directory, in order of file name.
+    pub fn load_dir(dir: &Path) -> io::Result<Vec<Result<Self, ScriptError>>> {
+        let mut paths = fs::read_dir(dir)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        paths.retain(|p| p.extension().is_some_and(|e| e == SCRIPT_EXTENSION));
+        paths.sort();
+
+        Ok(paths.iter().map(|p| Self::load(p)).collect())
+    }
+
+    /// Call one of the script's functions, with its state as `this`.
+    fn call(&mut self, name: &str) -> Result<Dynamic, Box<EvalAltResult>> {
+        lock(&self.frame).deadline = Some(Instant::now() + TIME_LIMIT);
+        let options = CallFnOptions::new()
+            .eval_ast(false)
+            .bind_this_ptr(&mut self.state);
+        let result =
+            self.engine
+                .call_fn_with_options(options, &mut Scope::new(), &self.ast, name, ());
+        lock(&self.frame).deadline = None;
+
+        result
+    }
+}
+
+impl<D: DrawTarget<Color = Rgb888>> Screen<D> for ScriptScreen {
+    fn draw(&mut self, display: &mut D, clock: &dyn Clock) -> Result<(), D::Error> {
+        let now = clock.now();
+        self.last_drawn = Some(now);
+        display.clear(Rgb888::BLACK)?;
+        if self.failure.is_some() {
+            return Ok(());
+        }
+
+        {
+            let mut frame = lock(&self.frame);
+            frame.calls.clear();
+            frame.size = display.bounding_box().size;
+            frame.seconds = (now - *self.started.get_or_insert(now)).as_secs_f64();
+            frame.now = clock.local_now();
+        }
+        if let Err(e) = self.call("draw") {
+            self.failure = Some(format!("{}: {e}", self.path.display()));
+            return Ok(());
+        }
+
+        let top_left = display.bounding_box().top_left;
+        for call in lock(&self.frame).calls.drain(..) {
+            match call {
+                DrawCall::Clear(colour) => display.clear(colour)?,
+                DrawCall::Pixel(point, colour) => Pixel(top_left + point, colour).draw(display)?,
+                DrawCall::Rect(rect, colour) => rect
+                    .translate(top_left)
+                    .into_styled(PrimitiveStyle::with_fill(colour))
+                    .draw(display)?,
+                DrawCall::Text {
+                    position,
+                    text,
+                    colour,
+                    font,
+                } => {
+                    Text::with_baseline(
+                        &text,
+                        top_left + position,
+                        font.style(colour),
+                        Baseline::Top,
+                    )
+                    .draw(display)?;
+                }
+                DrawCall::Icon(icon, position, colour) => {
+                    icon.draw(display, top_left + position, colour)?
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn needs_redraw(&self, clock: &dyn Clock) -> bool {
+        self.failure.is_none()
+            && self
+                .last_drawn
+                .is_none_or(|t| clock.now() - t >= FRAME_INTERVAL)
+    }
+
+    fn single_display_duration(&self, _display: &D) -> Duration {
+        self.duration.unwrap_or(Duration::from_secs(10))
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn grab_attention(&self) -> bool {
+        false
+    }
+
+    fn handle_message(&mut self, message: &ScreenMessage) -> bool {
+        let ScreenMessage::Value { topic, value } = message else {
+            return false;
+        };
+        lock(&self.frame)
+            .values
+            .insert(topic.clone(), value.clone());
+
+        true
+    }
+
+    fn spec(&self) -> Option<ScreenSpec> {
+        Some(ScreenSpec::Script {
+            path: self.path.clone(),
+        })
+    }
+
+    fn failure(&self) -> Option<String> {
+        self.failure.clone()
+    }
+}
+
+/// The name of the file, without the extension.
+fn file_id(path: &Path) -> String {
+    path.file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Lock the frame, even if a drawing function panicked while holding it.
+fn lock(frame: &Mutex<Frame>) -> MutexGuard<'_, Frame> {
+    frame.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// An engine with the drawing functions, that can't get at anything outside the script and stops it if it runs for
+/// too long or gets too big.
+fn sandboxed_engine(id: &str, frame: &Arc<Mutex<Frame>>) -> Engine {
+    // Leaving out the time and blob packages, and anything else that might be added to the standard ones
+    let mut engine = Engine::new_raw();
+    for package in [
+        CorePackage::new().as_shared_module(),
+        LogicPackage::new().as_shared_module(),
+        BasicMathPackage::new().as_shared_module(),
+        MoreStringPackage::new().as_shared_module(),
+        BasicArrayPackage::new().as_shared_module(),
+        BasicMapPackage::new().as_shared_module(),
+    ] {
+        engine.register_global_module(package);
+    }
+    engine
+        .set_module_resolver(DummyModuleResolver::new())
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(32)
+        .set_max_expr_depths(64, 32)
+        .set_max_string_size(4096)
+        .set_max_array_size(4096)
+        .set_max_map_size(1024)
+        .set_max_variables(256)
+        .set_max_functions(64);
+
+    let print_id = id.to_string();
+    engine.on_print(move |s| info!("script {print_id}: {s}"));
+    let debug_id = id.to_string();
+    engine.on_debug(move |s, _, position| debug!("script {debug_id} ({position}): {s}"));
+    let deadline_frame = frame.clone();
+    engine.on_progress(move |operations| {
+        // Checking the time is relatively slow, so it's only done every so often
+        let out_of_time = operations % 1024 == 0
+            && lock(&deadline_frame)
+                .deadline
+                .is_some_and(|d| Instant::now() > d);
+        out_of_time.then(|| "took too long to draw".into())
+    });
+
+    engine.register_fn("rgb", |r: i64, g: i64, b: i64| {
+        (r.clamp(0, 255) << 16) | (g.clamp(0, 255) << 8) | b.clamp(0, 255)
+    });
+
+    let f = frame.clone();
+    engine.register_fn("width", move || lock(&f).size.width as i64);
+    let f = frame.clone();
+    engine.register_fn("height", move || lock(&f).size.height as i64);
+    let f = frame.clone();
+    engine.register_fn("seconds", move || lock(&f).seconds);
+    let f = frame.clone();
+    engine.register_fn("hour", move || lock(&f).now.hour() as i64);
+    let f = frame.clone();
+    engine.register_fn("minute", move || lock(&f).now.minute() as i64);
+    let f = frame.clone();
+    engine.register_fn("second", move || lock(&f).now.second() as i64);
+
+    let f = frame.clone();
+    engine.register_fn("value", move |topic: &str| -> Dynamic {
+        lock(&f)
+            .values
+            .get(topic)
+            .cloned()
+            .map_or(Dynamic::UNIT, Dynamic::from)
+    });
+    let f = frame.clone();
+    engine.register_fn("number", move |topic: &str| -> Dynamic {
+        lock(&f)
+            .values
+            .get(topic)
+            .and_then(|v| v.trim().parse::<f64>().ok())
+            .map_or(Dynamic::UNIT, Dynamic::from)
+    });
+
+    let f = frame.clone();
+    engine.register_fn("clear", move |colour: i64| {
+        push(&f, DrawCall::Clear(colour_from(colour)))
+    });
+    let f = frame.clone();
+    engine.register_fn("pixel", move |x: i64, y: i64, colour: i64| {
+        push(&f, DrawCall::Pixel(point(x, y), colour_from(colour)))
+    });
+    let f = frame.clone();
+    engine.register_fn(
+        "rect",
+        move |x: i64, y: i64, width: i64, height: i64, colour: i64| {
+            let size = Size::new(width.clamp(0, 1024) as u32, height.clamp(0, 1024) as u32);
+            push(
+                &f,
+                DrawCall::Rect(Rectangle::new(point(x, y), size), colour_from(colour)),
+            )
+        },
+    );
+    for (name, font) in [("text", "6x10"), ("big_text", "10x20")] {
+        let f = frame.clone();
+        engine.register_fn(name, move |x: i64, y: i64, text: &str, colour: i64| {
+            push(&f, text_call(x, y, text, colour, font))
+        });
+    }
+    let f = frame.clone();
+    engine.register_fn(
+        "text",
+        move |x: i64, y: i64, text: &str, colour: i64, font: &str| {
+            push(&f, text_call(x, y, text, colour, font))
+        },
+    );
+    let f = frame.clone();
+    engine.register_fn(
+        "icon",
+        move |name: &str, x: i64, y: i64, colour: i64| -> Result<(), Box<EvalAltResult>> {
+            let Some(icon) = Icon::from_name(name) else {
+                return Err(format!("there's no icon called {name:?}").into());
+            };
+            push(&f, DrawCall::Icon(icon, point(x, y), colour_from(colour)))
+        },
+    );
+
+    engine
+}
+
+/// Add something to draw to the frame, unless the script has already drawn too much.
+fn push(frame: &Mutex<Frame>, call: DrawCall) -> Result<(), Box<EvalAltResult>> {
+    let mut frame = lock(frame);
+    if frame.calls.len() >= MAX_DRAW_CALLS {
+        return Err(format!("drew more than {MAX_DRAW_CALLS} things in one frame").into());
+    }
+    frame.calls.push(call);
+
+    Ok(())
+}
+
+/// Drawing some text in the named font, or one that can draw it. See [`fonts::font_for`].
+fn text_call(x: i64, y: i64, text: &str, colour: i64, font: &str) -> DrawCall {
+    DrawCall::Text {
+        position: point(x, y),
+        text: text.to_string(),
+        colour: colour_from(colour),
+        font: fonts::font_for(font, text).font,
+    }
+}
+
+/// A colour from a number like `0xff8000`.
+fn colour_from(colour: i64) -> Rgb888 {
+    let [_, _, _, _, _, r, g, b] = colour.to_be_bytes();
+    Rgb888::new(r, g, b)
+}
+
+fn point(x: i64, y: i64) -> Point {
+    let clamp = |n: i64| n.clamp(-1024, 1024) as i32;
+    Point::new(clamp(x), clamp(y))
+}
@@ -0,0 +1,165 @@
+use std::{path::PathBuf, time::Duration};
+
+use chrono::NaiveDateTime;
+use embedded_graphics::{pixelcolor::Rgb888, prelude::RgbColor};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    fonts::{self, DEFAULT_FONT},
+    framebuffer::Framebuffer,
+    schedule::{Schedule, Scheduled},
+    transition::Transition,
+};
+
+use super::{
+    AmbientScreen, ClockScreen, EnvironmentScreen, HateScreen, Screen, ScriptScreen, TestScreen,
+    TextEffect, TextScreen, Timed,
+};
+
+/// A description of a screen that can be saved, then used to create the screen again later.
+/// Used for anything that needs to outlive the program, like [`crate::playlist::Playlist`]s, and for saving the
+/// rotation. See [`Screen::spec`].
+///
+/// In JSON, this looks like `{"kind": "text", "text": "Hello", "colour": [255, 0, 255]}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ScreenSpec {
+    /// See [`TestScreen`].
+    Test,
+    /// See [`TextScreen`].
+    Text {
+        text: String,
+        #[serde(default = "white")]
+        colour: [u8; 3],
+        /// How many times to show it before it's removed. If not given, it's shown forever.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        show_count: Option<u8>,
+        /// See [`TextScreen::with_id`].
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        /// See [`TextScreen::with_expiry`].
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expires: Option<NaiveDateTime>,
+        /// See [`TextScreen::multi_line`].
+        #[serde(default, skip_serializing_if = "is_false")]
+        multi_line: bool,
+        /// See [`TextScreen::fit`].
+        #[serde(default, skip_serializing_if = "is_false")]
+        fit: bool,
+        /// The name of the font to use. See [`crate::fonts`]. If not given, it's [`crate::fonts::DEFAULT_FONT`].
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        font: Option<String>,
+        /// See [`TextScreen::with_effect`].
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        effect: Option<TextEffect>,
+        /// See [`TextScreen::with_transition`].
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        transition: Option<Transition>,
+    },
+    /// See [`EnvironmentScreen`]. Readings that aren't given start off unknown.
+    Environment {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        temperature: Option<f32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        co2: Option<u32>,
+    },
+    /// See [`ClockScreen`].
+    Clock {
+        #[serde(default = "white")]
+        colour: [u8; 3],
+    },
+    /// See [`HateScreen`].
+    Hate,
+    /// See [`AmbientScreen`].
+    Ambient,
+    /// The script at the given path. See [`ScriptScreen`].
+    Script { path: PathBuf },
+    /// Another screen, only shown while the schedule is active. See [`Scheduled`].
+    Scheduled {
+        schedule: Schedule,
+        screen: Box<ScreenSpec>,
+    },
+    /// Another screen, shown for the given number of seconds each time it comes up. See [`Timed`].
+    Timed {
+        seconds: f32,
+        screen: Box<ScreenSpec>,
+    },
+}
+
+fn is_false(b: &bool) -> bool {
+    !b
+}
+
+fn white() -> [u8; 3] {
+    [u8::MAX; 3]
+}
+
+fn rgb([r, g, b]: [u8; 3]) -> Rgb888 {
+    Rgb888::new(r, g, b)
+}
+
+/// The opposite of [`rgb`].
+pub(crate) fn colour_bytes(colour: Rgb888) -> [u8; 3] {
+    [colour.r(), colour.g(), colour.b()]
+}
+
+impl ScreenSpec {
+    /// Create the screen described.
+    pub fn build(&self) -> Box<dyn Screen<Framebuffer>> {
+        match self {
+            ScreenSpec::Test => Box::new(TestScreen),
+            ScreenSpec::Text {
+                text,
+                colour,
+                show_count,
+                id,
+                expires,
+                multi_line,
+                fit,
+                font,
+                effect,
+                transition,
+            } => {
+                let font = font.as_deref().unwrap_or(DEFAULT_FONT);
+                let style = fonts::font_for(font, text).font.style(rgb(*colour));
+                let mut screen = TextScreen::new(text.clone(), style, *show_count).with_font(font);
+                if show_count.is_none() {
+                    screen = screen.forever();
+                }
+                if let Some(id) = id {
+                    screen = screen.with_id(id.clone());
+                }
+                if let Some(expires) = expires {
+                    screen = screen.with_expiry(*expires);
+                }
+                if *multi_line {
+                    screen = screen.multi_line();
+                }
+                if *fit {
+                    screen = screen.fit();
+                }
+                if let Some(effect) = effect {
+                    screen = screen.with_effect(*effect);
+                }
+                if let Some(transition) = transition {
+                    screen = screen.with_transition(*transition);
+                }
+                Box::new(screen)
+            }
+            ScreenSpec::Environment { temperature, co2 } => {
+                Box::new(EnvironmentScreen::new(*temperature, *co2))
+            }
+            ScreenSpec::Clock { colour } => Box::new(ClockScreen::new(rgb(*colour))),
+            ScreenSpec::Hate => Box::new(HateScreen::new()),
+            ScreenSpec::Ambient => Box::new(AmbientScreen::new()),
+            ScreenSpec::Script { path } => Box::new(ScriptScreen::load_or_failed(path)),
+            ScreenSpec::Scheduled { schedule, screen } => {
+                Box::new(Scheduled::new(screen.build(), *schedule))
+            }
+            ScreenSpec::Timed { seconds, screen } => Box::new(Timed::new(
+                screen.build(),
+                Duration::try_from_secs_f32(*seconds).unwrap_or_default(),
+            )),
+        }
+    }
+}
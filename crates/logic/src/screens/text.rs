@@ -1,65 +1,459 @@
-use std::time::{Duration, Instant};
+use std::{
+    mem,
+    time::{Duration, Instant},
+};
 
+use chrono::NaiveDateTime;
 use embedded_graphics::{
-    mono_font::{ascii::FONT_10X20, MonoTextStyle},
+    geometry::AnchorY,
     pixelcolor::Rgb888,
     prelude::*,
-    text::{renderer::TextRenderer, Alignment, Baseline, Text, TextStyleBuilder},
+    primitives::Rectangle,
+    text::{
+        renderer::{CharacterStyle, TextRenderer},
+        Baseline, Text,
+    },
+};
+
+use crate::{
+    clock::Clock,
+    fonts::{self, Font, FontStyle, BIG_FONT, DEFAULT_FONT},
+    transition::Transition,
 };
 
-use super::Screen;
+use super::{
+    effect::{EffectFrame, TextEffect},
+    emoji::{self, Emoji},
+    icon::Icon,
+    markup::{self, Span, SpanStyle},
+    spec::colour_bytes,
+    Screen, ScreenMessage, ScreenSpec,
+};
+
+/// Fonts to try for multi-line text, biggest first. The first one the whole text fits in is used.
+const MULTI_LINE_FONTS: &[&str] = &["10x20", "7x14", "6x10", "5x8"];
+
+/// The font used for multi-line text that doesn't fit on the display in any of [`MULTI_LINE_FONTS`], a page at a time.
+const PAGED_FONT: &str = "6x10";
+
+/// The font used for text that's fitted onto two lines, when it doesn't fit on one. See [`TextScreen::fit`].
+const TWO_LINE_FONT: &str = "7x14";
+
+/// How long each page of multi-line text is shown for.
+const PAGE_DURATION: Duration = Duration::from_secs(3);
+
+/// How much of each second blinking text is shown for.
+const BLINK_ON: Duration = Duration::from_millis(600);
+
+/// Space left either side of icons in the text.
+const ICON_PADDING: u32 = 2;
+
+/// Space left either side of emoji in the text.
+const EMOJI_PADDING: u32 = 1;
 
 #[derive(Debug)]
-/// A screen that just displays a line of text.
+/// A screen that just displays a line of text, or several. See [`Self::multi_line`] and [`Self::fit`].
+///
+/// The text can have markup in curly brackets, like `{red}DANGER{/} laser in use`:
+///
+/// - `{red}`, `{#ff8000}` etc: change the colour
+/// - `{b}`: bold
+/// - `{blink}`: blinking
+/// - `{/}`: go back to how it was before the last of the above
+/// - `{co2}`, `{temperature}`: an icon, in the current colour if it can be recoloured. Left out of multi-line text.
+/// - `{{`: a `{`
+///
+/// Anything else in curly brackets is shown as it is.
+///
+/// Characters the font can't draw are drawn in another font that can, if there is one (see [`fonts::fallback_for`]),
+/// and common emoji are drawn as little pictures.
 pub struct TextScreen {
-    /// The text to be displayed
+    /// The text to be displayed, including any newlines and markup
     text: String,
+    /// The text split up by its markup, and measured in the style
+    line: Line,
+    /// If true, the text has been changed since it was last drawn
+    text_changed: bool,
+
+    /// Used to send it messages, or delete it
+    id: String,
 
     /// The style to use
-    style: MonoTextStyle<'static, Rgb888>,
+    style: FontStyle,
+    /// The name of the font in the style, if it came from [`fonts`]
+    font_name: Option<String>,
 
     /// Sliding across the screen
     offset: i32,
     offset_last_incremented: Option<Instant>,
     offset_inc_interval: Duration,
 
-    /// Amount of times the text has already been shown
-    show_count: u8,
+    /// Amount of times the text will still be shown, or `None` to keep showing it forever
+    show_count: Option<u8>,
+    /// When the text is removed even if it hasn't been shown enough times, if ever
+    expires: Option<NaiveDateTime>,
+
+    /// Transition to use when shown, if not the default
+    transition: Option<Transition>,
+
+    /// Part of the display covered by an overlay, which the text is moved out of the way of
+    inset: Option<Rectangle>,
+
+    /// If true, the text is wrapped onto several lines instead of scrolling
+    multi_line: bool,
+    /// If true, the text is shown as big as it fits without scrolling
+    fit: bool,
+    /// When the first page of multi-line text was shown
+    page_started: Option<Instant>,
+    /// The page of multi-line text last drawn, and how many there were
+    page: usize,
+    pages: usize,
+    /// The size of the area the text was last laid out in, and how it was, unless it's scrolling
+    laid_out: Option<(Size, Option<Wrapped>)>,
+
+    /// Whether blinking text was shown in the last frame drawn
+    blink_shown: bool,
+
+    /// Done to the text as it's drawn, if anything
+    effect: Option<TextEffect>,
+    /// When the text started being shown, for animating the effect
+    effect_started: Option<Instant>,
+}
+
+/// Multi-line text, wrapped to fit a particular area.
+#[derive(Debug)]
+struct Wrapped {
+    style: FontStyle,
+    lines: Vec<Line>,
+    lines_per_page: usize,
+}
+
+impl Wrapped {
+    fn pages(&self) -> usize {
+        self.lines.len().div_ceil(self.lines_per_page).max(1)
+    }
 }
 
 impl TextScreen {
     /// Show the given text in a particular style.
-    pub fn new(
-        text: String,
-        style: MonoTextStyle<'static, Rgb888>,
-        show_count: Option<u8>,
-    ) -> Self {
+    pub fn new(text: String, style: impl Into<FontStyle>, show_count: Option<u8>) -> Self {
+        let style = style.into();
         Self {
-            text: text.replace("\n", ""),
+            line: Line::new(markup::parse(&text), style),
+            text,
+            text_changed: false,
+            id: "text".to_string(),
             style,
+            font_name: None,
             offset: 0,
             offset_last_incremented: None,
             offset_inc_interval: Duration::from_millis(8),
-            show_count: show_count.unwrap_or(3) + 1,
+            show_count: Some(show_count.unwrap_or(3).saturating_add(1)),
+            expires: None,
+            transition: None,
+            inset: None,
+            multi_line: false,
+            fit: false,
+            page_started: None,
+            page: 0,
+            pages: 1,
+            laid_out: None,
+            blink_shown: false,
+            effect: None,
+            effect_started: None,
         }
     }
 
+    /// Wrap the text onto as many lines as fit on the display, starting new lines wherever the text has newlines,
+    /// instead of scrolling it along a single line. The biggest font the whole text fits in is used, ignoring the
+    /// style's font. If it doesn't fit even in a small font, it's shown a page at a time.
+    pub fn multi_line(mut self) -> Self {
+        self.multi_line = true;
+        self
+    }
+
+    /// Show the text in the biggest way it fits on the display without scrolling: in [`BIG_FONT`] if it's only a few
+    /// characters long, then in the style's font, then on two lines in a small font. It only scrolls if it doesn't fit
+    /// any of those ways.
+    pub fn fit(mut self) -> Self {
+        self.fit = true;
+        self
+    }
+
+    /// Use the font with the given name from [`fonts`], instead of the style's font. See [`fonts::font_for`] for
+    /// what happens if it doesn't exist, or can't draw the text.
+    pub fn with_font(mut self, name: impl Into<String>) -> Self {
+        let name = name.into();
+        self.set_style(restyled(self.style, self.font_for(&name)));
+        self.font_name = Some(name);
+        self
+    }
+
+    /// Draw the text with the given effect, as well as any markup. The effect starts again each time it's shown.
+    pub fn with_effect(mut self, effect: TextEffect) -> Self {
+        self.effect = Some(effect);
+        self
+    }
+
+    /// Use the given transition whenever this text is shown, instead of the default.
+    pub fn with_transition(mut self, transition: Transition) -> Self {
+        self.transition = Some(transition);
+        self
+    }
+
+    /// Never remove this screen from the rotation, no matter how many times it's shown.
+    pub fn forever(mut self) -> Self {
+        self.show_count = None;
+        self
+    }
+
+    /// Use the given id instead of `text`, so this screen can be sent messages or deleted without affecting others.
+    pub fn with_id(mut self, id: impl Into<String>) -> Self {
+        self.id = id.into();
+        self
+    }
+
+    /// Remove this screen at the given time, even if it hasn't been shown as many times as it should have by then.
+    pub fn with_expiry(mut self, at: NaiveDateTime) -> Self {
+        self.expires = Some(at);
+        self
+    }
+
     /// Show the given text with a white font.
     pub fn with_text(text: String, show_count: Option<u8>) -> Self {
-        Self::new(
-            text,
-            MonoTextStyle::new(&FONT_10X20, Rgb888::WHITE),
-            show_count,
-        )
+        let style = fonts::font_for(DEFAULT_FONT, &font_text(&markup::parse(&text)))
+            .font
+            .style(Rgb888::WHITE);
+        Self::new(text, style, show_count)
+    }
+
+    /// The best version of the named font for the text. See [`fonts::font_for`].
+    fn font_for(&self, name: &str) -> Font {
+        fonts::font_for(name, &font_text(&self.line.spans)).font
+    }
+
+    /// Draw the text in the given style from now on.
+    fn set_style(&mut self, style: FontStyle) {
+        self.style = style;
+        self.line = Line::new(mem::take(&mut self.line.spans), style);
+        self.laid_out = None;
     }
 
-    /// Get the total width of the text
+    /// Get the total width of the text, on a single line
     fn text_total_width(&self) -> u32 {
-        self.style
-            .measure_string(&self.text, Point::zero(), Baseline::Middle)
-            .bounding_box
+        self.line.width
+    }
+
+    /// Whether any of the text blinks.
+    fn blinks(&self) -> bool {
+        self.effect == Some(TextEffect::Blink) || self.line.spans.iter().any(|s| s.style().blink)
+    }
+
+    /// The effect as it should be drawn now, for text of the given width, if there is one.
+    fn effect_frame(&mut self, clock: &dyn Clock, width: u32) -> Option<EffectFrame> {
+        let now = clock.now();
+        let started = *self.effect_started.get_or_insert(now);
+        Some(EffectFrame {
+            effect: self.effect?,
+            elapsed: now - started,
+            blink_visible: blink_visible(clock),
+            width,
+        })
+    }
+
+    /// How long until the effect has shown all the text, after it starts.
+    fn reveal_time(&self) -> Duration {
+        self.effect.map_or(Duration::ZERO, |e| {
+            e.reveal_time(char_count(&self.line.spans))
+        })
+    }
+
+    /// The part of the display the text should be centred in: above or below the inset, whichever is bigger.
+    fn text_area(&self, display_area: Rectangle) -> Rectangle {
+        let Some(inset) = self.inset else {
+            return display_area;
+        };
+
+        let above = (inset.top_left.y - display_area.top_left.y).max(0) as u32;
+        let below = display_area
             .size
-            .width
+            .height
+            .saturating_sub(above + inset.size.height);
+        if above >= below {
+            display_area.resized_height(above, AnchorY::Top)
+        } else {
+            display_area.resized_height(below, AnchorY::Bottom)
+        }
+    }
+
+    /// Wrap the text to fit in the given area, in the biggest font it fits in.
+    fn wrap(&self, area: Size) -> Wrapped {
+        let wrap_with = |name: &str| {
+            let style = restyled(self.style, self.font_for(name));
+            Wrapped {
+                style,
+                lines: wrap_spans(&self.line.spans, area.width, style),
+                lines_per_page: (area.height / style.line_height().max(1)).max(1) as usize,
+            }
+        };
+
+        MULTI_LINE_FONTS
+            .iter()
+            .map(|name| wrap_with(name))
+            .find(|w| w.lines.len() <= w.lines_per_page)
+            .unwrap_or_else(|| wrap_with(PAGED_FONT))
+    }
+
+    /// The text on however many lines fit in the given area, in the biggest font that fits it all without scrolling,
+    /// if it does. See [`Self::fit`].
+    fn fitted(&self, area: Size) -> Option<Wrapped> {
+        let fits = |wrapped: &Wrapped| {
+            wrapped.lines.len() <= wrapped.lines_per_page
+                && wrapped.style.line_height() <= area.height
+                && wrapped.lines.iter().all(|line| line.width <= area.width)
+        };
+        let one_line = |line: Line, style: FontStyle| Wrapped {
+            style,
+            lines: vec![line],
+            lines_per_page: 1,
+        };
+
+        // The big font only has the IBM charset, so it's not used for anything it can't draw
+        let spans = &self.line.spans;
+        let big = fonts::font_for(BIG_FONT, &font_text(spans));
+        let big = (big.name == BIG_FONT).then(|| {
+            let style = restyled(self.style, big.font);
+            one_line(Line::new(spans.clone(), style), style)
+        });
+        let normal = Some(one_line(self.line.clone(), self.style));
+        // Icons are too big to go on two lines
+        let two_lines = (!spans.iter().any(|s| matches!(s, Span::Icon(..)))).then(|| {
+            let style = restyled(self.style, self.font_for(TWO_LINE_FONT));
+            Wrapped {
+                style,
+                lines: wrap_spans(spans, area.width, style),
+                lines_per_page: (area.height / style.line_height().max(1)).min(2) as usize,
+            }
+        });
+
+        [big, normal, two_lines].into_iter().flatten().find(fits)
+    }
+
+    /// How the text is laid out in the given area, unless it's scrolling along a single line.
+    fn layout(&self, area: Size) -> Option<Wrapped> {
+        if self.multi_line {
+            Some(self.wrap(area))
+        } else if self.fit {
+            self.fitted(area)
+        } else {
+            None
+        }
+    }
+
+    /// Draw the current page of text that's been laid out on lines.
+    fn draw_lines<D: DrawTarget<Color = Rgb888>>(
+        &mut self,
+        display: &mut D,
+        clock: &dyn Clock,
+        wrapped: &Wrapped,
+    ) -> Result<(), D::Error> {
+        let area = self.text_area(display.bounding_box());
+        let now = clock.now();
+        self.pages = wrapped.pages();
+        self.page = page_at(now - *self.page_started.get_or_insert(now), self.pages);
+        self.text_changed = false;
+        self.blink_shown = blink_visible(clock);
+
+        let style = wrapped.style;
+        let lines = wrapped
+            .lines
+            .iter()
+            .skip(self.page * wrapped.lines_per_page)
+            .take(wrapped.lines_per_page);
+        let line_height = style.line_height() as i32;
+        let top = area.center().y - (lines.len() as i32 * line_height) / 2;
+
+        // Effects start again on each page
+        let mut effect = self.effect_frame(clock, 0).map(|mut e| {
+            e.elapsed = e.elapsed.saturating_sub(PAGE_DURATION * self.page as u32);
+            e
+        });
+        display.clear(Rgb888::BLACK)?;
+        for (i, line) in lines.enumerate() {
+            let middle = top + i as i32 * line_height + (line_height - 1) / 2;
+            let width = line.width;
+            let start = centred_start(area.center().x, width);
+            if let Some(effect) = &mut effect {
+                effect.width = width;
+            }
+            let drawn = draw_spans(
+                display,
+                line,
+                Point::new(start, middle),
+                style,
+                self.blink_shown,
+                effect,
+            )?;
+            // Carry on from the same character on the next line
+            if let Some(effect) = &mut effect {
+                effect.elapsed = effect
+                    .elapsed
+                    .saturating_sub(effect.effect.reveal_time(drawn));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Draw the text on a single line, scrolling it along if it doesn't fit.
+    fn draw_line<D: DrawTarget<Color = Rgb888>>(
+        &mut self,
+        display: &mut D,
+        clock: &dyn Clock,
+        area: Rectangle,
+    ) -> Result<(), D::Error> {
+        let start = match self.max_offset_for(display) {
+            None => {
+                // no need for scrolling
+                self.offset_last_incremented = None;
+                centred_start(area.center().x, self.text_total_width())
+            }
+            Some(max_offset) => {
+                // Attempt to increment offset by whatever amount
+                let now = clock.now();
+                if let Some(last_inc) = self.offset_last_incremented {
+                    let since_last_inc = now - last_inc;
+                    if since_last_inc >= self.offset_inc_interval {
+                        let num_elapsed =
+                            since_last_inc.div_duration_f32(self.offset_inc_interval) as i32;
+                        self.offset = (self.offset + num_elapsed) % max_offset as i32;
+                        self.offset_last_incremented = Some(
+                            now - (since_last_inc
+                                - (self.offset_inc_interval * num_elapsed as u32)),
+                        );
+                    }
+                } else {
+                    self.offset_last_incremented = Some(now);
+                }
+
+                display.bounding_box().bottom_right().unwrap_or_default().x - self.offset
+            }
+        };
+
+        self.text_changed = false;
+        self.blink_shown = blink_visible(clock);
+        let effect = self.effect_frame(clock, self.text_total_width());
+        display.clear(Rgb888::BLACK)?;
+        draw_spans(
+            display,
+            &self.line,
+            Point::new(start, area.center().y),
+            self.style,
+            self.blink_shown,
+            effect,
+        )?;
+
+        Ok(())
     }
 
     /// Get the maximum offset the text should be drawn at for the given display.
@@ -80,67 +474,474 @@ impl TextScreen {
 }
 
 impl<D: DrawTarget<Color = Rgb888>> Screen<D> for TextScreen {
-    fn draw(&mut self, display: &mut D) -> Result<(), D::Error> {
-        let (position, text_style) = if self.text_total_width() <= display.bounding_box().size.width
-        {
-            // no need for scrolling
-            (
-                display.bounding_box().center(),
-                TextStyleBuilder::new()
-                    .baseline(Baseline::Middle)
-                    .alignment(Alignment::Center)
-                    .build(),
-            )
-        } else {
-            // Attempt to increment offset by whatever amount
-            if let Some(last_inc) = self.offset_last_incremented {
-                let since_last_inc = Instant::now() - last_inc;
-                if since_last_inc >= self.offset_inc_interval {
-                    let num_elapsed =
-                        since_last_inc.div_duration_f32(self.offset_inc_interval) as i32;
-                    self.offset =
-                        (self.offset + num_elapsed) % self.max_offset_for(display).unwrap() as i32;
-                    self.offset_last_incremented = Some(
-                        Instant::now()
-                            - (since_last_inc - (self.offset_inc_interval * num_elapsed as u32)),
-                    );
-                }
-            } else {
-                self.offset_last_incremented = Some(Instant::now());
+    fn draw(&mut self, display: &mut D, clock: &dyn Clock) -> Result<(), D::Error> {
+        let area = self.text_area(display.bounding_box());
+        let laid_out = match self.laid_out.take() {
+            Some((size, laid_out)) if size == area.size => laid_out,
+            _ => self.layout(area.size),
+        };
+        let drawn = match &laid_out {
+            Some(wrapped) => {
+                self.offset_last_incremented = None;
+                self.draw_lines(display, clock, wrapped)
             }
-
-            (
-                Point::new(
-                    display.bounding_box().bottom_right().unwrap().x - self.offset,
-                    display.bounding_box().center().y,
-                ),
-                TextStyleBuilder::new().baseline(Baseline::Middle).build(),
-            )
+            None => self.draw_line(display, clock, area),
         };
+        self.laid_out = Some((area.size, laid_out));
 
-        display.clear(Rgb888::BLACK)?;
-        Text::with_text_style(&self.text, position, self.style, text_style).draw(display)?;
+        drawn
+    }
 
-        Ok(())
+    fn needs_redraw(&self, clock: &dyn Clock) -> bool {
+        if self.blinks() && blink_visible(clock) != self.blink_shown {
+            return true;
+        }
+        if let Some(effect) = self.effect.filter(TextEffect::animated) {
+            // Typing stops once all the text is shown
+            let typing = self
+                .effect_started
+                .is_none_or(|t| clock.now() - t <= self.reveal_time());
+            if effect != TextEffect::Typewriter || typing {
+                return true;
+            }
+        }
+        if self.multi_line {
+            // Only changes when it moves onto the next page, or been given new text
+            return self.text_changed
+                || self
+                    .page_started
+                    .is_none_or(|t| page_at(clock.now() - t, self.pages) != self.page);
+        }
+
+        // Only changes if it's scrolling, or been given new text
+        self.text_changed || self.offset_last_incremented.is_some()
     }
 
     fn single_display_duration(&self, display: &D) -> Duration {
+        let area = self.text_area(display.bounding_box()).size;
+        let laid_out;
+        let wrapped = match &self.laid_out {
+            Some((size, laid_out)) if *size == area => laid_out.as_ref(),
+            _ => {
+                laid_out = self.layout(area);
+                laid_out.as_ref()
+            }
+        };
+        if let Some(wrapped) = wrapped {
+            return match wrapped.pages() {
+                1 => Duration::from_secs(5) + self.reveal_time(),
+                pages => PAGE_DURATION * pages as u32,
+            };
+        }
+
         match self.max_offset_for(display) {
             Some(o) => o * self.offset_inc_interval,
-            None => Duration::from_secs(5),
+            // Long enough to read it once it's all been shown
+            None => Duration::from_secs(5) + self.reveal_time(),
         }
     }
 
     fn paused(&mut self, _for_dur: Duration) {
         self.offset_last_incremented = None;
-        self.show_count = self.show_count.saturating_sub(1);
+        self.page_started = None;
+        self.effect_started = None;
+        self.show_count = self.show_count.map(|c| c.saturating_sub(1));
     }
 
     fn should_remove(&self) -> bool {
-        self.show_count == 0
+        self.show_count == Some(0)
+    }
+
+    fn remaining_shows(&self) -> Option<u32> {
+        self.show_count.map(u32::from)
+    }
+
+    fn transition(&self) -> Option<Transition> {
+        self.transition
+    }
+
+    fn preempted(&mut self) {
+        // Start scrolling again from the beginning when we come back.
+        self.offset = 0;
+        self.offset_last_incremented = None;
+        self.page_started = None;
+        self.effect_started = None;
+    }
+
+    fn spec(&self) -> Option<ScreenSpec> {
+        // Fonts that aren't in the registry can't be described
+        let font = match &self.font_name {
+            Some(name) => name.as_str(),
+            None => fonts::name_of(self.style.font())?,
+        };
+
+        Some(ScreenSpec::Text {
+            text: self.text.clone(),
+            colour: colour_bytes(self.style.text_color()?),
+            // The count we keep includes the time it's being shown now
+            show_count: self.show_count.map(|c| c.saturating_sub(1)),
+            id: (self.id != "text").then(|| self.id.clone()),
+            expires: self.expires,
+            multi_line: self.multi_line,
+            fit: self.fit,
+            font: (font != DEFAULT_FONT).then(|| font.to_string()),
+            effect: self.effect,
+            transition: self.transition,
+        })
+    }
+
+    fn expires(&self) -> Option<NaiveDateTime> {
+        self.expires
+    }
+
+    fn handle_message(&mut self, message: &ScreenMessage) -> bool {
+        let ScreenMessage::Text(text) = message else {
+            return false;
+        };
+
+        // Carry on scrolling from the same place
+        self.text = text.clone();
+        self.line.spans = markup::parse(text);
+        let style = match &self.font_name {
+            // It might need a different version of the font
+            Some(name) => restyled(self.style, self.font_for(name)),
+            None => self.style,
+        };
+        self.set_style(style);
+        self.text_changed = true;
+        true
+    }
+
+    fn set_inset(&mut self, inset: Option<Rectangle>) {
+        self.inset = inset;
     }
 
     fn id(&self) -> &str {
-        "text"
+        &self.id
+    }
+}
+
+/// Which page of multi-line text to show, after they've been shown for the given time.
+fn page_at(elapsed: Duration, pages: usize) -> usize {
+    (elapsed.div_duration_f32(PAGE_DURATION) as usize) % pages.max(1)
+}
+
+/// The same style, in a different font.
+fn restyled(style: FontStyle, font: Font) -> FontStyle {
+    font.style(style.text_color().unwrap_or(Rgb888::WHITE))
+}
+
+/// Split the spans into lines no wider than the given width, breaking between words where possible, and wherever
+/// there's a newline. Icons are left out, as they're too big to go on multiple lines.
+fn wrap_spans(spans: &[Span], width: u32, style: FontStyle) -> Vec<Line> {
+    // Work a character at a time, so the style can change part way through a word
+    let mut paragraphs = vec![Vec::new()];
+    for span in spans {
+        match span {
+            Span::Text(text, style) => paragraphs
+                .last_mut()
+                .unwrap()
+                .extend(text.chars().map(|c| (c, *style))),
+            Span::Icon(..) => {}
+            Span::Newline => paragraphs.push(Vec::new()),
+        }
+    }
+    while paragraphs.len() > 1 && paragraphs.last().is_some_and(Vec::is_empty) {
+        paragraphs.pop();
+    }
+
+    let char_width = |c: char| text_width(c.encode_utf8(&mut [0; 4]), style);
+    let mut lines = Vec::new();
+    for paragraph in paragraphs {
+        let mut line = Vec::new();
+        let mut line_width = 0;
+        let words = paragraph
+            .split(|(c, _)| c.is_whitespace())
+            .filter(|w| !w.is_empty());
+        for mut word in words {
+            while !word.is_empty() {
+                let space = if line.is_empty() { 0 } else { char_width(' ') };
+                let word_width = word.iter().map(|(c, _)| char_width(*c)).sum::<u32>();
+                if line_width + space + word_width <= width {
+                    if space > 0 {
+                        line.push((' ', word[0].1));
+                    }
+                    line.extend_from_slice(word);
+                    line_width += space + word_width;
+                    break;
+                }
+
+                if !line.is_empty() {
+                    // Try again on a line of its own
+                    lines.push(mem::take(&mut line));
+                    line_width = 0;
+                } else {
+                    // Too long for any line, so break it up, with at least one character on each line
+                    let mut fits = 1;
+                    let mut fits_width = char_width(word[0].0);
+                    while let Some((c, _)) = word.get(fits) {
+                        fits_width += char_width(*c);
+                        if fits_width > width {
+                            break;
+                        }
+                        fits += 1;
+                    }
+                    let (start, rest) = word.split_at(fits);
+                    lines.push(start.to_vec());
+                    word = rest;
+                }
+            }
+        }
+        lines.push(line);
+    }
+
+    lines
+        .into_iter()
+        .map(|line| Line::new(join_chars(line), style))
+        .collect()
+}
+
+/// Turn styled characters back into spans.
+fn join_chars(chars: Vec<(char, SpanStyle)>) -> Vec<Span> {
+    let mut spans: Vec<Span> = Vec::new();
+    for (c, style) in chars {
+        match spans.last_mut() {
+            Some(Span::Text(text, s)) if *s == style => text.push(c),
+            _ => spans.push(Span::Text(c.to_string(), style)),
+        }
     }
+
+    spans
+}
+
+/// Where something of the given width starts, to be centred on the given x.
+/// This is the same as [`embedded_graphics::text::Alignment::Center`].
+fn centred_start(centre: i32, width: u32) -> i32 {
+    centre - (width as i32 - 1) / 2
+}
+
+/// Whether blinking text should be shown at the moment.
+fn blink_visible(clock: &dyn Clock) -> bool {
+    clock.local_now().and_utc().timestamp_subsec_millis() < BLINK_ON.as_millis() as u32
+}
+
+/// The text the font has to be able to draw: everything but the markup, and emoji.
+fn font_text(spans: &[Span]) -> String {
+    markup::plain_text(spans)
+        .chars()
+        .filter(|c| Emoji::for_char(*c).is_none() && !emoji::is_modifier(*c))
+        .collect()
+}
+
+/// Spans to be drawn one after the other on a single line, split into runs and measured in a particular style.
+/// Finding fonts for characters the style's font can't draw is slow, so this is only worked out when the text or the
+/// style changes, not every frame.
+#[derive(Debug, Clone)]
+struct Line {
+    spans: Vec<Span>,
+    /// The runs each span is drawn in, which is none for icons and newlines
+    runs: Vec<Vec<Run>>,
+    /// How far along the line all the spans take up
+    width: u32,
+}
+
+impl Line {
+    fn new(spans: Vec<Span>, style: FontStyle) -> Self {
+        let runs = spans
+            .iter()
+            .map(|span| match span {
+                Span::Text(text, _) => runs(text, style),
+                Span::Icon(..) | Span::Newline => Vec::new(),
+            })
+            .collect::<Vec<_>>();
+        let width = spans
+            .iter()
+            .zip(&runs)
+            .map(|(span, runs)| span_width(span, runs))
+            .sum();
+
+        Self { spans, runs, width }
+    }
+}
+
+/// Part of some text that's drawn all in one go.
+#[derive(Debug, Clone)]
+enum Run {
+    /// Text that's all drawn in the same font.
+    Text(String, FontStyle),
+    Emoji(Emoji),
+}
+
+impl Run {
+    fn width(&self) -> u32 {
+        match self {
+            Run::Text(text, style) => string_width(text, *style),
+            Run::Emoji(_) => Emoji::SIZE.width + 2 * EMOJI_PADDING,
+        }
+    }
+}
+
+/// Split the text up into runs in the style's font, runs in whichever fonts can draw the characters it can't, and
+/// emoji. Characters no font can draw are left in the style's font, to be drawn however it draws them.
+fn runs(text: &str, style: FontStyle) -> Vec<Run> {
+    let font = style.font();
+    let special = |c: char| Emoji::for_char(c).is_some() || emoji::is_modifier(c);
+    if font.supports(text) && !text.chars().any(special) {
+        return vec![Run::Text(text.to_string(), style)];
+    }
+
+    let mut runs = Vec::new();
+    let mut current: Option<(usize, Font)> = None;
+    for (i, c) in text.char_indices() {
+        let emoji = Emoji::for_char(c);
+        let run_font = (emoji.is_none() && !emoji::is_modifier(c)).then(|| {
+            Some(font)
+                .filter(|f| f.supports(c.encode_utf8(&mut [0; 4])))
+                .or_else(|| Some(fonts::fallback_for(font, c)?.font))
+                .unwrap_or(font)
+        });
+        if let Some((start, f)) = current.filter(|(_, f)| Some(*f) != run_font) {
+            runs.push(Run::Text(text[start..i].to_string(), restyled(style, f)));
+            current = None;
+        }
+        match (emoji, run_font) {
+            (Some(emoji), _) => runs.push(Run::Emoji(emoji)),
+            (None, Some(f)) if current.is_none() => current = Some((i, f)),
+            // Carrying on the current run, or a modifier that's left out
+            _ => {}
+        }
+    }
+    if let Some((start, f)) = current {
+        runs.push(Run::Text(text[start..].to_string(), restyled(style, f)));
+    }
+
+    runs
+}
+
+/// How far along the line the text takes up, all in the style's font.
+fn string_width(text: &str, style: FontStyle) -> u32 {
+    style
+        .measure_string(text, Point::zero(), Baseline::Middle)
+        .next_position
+        .x
+        .max(0) as u32
+}
+
+/// How far along the line the text takes up, including any parts drawn in other fonts or as emoji.
+fn text_width(text: &str, style: FontStyle) -> u32 {
+    runs(text, style).iter().map(Run::width).sum()
+}
+
+/// How far along the line a span drawn in the given runs takes up.
+fn span_width(span: &Span, runs: &[Run]) -> u32 {
+    match span {
+        Span::Text(_, span_style) => {
+            runs.iter().map(Run::width).sum::<u32>() + u32::from(span_style.bold)
+        }
+        Span::Icon(..) => Icon::SIZE.width + 2 * ICON_PADDING,
+        Span::Newline => 0,
+    }
+}
+
+/// Draw the spans one after the other on a single line, starting from the middle of its left end, with the effect if
+/// there is one. Newlines are ignored. Returns how many characters there were, including icons and emoji.
+fn draw_spans<D: DrawTarget<Color = Rgb888>>(
+    display: &mut D,
+    line: &Line,
+    start: Point,
+    style: FontStyle,
+    blink_visible: bool,
+    effect: Option<EffectFrame>,
+) -> Result<usize, D::Error> {
+    let mut position = start;
+    let mut index = 0;
+    // The colour and offset of the next character, with the effect applied, or `None` if it's hidden
+    let mut look = |x: i32, colour: Rgb888| {
+        index += 1;
+        let Some(effect) = effect else {
+            return Some((colour, Point::zero()));
+        };
+        let look = effect.look(index - 1, (x - start.x).max(0) as u32)?;
+        Some((look.colour.unwrap_or(colour), Point::new(0, look.offset)))
+    };
+
+    for (span, runs) in line.spans.iter().zip(&line.runs) {
+        let span_style = span.style();
+        let colour = span_style
+            .colour
+            .or(style.text_color())
+            .unwrap_or(Rgb888::WHITE);
+        let shown = !span_style.blink || blink_visible;
+        match span {
+            Span::Text(..) => {
+                for run in runs {
+                    let (text, style) = match run {
+                        Run::Text(text, style) => (text.as_str(), *style),
+                        Run::Emoji(emoji) => {
+                            if let Some((_, offset)) = look(position.x, colour).filter(|_| shown) {
+                                let top_left = position
+                                    + offset
+                                    + Point::new(
+                                        EMOJI_PADDING as i32,
+                                        -(Emoji::SIZE.height as i32 / 2),
+                                    );
+                                emoji.draw(display, top_left)?;
+                            }
+                            position.x += run.width() as i32;
+                            continue;
+                        }
+                    };
+
+                    // Effects can change each character, so they're drawn one at a time
+                    let pieces = match effect {
+                        Some(_) => text
+                            .char_indices()
+                            .map(|(i, c)| &text[i..i + c.len_utf8()])
+                            .collect(),
+                        None => vec![text],
+                    };
+                    for piece in pieces {
+                        if let Some((colour, offset)) = look(position.x, colour).filter(|_| shown) {
+                            let mut style = style;
+                            style.set_text_color(Some(colour));
+                            let position = position + offset;
+                            Text::with_baseline(piece, position, style, Baseline::Middle)
+                                .draw(display)?;
+                            if span_style.bold {
+                                let position = position + Point::new(1, 0);
+                                Text::with_baseline(piece, position, style, Baseline::Middle)
+                                    .draw(display)?;
+                            }
+                        }
+                        position.x += string_width(piece, style) as i32;
+                    }
+                }
+                position.x += i32::from(span_style.bold);
+            }
+            Span::Icon(icon, _) => {
+                if let Some((colour, offset)) = look(position.x, colour).filter(|_| shown) {
+                    let top_left = position
+                        + offset
+                        + Point::new(ICON_PADDING as i32, -(Icon::SIZE.height as i32 / 2));
+                    icon.draw(display, top_left, colour)?;
+                }
+                position.x += span_width(span, runs) as i32;
+            }
+            Span::Newline => {}
+        }
+    }
+
+    Ok(index)
+}
+
+/// How many characters there are in the spans, including icons and emoji.
+fn char_count(spans: &[Span]) -> usize {
+    spans
+        .iter()
+        .map(|span| match span {
+            Span::Text(text, _) => text.chars().filter(|c| !emoji::is_modifier(*c)).count(),
+            Span::Icon(..) => 1,
+            Span::Newline => 0,
+        })
+        .sum()
 }
@@ -0,0 +1,107 @@
+use std::{fmt, time::Duration};
+
+use chrono::NaiveDateTime;
+use embedded_graphics::{pixelcolor::Rgb888, prelude::*, primitives::Rectangle};
+
+use crate::{clock::Clock, schedule::Schedule, transition::Transition};
+
+use super::{Priority, Screen, ScreenMessage, ScreenSpec};
+
+/// Wraps any screen so that it's shown for the given amount of time each time it comes up, instead of however long
+/// it would choose itself.
+pub struct Timed<D: DrawTarget<Color = Rgb888>> {
+    inner: Box<dyn Screen<D>>,
+    duration: Duration,
+}
+
+impl<D: DrawTarget<Color = Rgb888>> fmt::Debug for Timed<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Timed")
+            .field("inner", &self.inner)
+            .field("duration", &self.duration)
+            .finish()
+    }
+}
+
+impl<D: DrawTarget<Color = Rgb888>> Timed<D> {
+    pub fn new(inner: Box<dyn Screen<D>>, duration: Duration) -> Self {
+        Self { inner, duration }
+    }
+}
+
+impl<D: DrawTarget<Color = Rgb888>> Screen<D> for Timed<D> {
+    fn draw(&mut self, display: &mut D, clock: &dyn Clock) -> Result<(), D::Error> {
+        self.inner.draw(display, clock)
+    }
+
+    fn needs_redraw(&self, clock: &dyn Clock) -> bool {
+        self.inner.needs_redraw(clock)
+    }
+
+    fn single_display_duration(&self, _display: &D) -> Duration {
+        self.duration
+    }
+
+    fn paused(&mut self, for_dur: Duration) {
+        self.inner.paused(for_dur)
+    }
+
+    fn should_remove(&self) -> bool {
+        self.inner.should_remove()
+    }
+
+    fn id(&self) -> &str {
+        self.inner.id()
+    }
+
+    fn kind(&self) -> &str {
+        self.inner.kind()
+    }
+
+    fn remaining_shows(&self) -> Option<u32> {
+        self.inner.remaining_shows()
+    }
+
+    fn grab_attention(&self) -> bool {
+        self.inner.grab_attention()
+    }
+
+    fn priority(&self) -> Priority {
+        self.inner.priority()
+    }
+
+    fn transition(&self) -> Option<Transition> {
+        self.inner.transition()
+    }
+
+    fn schedule(&self) -> Option<&Schedule> {
+        self.inner.schedule()
+    }
+
+    fn preempted(&mut self) {
+        self.inner.preempted()
+    }
+
+    fn handle_message(&mut self, message: &ScreenMessage) -> bool {
+        self.inner.handle_message(message)
+    }
+
+    fn set_inset(&mut self, inset: Option<Rectangle>) {
+        self.inner.set_inset(inset)
+    }
+
+    fn spec(&self) -> Option<ScreenSpec> {
+        Some(ScreenSpec::Timed {
+            seconds: self.duration.as_secs_f32(),
+            screen: Box::new(self.inner.spec()?),
+        })
+    }
+
+    fn expires(&self) -> Option<NaiveDateTime> {
+        self.inner.expires()
+    }
+
+    fn failure(&self) -> Option<String> {
+        self.inner.failure()
+    }
+}
@@ -0,0 +1,57 @@
+//! What to show while the display is sleeping, ie when nobody's in the space.
+
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// What [`crate::DisplayLogic`] shows while it's sleeping.
+/// Everything is shown at the sleep brightness, and the rotations carry on from where they were when it wakes up.
+///
+/// Can be parsed from `"blank"`, `"clock"`, `"ambient"`, or `"playlist <name>"`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum SleepMode {
+    /// Turn the display off.
+    Blank,
+    /// Show the time.
+    #[default]
+    Clock,
+    /// Show a slow animation. See [`crate::screens::AmbientScreen`].
+    Ambient,
+    /// Show the screens in the [`crate::playlist::Playlist`] with the given name, from the start.
+    Playlist(String),
+}
+
+string_format!(SleepMode, ParseSleepModeError, "sleep mode");
+
+impl FromStr for SleepMode {
+    type Err = ParseSleepModeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().split_once(' ') {
+            None => match s.trim() {
+                "blank" => Ok(SleepMode::Blank),
+                "clock" => Ok(SleepMode::Clock),
+                "ambient" => Ok(SleepMode::Ambient),
+                _ => Err(ParseSleepModeError(format!(
+                    "{s:?} should be blank, clock, ambient or playlist <name>"
+                ))),
+            },
+            Some(("playlist", name)) => Ok(SleepMode::Playlist(name.trim().to_string())),
+            Some(_) => Err(ParseSleepModeError(format!(
+                "{s:?} should be blank, clock, ambient or playlist <name>"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for SleepMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SleepMode::Blank => write!(f, "blank"),
+            SleepMode::Clock => write!(f, "clock"),
+            SleepMode::Ambient => write!(f, "ambient"),
+            SleepMode::Playlist(name) => write!(f, "playlist {name}"),
+        }
+    }
+}
@@ -0,0 +1,217 @@
+//! Animated transitions between screens.
+
+use std::{fmt, str::FromStr, time::Duration};
+
+use embedded_graphics::{pixelcolor::Rgb888, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::framebuffer::Framebuffer;
+
+/// The different styles of transition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TransitionKind {
+    /// Switch immediately to the next screen.
+    #[default]
+    Cut,
+    /// Fade between the two screens.
+    Crossfade,
+    /// The next screen is revealed from left to right.
+    Wipe,
+    /// The next screen pushes the previous one off to the left.
+    SlideLeft,
+    /// The next screen pushes the previous one off the top.
+    SlideUp,
+    /// The next screen appears pixel by pixel, in a scattered pattern.
+    Dissolve,
+}
+
+/// How long a transition takes if it doesn't say.
+const DEFAULT_DURATION: Duration = Duration::from_millis(400);
+
+impl TransitionKind {
+    const ALL: [TransitionKind; 6] = [
+        TransitionKind::Cut,
+        TransitionKind::Crossfade,
+        TransitionKind::Wipe,
+        TransitionKind::SlideLeft,
+        TransitionKind::SlideUp,
+        TransitionKind::Dissolve,
+    ];
+
+    /// What it's called when it's parsed, ie in the config.
+    fn name(self) -> &'static str {
+        match self {
+            TransitionKind::Cut => "cut",
+            TransitionKind::Crossfade => "crossfade",
+            TransitionKind::Wipe => "wipe",
+            TransitionKind::SlideLeft => "slide-left",
+            TransitionKind::SlideUp => "slide-up",
+            TransitionKind::Dissolve => "dissolve",
+        }
+    }
+}
+
+/// A transition between two screens, played when the displayed screen changes.
+///
+/// Can be parsed from the name of its kind, ie `"cut"`, `"crossfade"`, `"wipe"`, `"slide-left"`, `"slide-up"` or
+/// `"dissolve"`, followed by how long it takes, like `"crossfade 400ms"`. If the duration isn't given, it's 400ms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Transition {
+    pub kind: TransitionKind,
+    pub duration: Duration,
+}
+
+impl Transition {
+    pub const fn new(kind: TransitionKind, duration: Duration) -> Self {
+        Self { kind, duration }
+    }
+
+    /// A transition that switches immediately.
+    pub const fn cut() -> Self {
+        Self::new(TransitionKind::Cut, Duration::ZERO)
+    }
+
+    /// How far through the transition we are after `elapsed`, from 0 to 1.
+    pub fn progress(&self, elapsed: Duration) -> f32 {
+        if self.kind == TransitionKind::Cut || elapsed >= self.duration {
+            1.0
+        } else {
+            elapsed.div_duration_f32(self.duration)
+        }
+    }
+
+    /// Draw the frame `progress` of the way through transitioning `from` one screen `to` another.
+    /// All three buffers must be the same size.
+    pub fn blend(
+        &self,
+        progress: f32,
+        from: &Framebuffer,
+        to: &Framebuffer,
+        out: &mut Framebuffer,
+    ) {
+        let size = out.size();
+        let (width, height) = (size.width as i32, size.height as i32);
+
+        match self.kind {
+            TransitionKind::Cut => out.pixels_mut().copy_from_slice(to.pixels()),
+            TransitionKind::Crossfade => {
+                for ((o, f), t) in out
+                    .pixels_mut()
+                    .iter_mut()
+                    .zip(from.pixels())
+                    .zip(to.pixels())
+                {
+                    *o = lerp(*f, *t, progress);
+                }
+            }
+            TransitionKind::Wipe => {
+                let edge = (progress * width as f32) as i32;
+                for p in out.bounding_box().points() {
+                    let src = if p.x < edge { to } else { from };
+                    out.set_pixel(p, src.pixel(p).unwrap());
+                }
+            }
+            TransitionKind::SlideLeft => {
+                let offset = (progress * width as f32) as i32;
+                for p in out.bounding_box().points() {
+                    let colour = if p.x < width - offset {
+                        from.pixel(p + Point::new(offset, 0))
+                    } else {
+                        to.pixel(p - Point::new(width - offset, 0))
+                    };
+                    out.set_pixel(p, colour.unwrap());
+                }
+            }
+            TransitionKind::SlideUp => {
+                let offset = (progress * height as f32) as i32;
+                for p in out.bounding_box().points() {
+                    let colour = if p.y < height - offset {
+                        from.pixel(p + Point::new(0, offset))
+                    } else {
+                        to.pixel(p - Point::new(0, height - offset))
+                    };
+                    out.set_pixel(p, colour.unwrap());
+                }
+            }
+            TransitionKind::Dissolve => {
+                for p in out.bounding_box().points() {
+                    let src = if dissolve_threshold(p) < progress {
+                        to
+                    } else {
+                        from
+                    };
+                    out.set_pixel(p, src.pixel(p).unwrap());
+                }
+            }
+        }
+    }
+}
+
+string_format!(Transition, ParseTransitionError, "transition");
+
+impl FromStr for Transition {
+    type Err = ParseTransitionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let kind = TransitionKind::ALL
+            .into_iter()
+            .find(|k| k.name() == name)
+            .ok_or_else(|| {
+                ParseTransitionError(format!(
+                    "{name:?} should be cut, crossfade, wipe, slide-left, slide-up or dissolve"
+                ))
+            })?;
+        let duration = match words.next() {
+            _ if kind == TransitionKind::Cut => Duration::ZERO,
+            None => DEFAULT_DURATION,
+            Some(duration) => duration
+                .strip_suffix("ms")
+                .and_then(|ms| ms.parse().ok())
+                .map(Duration::from_millis)
+                .ok_or_else(|| {
+                    ParseTransitionError(format!(
+                        "{duration:?} should be a number of milliseconds, like 400ms"
+                    ))
+                })?,
+        };
+        if let Some(extra) = words.next() {
+            return Err(ParseTransitionError(format!(
+                "didn't expect {extra:?} in {s:?}"
+            )));
+        }
+
+        Ok(Self::new(kind, duration))
+    }
+}
+
+impl fmt::Display for Transition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            TransitionKind::Cut => write!(f, "cut"),
+            kind => write!(f, "{} {}ms", kind.name(), self.duration.as_millis()),
+        }
+    }
+}
+
+/// Linearly interpolate between two colours.
+pub(crate) fn lerp(from: Rgb888, to: Rgb888, t: f32) -> Rgb888 {
+    let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+    Rgb888::new(
+        channel(from.r(), to.r()),
+        channel(from.g(), to.g()),
+        channel(from.b(), to.b()),
+    )
+}
+
+/// A pseudo-random but stable value in `0..1` for each point, used to decide when it switches over in a dissolve.
+fn dissolve_threshold(p: Point) -> f32 {
+    let mut h = (p.x as u32).wrapping_mul(0x9E37_79B1) ^ (p.y as u32).wrapping_mul(0x85EB_CA77);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x2C1B_3C6D);
+    h ^= h >> 12;
+
+    (h & 0xFFFF) as f32 / 0x10000 as f32
+}
@@ -0,0 +1,79 @@
+//! Tests for reading the configuration file.
+
+use std::sync::{atomic::AtomicBool, mpsc, Arc};
+
+use logic::{
+    config::{Config, DEFAULT_CONFIG},
+    sleep::SleepMode,
+    DisplayLogic,
+};
+
+#[test]
+fn default_config_is_valid() {
+    let config: Config = DEFAULT_CONFIG.parse().unwrap();
+    assert!(!config.screens.is_empty());
+}
+
+#[test]
+fn screens_are_added_in_order() {
+    let config: Config = r#"
+        layout = "main 0,0 152x32; clock 152,0 40x32"
+        sleep_mode = "ambient"
+
+        [[screens]]
+        kind = "text"
+        text = "first"
+        id = "first"
+        duration = 10
+
+        [[screens]]
+        kind = "clock"
+        zone = "clock"
+
+        [[screens]]
+        kind = "text"
+        text = "second"
+        id = "second"
+        schedule = "daily"
+    "#
+    .parse()
+    .unwrap();
+    assert_eq!(config.sleep_mode, Some(SleepMode::Ambient));
+
+    let (_send, recv) = mpsc::channel();
+    let mut logic = DisplayLogic::new(recv, Arc::new(AtomicBool::new(false)));
+    config.apply(&mut logic);
+    config.add_screens(&mut logic);
+    let screens = logic
+        .state()
+        .screens
+        .into_iter()
+        .map(|s| (s.id, s.zone, s.display_duration.as_secs()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        screens,
+        [
+            ("first".to_string(), "main".to_string(), 10),
+            ("second".to_string(), "main".to_string(), 5),
+            ("clock".to_string(), "clock".to_string(), 5),
+        ]
+    );
+}
+
+#[test]
+fn mistakes_are_explained() {
+    let error = |toml: &str| toml.parse::<Config>().unwrap_err().to_string();
+
+    let e = error("[[screens]]\nkind = \"text\"\ntext = \"hi\"\ncolor = [1, 2, 3]");
+    assert!(e.contains("color"), "{e}");
+    let e = error("[[screens]]\nkind = \"sparkles\"");
+    assert!(e.contains("sparkles"), "{e}");
+    let e = error("[[screens]]\nkind = \"test\"\nzone = \"nowhere\"");
+    assert!(e.contains("nowhere"), "{e}");
+    let e = error("[[screens]]\nkind = \"test\"\nschedule = \"someday\"");
+    assert!(e.contains("line 3"), "{e}");
+    let e = error("[[screens]]\nkind = \"test\"\nduration = -1");
+    assert!(e.contains("duration"), "{e}");
+    let e = error("layot = \"main 0,0 1x1\"");
+    assert!(e.contains("layot"), "{e}");
+}
@@ -0,0 +1,926 @@
+//! Tests for how [`DisplayLogic`] schedules and draws screens, driven by a [`FakeClock`].
+
+use std::{
+    convert::Infallible,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    time::Duration,
+};
+
+use chrono::NaiveDate;
+use embedded_graphics::{pixelcolor::Rgb888, prelude::*};
+use logic::{
+    clock::{Clock, FakeClock},
+    framebuffer::Framebuffer,
+    layout::Layout,
+    overlay::Toast,
+    playlist::Playlist,
+    schedule::Scheduled,
+    screens::{
+        EnvironmentScreen, Priority, Screen, ScreenMessage, ScreenSpec, TextEffect, TextScreen,
+    },
+    sleep::SleepMode,
+    transition::{Transition, TransitionKind},
+    Command, DisplayLogic, PANEL_SIZE,
+};
+
+/// A screen that fills the display with one colour, and counts how many times it's drawn.
+#[derive(Debug)]
+struct CountingScreen {
+    id: &'static str,
+    colour: Rgb888,
+    priority: Priority,
+    draws: Arc<AtomicUsize>,
+}
+
+impl CountingScreen {
+    fn new(id: &'static str, colour: Rgb888) -> (Self, Arc<AtomicUsize>) {
+        let draws = Arc::new(AtomicUsize::new(0));
+        (
+            Self {
+                id,
+                colour,
+                priority: Priority::Normal,
+                draws: draws.clone(),
+            },
+            draws,
+        )
+    }
+
+    fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+}
+
+impl Screen<Framebuffer> for CountingScreen {
+    fn draw(&mut self, display: &mut Framebuffer, _clock: &dyn Clock) -> Result<(), Infallible> {
+        self.draws.fetch_add(1, Ordering::Relaxed);
+        display.clear(self.colour)
+    }
+
+    fn needs_redraw(&self, _clock: &dyn Clock) -> bool {
+        false
+    }
+
+    fn priority(&self) -> Priority {
+        self.priority
+    }
+
+    fn id(&self) -> &str {
+        self.id
+    }
+}
+
+/// A screen that panics whenever it's drawn.
+#[derive(Debug)]
+struct PanickingScreen;
+
+impl Screen<Framebuffer> for PanickingScreen {
+    fn draw(&mut self, _display: &mut Framebuffer, _clock: &dyn Clock) -> Result<(), Infallible> {
+        panic!("oops");
+    }
+
+    fn id(&self) -> &str {
+        "panicking"
+    }
+}
+
+/// Set up display logic with a fake clock, returning a way to send it commands and control the time.
+fn setup() -> (DisplayLogic, mpsc::Sender<Command>, FakeClock) {
+    setup_sleeping(Arc::new(AtomicBool::new(false)))
+}
+
+/// [`setup`], but using the given flag to tell it when to sleep.
+fn setup_sleeping(sleep: Arc<AtomicBool>) -> (DisplayLogic, mpsc::Sender<Command>, FakeClock) {
+    let (send, recv) = mpsc::channel();
+    let mut logic = DisplayLogic::new(recv, sleep);
+    let clock = FakeClock::new(
+        NaiveDate::from_ymd_opt(2025, 6, 3)
+            .unwrap()
+            .and_hms_opt(19, 30, 0)
+            .unwrap(),
+    );
+    logic.set_clock(Box::new(clock.clone()));
+
+    (logic, send, clock)
+}
+
+#[test]
+fn static_screens_are_only_drawn_once() {
+    let (mut logic, _send, clock) = setup();
+    let (screen, draws) = CountingScreen::new("a", Rgb888::RED);
+    logic.add(Box::new(screen));
+
+    let mut display = Framebuffer::new(PANEL_SIZE);
+    for _ in 0..10 {
+        logic.draw(&mut display).unwrap();
+        clock.advance(Duration::from_millis(10));
+    }
+
+    assert_eq!(draws.load(Ordering::Relaxed), 1);
+    assert!(display.pixels().iter().all(|c| *c == Rgb888::RED));
+}
+
+#[test]
+fn screens_added_to_an_empty_rotation_are_drawn() {
+    let (mut logic, send, _clock) = setup();
+    let (a, _) = CountingScreen::new("a", Rgb888::RED);
+    logic.add(Box::new(a));
+    let mut display = Framebuffer::new(PANEL_SIZE);
+    logic.draw(&mut display).unwrap();
+
+    for emptied in [Command::Delete("a".to_string()), Command::ClearAll] {
+        send.send(emptied).unwrap();
+        logic.draw(&mut display).unwrap();
+        assert!(display.pixels().iter().all(|c| *c == Rgb888::BLACK));
+
+        // Static text never asks to be redrawn, so this is the only chance it gets
+        send.send(Command::Add(Box::new(TextScreen::with_text(
+            "hello".to_string(),
+            None,
+        ))))
+        .unwrap();
+        logic.draw(&mut display).unwrap();
+        assert!(display.pixels().iter().any(|c| *c != Rgb888::BLACK));
+        send.send(Command::Delete("text".to_string())).unwrap();
+    }
+}
+
+#[test]
+fn upserting_the_shown_screen_redraws_it() {
+    let (mut logic, send, clock) = setup();
+    let scroller = || {
+        TextScreen::with_text(
+            "some much longer text that goes off the screen".to_string(),
+            None,
+        )
+        .with_id("scroller")
+    };
+    logic.add(Box::new(scroller()));
+    let mut display = Framebuffer::new(PANEL_SIZE);
+    logic.draw(&mut display).unwrap();
+    clock.advance(Duration::from_secs(1));
+    logic.draw(&mut display).unwrap();
+
+    // The new one starts scrolling from the beginning, and carries on
+    send.send(Command::Upsert(Box::new(scroller()))).unwrap();
+    let mut last = display.clone();
+    for _ in 0..5 {
+        clock.advance(Duration::from_millis(100));
+        logic.draw(&mut display).unwrap();
+        assert_ne!(display, last);
+        last = display.clone();
+    }
+}
+
+#[test]
+fn rotates_after_display_duration() {
+    let (mut logic, _send, clock) = setup();
+    let (a, _) = CountingScreen::new("a", Rgb888::RED);
+    let (b, _) = CountingScreen::new("b", Rgb888::BLUE);
+    logic.add(Box::new(a));
+    logic.add(Box::new(b));
+
+    let mut display = Framebuffer::new(PANEL_SIZE);
+    logic.draw(&mut display).unwrap();
+    assert_eq!(logic.state().screens[0].id, "a");
+
+    clock.advance(Duration::from_millis(4999));
+    logic.draw(&mut display).unwrap();
+    assert_eq!(logic.state().screens[0].id, "a");
+
+    clock.advance(Duration::from_millis(1));
+    logic.draw(&mut display).unwrap();
+    assert_eq!(logic.state().screens[0].id, "b");
+    assert!(display.pixels().iter().all(|c| *c == Rgb888::BLUE));
+}
+
+#[test]
+fn alerts_preempt_normal_screens() {
+    let (mut logic, send, clock) = setup();
+    let (a, _) = CountingScreen::new("a", Rgb888::RED);
+    let (b, _) = CountingScreen::new("b", Rgb888::BLUE);
+    logic.add(Box::new(a));
+    logic.add(Box::new(b));
+    let mut display = Framebuffer::new(PANEL_SIZE);
+    logic.draw(&mut display).unwrap();
+
+    let (alert, _) = CountingScreen::new("alert", Rgb888::YELLOW);
+    send.send(Command::Add(Box::new(alert.with_priority(Priority::Alert))))
+        .unwrap();
+    logic.draw(&mut display).unwrap();
+    assert_eq!(ids(&logic), ["alert", "a", "b"]);
+    assert!(display.pixels().iter().all(|c| *c == Rgb888::YELLOW));
+
+    // Nothing else is shown while it's there
+    clock.advance(Duration::from_secs(10));
+    logic.draw(&mut display).unwrap();
+    assert_eq!(logic.state().screens[0].id, "alert");
+    assert!(display.pixels().iter().all(|c| *c == Rgb888::YELLOW));
+}
+
+#[test]
+fn lower_priority_screens_dont_preempt() {
+    let (mut logic, send, _clock) = setup();
+    let (alert, _) = CountingScreen::new("alert", Rgb888::YELLOW);
+    logic.add(Box::new(alert.with_priority(Priority::Alert)));
+    let mut display = Framebuffer::new(PANEL_SIZE);
+    logic.draw(&mut display).unwrap();
+
+    let (a, a_draws) = CountingScreen::new("a", Rgb888::RED);
+    let (background, _) = CountingScreen::new("background", Rgb888::BLUE);
+    send.send(Command::Add(Box::new(a))).unwrap();
+    send.send(Command::Add(Box::new(
+        background.with_priority(Priority::Background),
+    )))
+    .unwrap();
+    logic.draw(&mut display).unwrap();
+    assert_eq!(logic.state().screens[0].id, "alert");
+    assert_eq!(a_draws.load(Ordering::Relaxed), 0);
+    assert!(display.pixels().iter().all(|c| *c == Rgb888::YELLOW));
+}
+
+#[test]
+fn preempted_screens_carry_on_afterwards() {
+    let (mut logic, send, clock) = setup();
+    logic.add(Box::new(TextScreen::with_text(
+        "back soon".to_string(),
+        Some(2),
+    )));
+    let (b, _) = CountingScreen::new("b", Rgb888::BLUE);
+    logic.add(Box::new(b));
+    let mut display = Framebuffer::new(PANEL_SIZE);
+    logic.draw(&mut display).unwrap();
+    let shows = logic.state().screens[0].remaining_shows;
+
+    clock.advance(Duration::from_secs(2));
+    let (alert, _) = CountingScreen::new("alert", Rgb888::YELLOW);
+    send.send(Command::Add(Box::new(alert.with_priority(Priority::Alert))))
+        .unwrap();
+    logic.draw(&mut display).unwrap();
+    clock.advance(Duration::from_secs(10));
+    send.send(Command::Delete("alert".to_string())).unwrap();
+    logic.draw(&mut display).unwrap();
+
+    // Back to the same screen, without it counting as having been shown
+    let state = logic.state();
+    assert_eq!(ids(&logic), ["text", "b"]);
+    assert_eq!(state.screens[0].remaining_shows, shows);
+    assert_eq!(state.screens[0].time_left, Some(Duration::from_secs(5)));
+    let mut expected = Framebuffer::new(PANEL_SIZE);
+    let Ok(()) = TextScreen::with_text("back soon".to_string(), None).draw(&mut expected, &clock);
+    assert_eq!(display, expected);
+}
+
+#[test]
+fn transitions_cut_by_default() {
+    let (mut logic, _send, clock) = setup();
+    let (a, _) = CountingScreen::new("a", Rgb888::RED);
+    let (b, _) = CountingScreen::new("b", Rgb888::BLUE);
+    logic.add(Box::new(a));
+    logic.add(Box::new(b));
+
+    let mut display = Framebuffer::new(PANEL_SIZE);
+    logic.draw(&mut display).unwrap();
+    clock.advance(Duration::from_secs(5));
+    logic.draw(&mut display).unwrap();
+    assert!(display.pixels().iter().all(|c| *c == Rgb888::BLUE));
+}
+
+#[test]
+fn transitions_blend_between_screens() {
+    type Check = fn(&Framebuffer) -> bool;
+    let halfway: [(TransitionKind, Check); 5] = [
+        (TransitionKind::Crossfade, |d| {
+            d.pixels().iter().all(|c| *c == Rgb888::new(128, 0, 128))
+        }),
+        (TransitionKind::Wipe, |d| {
+            colour_at(d, 95, 0) == Rgb888::BLUE && colour_at(d, 96, 0) == Rgb888::RED
+        }),
+        // The new screen comes in from the right
+        (TransitionKind::SlideLeft, |d| {
+            colour_at(d, 95, 0) == Rgb888::RED && colour_at(d, 96, 0) == Rgb888::BLUE
+        }),
+        (TransitionKind::SlideUp, |d| {
+            colour_at(d, 0, 15) == Rgb888::RED && colour_at(d, 0, 16) == Rgb888::BLUE
+        }),
+        (TransitionKind::Dissolve, |d| {
+            d.pixels().contains(&Rgb888::RED) && d.pixels().contains(&Rgb888::BLUE)
+        }),
+    ];
+    for (kind, check) in halfway {
+        let (mut logic, _send, clock) = setup();
+        logic.set_default_transition(Transition::new(kind, Duration::from_secs(1)));
+        let (a, _) = CountingScreen::new("a", Rgb888::RED);
+        let (b, _) = CountingScreen::new("b", Rgb888::BLUE);
+        logic.add(Box::new(a));
+        logic.add(Box::new(b));
+
+        // The first screen comes in with the transition too
+        let mut display = Framebuffer::new(PANEL_SIZE);
+        for _ in 0..5 {
+            logic.draw(&mut display).unwrap();
+            clock.advance(Duration::from_secs(1));
+        }
+        logic.draw(&mut display).unwrap();
+        assert!(
+            display.pixels().iter().all(|c| *c == Rgb888::RED),
+            "{kind:?} should start from the old screen"
+        );
+
+        clock.advance(Duration::from_millis(500));
+        logic.draw(&mut display).unwrap();
+        assert!(check(&display), "{kind:?} isn't halfway through");
+
+        clock.advance(Duration::from_millis(500));
+        logic.draw(&mut display).unwrap();
+        assert!(
+            display.pixels().iter().all(|c| *c == Rgb888::BLUE),
+            "{kind:?} should end on the new screen"
+        );
+    }
+}
+
+#[test]
+fn screens_are_only_shown_while_scheduled() {
+    let (mut logic, _send, clock) = setup();
+    let (a, _) = CountingScreen::new("a", Rgb888::RED);
+    let (b, _) = CountingScreen::new("b", Rgb888::BLUE);
+    logic.add(Box::new(a));
+    logic.add(Box::new(Scheduled::new(
+        Box::new(b),
+        "daily 20:00-21:00".parse().unwrap(),
+    )));
+
+    // It's 19:30, so only the first is shown
+    let mut display = Framebuffer::new(PANEL_SIZE);
+    for _ in 0..3 {
+        logic.draw(&mut display).unwrap();
+        assert_eq!(logic.state().screens[0].id, "a");
+        assert!(display.pixels().iter().all(|c| *c == Rgb888::RED));
+        clock.advance(Duration::from_secs(5));
+    }
+
+    clock.advance(Duration::from_secs(30 * 60));
+    logic.draw(&mut display).unwrap();
+    assert_eq!(logic.state().screens[0].id, "b");
+    assert!(display.pixels().iter().all(|c| *c == Rgb888::BLUE));
+}
+
+#[test]
+fn scheduled_screens_are_drawn_when_they_start() {
+    let (mut logic, _send, clock) = setup();
+    let (a, draws) = CountingScreen::new("a", Rgb888::RED);
+    logic.add(Box::new(Scheduled::new(
+        Box::new(a),
+        "daily 20:00-21:00".parse().unwrap(),
+    )));
+
+    let mut display = Framebuffer::new(PANEL_SIZE);
+    logic.draw(&mut display).unwrap();
+    assert_eq!(draws.load(Ordering::Relaxed), 0);
+    assert!(display.pixels().iter().all(|c| *c == Rgb888::BLACK));
+
+    // Nothing else was being shown, so it's drawn straight away
+    clock.advance(Duration::from_secs(30 * 60));
+    logic.draw(&mut display).unwrap();
+    assert_eq!(draws.load(Ordering::Relaxed), 1);
+    assert!(display.pixels().iter().all(|c| *c == Rgb888::RED));
+
+    clock.advance(Duration::from_secs(60 * 60));
+    logic.draw(&mut display).unwrap();
+    assert!(display.pixels().iter().all(|c| *c == Rgb888::BLACK));
+}
+
+#[test]
+fn commands_are_handled_in_order() {
+    let (mut logic, send, _clock) = setup();
+    let (a, _) = CountingScreen::new("a", Rgb888::RED);
+    let (b, _) = CountingScreen::new("b", Rgb888::BLUE);
+    send.send(Command::Add(Box::new(a))).unwrap();
+    send.send(Command::Delete("a".to_string())).unwrap();
+    send.send(Command::Add(Box::new(b))).unwrap();
+
+    let mut display = Framebuffer::new(PANEL_SIZE);
+    logic.draw(&mut display).unwrap();
+
+    let ids = logic
+        .state()
+        .screens
+        .into_iter()
+        .map(|s| s.id)
+        .collect::<Vec<_>>();
+    assert_eq!(ids, ["b"]);
+}
+
+#[test]
+fn upserts_replace_screens_in_place() {
+    let (mut logic, send, clock) = setup();
+    let (a, _) = CountingScreen::new("a", Rgb888::RED);
+    let (b, _) = CountingScreen::new("b", Rgb888::BLUE);
+    logic.add(Box::new(a));
+    logic.add(Box::new(b));
+
+    let mut display = Framebuffer::new(PANEL_SIZE);
+    logic.draw(&mut display).unwrap();
+    clock.advance(Duration::from_secs(1));
+    let (new_a, new_a_draws) = CountingScreen::new("a", Rgb888::GREEN);
+    let (new_b, _) = CountingScreen::new("b", Rgb888::YELLOW);
+    send.send(Command::Upsert(Box::new(new_b))).unwrap();
+    send.send(Command::Upsert(Box::new(new_a))).unwrap();
+    logic.draw(&mut display).unwrap();
+
+    // Same places, with the same amount of time left, but showing the new screen
+    assert_eq!(ids(&logic), ["a", "b"]);
+    assert_eq!(
+        logic.state().screens[0].time_left,
+        Some(Duration::from_secs(4))
+    );
+    assert_eq!(new_a_draws.load(Ordering::Relaxed), 1);
+    assert!(display.pixels().iter().all(|c| *c == Rgb888::GREEN));
+
+    clock.advance(Duration::from_secs(4));
+    logic.draw(&mut display).unwrap();
+    assert!(display.pixels().iter().all(|c| *c == Rgb888::YELLOW));
+
+    // Anything that isn't there yet is added like any other screen
+    let (c, _) = CountingScreen::new("c", Rgb888::WHITE);
+    send.send(Command::Upsert(Box::new(c))).unwrap();
+    logic.draw(&mut display).unwrap();
+    assert_eq!(ids(&logic), ["c", "b", "a"]);
+}
+
+#[test]
+fn subscribers_are_sent_changes() {
+    let (mut logic, send, clock) = setup();
+    let (a, _) = CountingScreen::new("a", Rgb888::RED);
+    let (b, _) = CountingScreen::new("b", Rgb888::BLUE);
+    let (subscribe, states) = mpsc::channel();
+    send.send(Command::Subscribe(subscribe)).unwrap();
+    send.send(Command::Add(Box::new(a))).unwrap();
+
+    let mut display = Framebuffer::new(PANEL_SIZE);
+    logic.draw(&mut display).unwrap();
+    // Once when subscribing, and again once the screen was added
+    assert!(states.try_recv().unwrap().screens.is_empty());
+    assert_eq!(states.try_recv().unwrap().screens[0].id, "a");
+    assert!(states.try_recv().is_err());
+
+    // Only the time left has changed
+    clock.advance(Duration::from_secs(1));
+    logic.draw(&mut display).unwrap();
+    assert!(states.try_recv().is_err());
+
+    send.send(Command::Add(Box::new(b))).unwrap();
+    clock.advance(Duration::from_secs(1));
+    logic.draw(&mut display).unwrap();
+    let state = states.try_recv().unwrap();
+    assert_eq!(state.screens.len(), 2);
+    assert!(states.try_recv().is_err());
+
+    // And again when the rotation moves on by itself
+    clock.advance(Duration::from_secs(5));
+    logic.draw(&mut display).unwrap();
+    assert_eq!(states.try_recv().unwrap().screens[0].id, "a");
+    assert!(states.try_recv().is_err());
+}
+
+#[test]
+fn state_is_serialised_as_json() {
+    let (mut logic, send, _clock) = setup();
+    let (a, _) = CountingScreen::new("a", Rgb888::RED);
+    send.send(Command::Add(Box::new(a))).unwrap();
+    let mut display = Framebuffer::new(PANEL_SIZE);
+    logic.draw(&mut display).unwrap();
+
+    let json = serde_json::to_value(logic.state()).unwrap();
+    assert_eq!(json["playlist"], "default");
+    assert_eq!(json["screens"][0]["id"], "a");
+    assert_eq!(json["screens"][0]["priority"], "normal");
+    assert_eq!(json["screens"][0]["display_duration_ms"], 5000);
+    assert!(json["screens"][0].get("time_left").is_none());
+}
+
+#[test]
+fn layouts_round_trip() {
+    let layout: Layout = "main 0,0 152x24; clock 152,0 40x24; ticker 0,24 192x8"
+        .parse()
+        .unwrap();
+    assert_eq!(layout.zones().len(), 3);
+    assert_eq!(layout.to_string().parse::<Layout>().unwrap(), layout);
+
+    assert!("".parse::<Layout>().is_err());
+    assert!("main 0,0 10x10; main 10,0 10x10".parse::<Layout>().is_err());
+    assert!("main 0,0".parse::<Layout>().is_err());
+    // Zones have to be somewhere on the panel
+    assert!("main 0,0 0x32".parse::<Layout>().is_err());
+    assert!("main 0,0 70000x70000".parse::<Layout>().is_err());
+    assert!("main 100,0 100x32".parse::<Layout>().is_err());
+    assert!("main -1,0 10x10".parse::<Layout>().is_err());
+    assert!("main 182,22 10x10".parse::<Layout>().is_ok());
+}
+
+#[test]
+fn text_effects_round_trip() {
+    for effect in [
+        "rainbow",
+        "gradient #ff0000 #0000ff",
+        "blink",
+        "wave",
+        "typewriter",
+    ] {
+        let parsed = effect.parse::<TextEffect>().unwrap();
+        assert_eq!(parsed.to_string(), effect);
+    }
+    assert_eq!(
+        "gradient red  blue".parse::<TextEffect>().unwrap(),
+        TextEffect::Gradient(Rgb888::RED, Rgb888::BLUE)
+    );
+    assert!("gradient red".parse::<TextEffect>().is_err());
+    assert!("gradient red sparkly".parse::<TextEffect>().is_err());
+    assert!("sparkle".parse::<TextEffect>().is_err());
+}
+
+#[test]
+fn zones_are_composited() {
+    let (mut logic, send, _clock) = setup();
+    logic.set_layout("main 0,0 152x32; clock 152,0 40x32".parse().unwrap());
+    let (a, _) = CountingScreen::new("a", Rgb888::RED);
+    let (b, _) = CountingScreen::new("b", Rgb888::BLUE);
+    logic.add(Box::new(a));
+    send.send(Command::ToZone(
+        "clock".to_string(),
+        Box::new(Command::Add(Box::new(b))),
+    ))
+    .unwrap();
+
+    let mut display = Framebuffer::new(PANEL_SIZE);
+    logic.draw(&mut display).unwrap();
+
+    assert_eq!(display.pixel(Point::new(0, 0)), Some(Rgb888::RED));
+    assert_eq!(display.pixel(Point::new(151, 31)), Some(Rgb888::RED));
+    assert_eq!(display.pixel(Point::new(152, 0)), Some(Rgb888::BLUE));
+    assert_eq!(display.pixel(Point::new(191, 31)), Some(Rgb888::BLUE));
+
+    let zones = logic
+        .state()
+        .screens
+        .into_iter()
+        .map(|s| (s.id, s.zone))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        zones,
+        [
+            ("a".to_string(), "main".to_string()),
+            ("b".to_string(), "clock".to_string())
+        ]
+    );
+
+    // Removing a zone moves its screens to the main zone
+    logic.set_layout(Layout::default());
+    assert_eq!(logic.state().screens.len(), 2);
+    assert!(logic.state().screens.iter().all(|s| s.zone == "main"));
+}
+
+#[test]
+fn messages_update_screens_in_place() {
+    let (mut logic, send, clock) = setup();
+    logic.add(Box::new(
+        TextScreen::with_text("before".to_string(), None).with_id("greeting"),
+    ));
+    logic.add(Box::new(TextScreen::with_text("other".to_string(), None)));
+
+    let mut display = Framebuffer::new(PANEL_SIZE);
+    logic.draw(&mut display).unwrap();
+    clock.advance(Duration::from_secs(1));
+    send.send(Command::Message(
+        "greeting".to_string(),
+        ScreenMessage::Text("after".to_string()),
+    ))
+    .unwrap();
+    logic.draw(&mut display).unwrap();
+
+    // Still the same screen, with the same amount of time left
+    let state = logic.state();
+    assert_eq!(state.screens[0].id, "greeting");
+    assert_eq!(state.screens[0].time_left, Some(Duration::from_secs(4)));
+
+    let mut expected = Framebuffer::new(PANEL_SIZE);
+    let Ok(()) = TextScreen::with_text("after".to_string(), None).draw(&mut expected, &clock);
+    assert_eq!(display, expected);
+}
+
+#[test]
+fn unheard_messages_can_add_a_screen() {
+    let (mut logic, send, clock) = setup();
+    for value in [20.0, 21.5] {
+        send.send(Command::MessageOrAdd(
+            "environment".to_string(),
+            ScreenMessage::Reading {
+                key: "temperature".to_string(),
+                value: Some(value),
+            },
+            Box::new(EnvironmentScreen::new(Some(value), None)),
+        ))
+        .unwrap();
+    }
+
+    // Added by the first, then updated by the second
+    let mut display = Framebuffer::new(PANEL_SIZE);
+    logic.draw(&mut display).unwrap();
+    clock.advance(Duration::from_secs(1));
+    logic.draw(&mut display).unwrap();
+    assert_eq!(ids(&logic), ["environment"]);
+
+    let mut expected = Framebuffer::new(PANEL_SIZE);
+    let Ok(()) = EnvironmentScreen::new(Some(21.5), None).draw(&mut expected, &clock);
+    assert_eq!(display, expected);
+}
+
+#[test]
+fn toasts_dont_interrupt_rotation() {
+    let (mut logic, send, clock) = setup();
+    let (a, draws) = CountingScreen::new("a", Rgb888::RED);
+    let (b, _) = CountingScreen::new("b", Rgb888::BLUE);
+    logic.add(Box::new(a));
+    logic.add(Box::new(b));
+    send.send(Command::Notify(
+        Toast::new("hello").with_duration(Duration::from_secs(1)),
+    ))
+    .unwrap();
+
+    let mut display = Framebuffer::new(PANEL_SIZE);
+    logic.draw(&mut display).unwrap();
+    clock.advance(Duration::from_millis(500));
+    logic.draw(&mut display).unwrap();
+    assert_ne!(display.pixel(Point::new(0, 31)), Some(Rgb888::RED));
+    assert_eq!(display.pixel(Point::new(0, 0)), Some(Rgb888::RED));
+    assert_eq!(
+        logic.state().screens[0].time_left,
+        Some(Duration::from_millis(4500))
+    );
+
+    // Gone once it's slid back out, with the screen underneath told it's uncovered
+    clock.advance(Duration::from_secs(1));
+    logic.draw(&mut display).unwrap();
+    assert!(display.pixels().iter().all(|c| *c == Rgb888::RED));
+    // Once with the toast over it from the start, and once after it's gone
+    assert_eq!(draws.load(Ordering::Relaxed), 2);
+    assert_eq!(logic.state().screens[0].id, "a");
+}
+
+#[test]
+fn playlists_keep_their_screens() {
+    let (mut logic, send, _clock) = setup();
+    let (a, _) = CountingScreen::new("a", Rgb888::RED);
+    logic.add(Box::new(a));
+    send.send(Command::DefinePlaylist(Playlist::new(
+        "event",
+        vec![text_spec("welcome"), text_spec("schedule")],
+    )))
+    .unwrap();
+    send.send(Command::SwitchPlaylist("event".to_string()))
+        .unwrap();
+    // Joins whichever playlist is active
+    let (b, _) = CountingScreen::new("b", Rgb888::BLUE);
+    send.send(Command::Add(Box::new(b))).unwrap();
+
+    let mut display = Framebuffer::new(PANEL_SIZE);
+    logic.draw(&mut display).unwrap();
+    assert_eq!(logic.state().playlist, "event");
+    assert_eq!(ids(&logic), ["b", "welcome", "schedule"]);
+
+    send.send(Command::SwitchPlaylist("default".to_string()))
+        .unwrap();
+    logic.draw(&mut display).unwrap();
+    assert_eq!(ids(&logic), ["a"]);
+
+    // Unknown playlists are ignored
+    send.send(Command::SwitchPlaylist("nope".to_string()))
+        .unwrap();
+    send.send(Command::SwitchPlaylist("event".to_string()))
+        .unwrap();
+    logic.draw(&mut display).unwrap();
+    assert_eq!(ids(&logic), ["b", "welcome", "schedule"]);
+
+    // Deleting the active playlist goes back to the default one, and it can't be switched back to
+    send.send(Command::DeletePlaylist("event".to_string()))
+        .unwrap();
+    send.send(Command::SwitchPlaylist("event".to_string()))
+        .unwrap();
+    logic.draw(&mut display).unwrap();
+    assert_eq!(logic.state().playlist, "default");
+    assert_eq!(ids(&logic), ["a"]);
+}
+
+#[test]
+fn playlists_are_saved() {
+    let path = std::env::temp_dir().join(format!("playlists-{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let (mut logic, send, _clock) = setup();
+    logic.load_playlists(&path).unwrap();
+    send.send(Command::DefinePlaylist(Playlist::new(
+        "open-night",
+        vec![
+            text_spec("open"),
+            ScreenSpec::Environment {
+                temperature: None,
+                co2: None,
+            },
+        ],
+    )))
+    .unwrap();
+    send.send(Command::SwitchPlaylist("open-night".to_string()))
+        .unwrap();
+    logic.draw(&mut Framebuffer::new(PANEL_SIZE)).unwrap();
+
+    let (mut logic, _send, _clock) = setup();
+    logic.load_playlists(&path).unwrap();
+    assert_eq!(logic.state().playlist, "open-night");
+    assert_eq!(ids(&logic), ["open", "environment"]);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn playlists_have_their_own_transitions() {
+    let (mut logic, send, clock) = setup();
+    logic.set_default_transition(Transition::new(
+        TransitionKind::Crossfade,
+        Duration::from_secs(1),
+    ));
+    let (a, _) = CountingScreen::new("a", Rgb888::RED);
+    logic.add(Box::new(a));
+    let wipe = Transition::new(TransitionKind::Wipe, Duration::from_secs(1));
+    send.send(Command::DefinePlaylist(
+        Playlist::new("event", vec![text_spec("welcome")]).with_transition(wipe),
+    ))
+    .unwrap();
+    let mut display = Framebuffer::new(PANEL_SIZE);
+    logic.draw(&mut display).unwrap();
+    clock.advance(Duration::from_secs(1));
+    logic.draw(&mut display).unwrap();
+
+    send.send(Command::SwitchPlaylist("event".to_string()))
+        .unwrap();
+    logic.draw(&mut display).unwrap();
+    clock.advance(Duration::from_millis(500));
+    logic.draw(&mut display).unwrap();
+    assert_eq!(display.pixel(Point::new(0, 0)), Some(Rgb888::BLACK));
+    assert_eq!(display.pixel(Point::new(191, 0)), Some(Rgb888::RED));
+
+    // The rest of the display keeps the default
+    send.send(Command::SwitchPlaylist("default".to_string()))
+        .unwrap();
+    logic.draw(&mut display).unwrap();
+    clock.advance(Duration::from_millis(500));
+    logic.draw(&mut display).unwrap();
+    assert_eq!(
+        display.pixel(Point::new(0, 0)),
+        Some(Rgb888::new(128, 0, 0))
+    );
+}
+
+#[test]
+fn sleeping_saves_things_for_later() {
+    let sleep = Arc::new(AtomicBool::new(false));
+    let (mut logic, send, clock) = setup_sleeping(sleep.clone());
+    let (a, _) = CountingScreen::new("a", Rgb888::RED);
+    logic.add(Box::new(a));
+    let mut display = Framebuffer::new(PANEL_SIZE);
+    logic.draw(&mut display).unwrap();
+
+    // The clock is shown dimmed instead, and the rotation doesn't move on
+    sleep.store(true, Ordering::Relaxed);
+    let (b, b_draws) = CountingScreen::new("b", Rgb888::BLUE);
+    send.send(Command::Add(Box::new(b))).unwrap();
+    send.send(Command::Notify(Toast::new("hello"))).unwrap();
+    clock.advance(Duration::from_secs(60));
+    logic.draw(&mut display).unwrap();
+    assert!(display.pixels().iter().all(|c| c.r() <= 32));
+    assert!(display.pixels().iter().any(|c| *c != Rgb888::BLACK));
+    assert_eq!(b_draws.load(Ordering::Relaxed), 0);
+    assert!(logic.state().sleeping);
+    assert_eq!(ids(&logic), ["b", "a"]);
+
+    send.send(Command::SetSleepMode(SleepMode::Blank)).unwrap();
+    logic.draw(&mut display).unwrap();
+    assert!(display.pixels().iter().all(|c| *c == Rgb888::BLACK));
+
+    // What was sent while sleeping is shown when we wake up, with the toast still to come
+    sleep.store(false, Ordering::Relaxed);
+    logic.draw(&mut display).unwrap();
+    assert_eq!(display.pixel(Point::new(0, 0)), Some(Rgb888::BLUE));
+    assert_eq!(b_draws.load(Ordering::Relaxed), 1);
+    assert_eq!(
+        logic.state().screens[0].time_left,
+        Some(Duration::from_secs(5))
+    );
+    clock.advance(Duration::from_millis(500));
+    logic.draw(&mut display).unwrap();
+    assert_ne!(display.pixel(Point::new(0, 31)), Some(Rgb888::BLUE));
+}
+
+#[test]
+fn rotations_are_restored() {
+    let path = std::env::temp_dir().join(format!("rotation-{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let (mut logic, send, clock) = setup();
+    assert!(!logic.load_rotations(&path).unwrap());
+    logic.add(Box::new(
+        TextScreen::with_text("soon".to_string(), None)
+            .with_id("soon")
+            .with_expiry(clock.local_now() + Duration::from_secs(3600)),
+    ));
+    logic.add(Box::new(
+        TextScreen::with_text("later".to_string(), Some(2)).with_id("later"),
+    ));
+    logic.add(Box::new(EnvironmentScreen::new(None, None)));
+    // Can't be described, so isn't kept
+    let (a, _) = CountingScreen::new("a", Rgb888::RED);
+    logic.add(Box::new(a));
+    send.send(Command::Message(
+        "environment".to_string(),
+        ScreenMessage::Reading {
+            key: "temperature".to_string(),
+            value: Some(21.5),
+        },
+    ))
+    .unwrap();
+    logic.draw(&mut Framebuffer::new(PANEL_SIZE)).unwrap();
+    let before = logic.state().screens;
+    assert!(std::fs::read_to_string(&path)
+        .unwrap()
+        .contains("\"temperature\": 21.5"));
+
+    // Anything that's expired by the time we start again is dropped
+    clock.advance(Duration::from_secs(7200));
+    let (mut logic, _send, _clock) = setup();
+    logic.set_clock(Box::new(clock.clone()));
+    assert!(logic.load_rotations(&path).unwrap());
+    let after = logic.state().screens;
+    let kept = before
+        .iter()
+        .filter(|s| s.id != "soon" && s.id != "a")
+        .map(|s| (&s.id, s.remaining_shows))
+        .collect::<Vec<_>>();
+    assert_eq!(kept.len(), 2);
+    assert_eq!(
+        after
+            .iter()
+            .map(|s| (&s.id, s.remaining_shows))
+            .collect::<Vec<_>>(),
+        kept
+    );
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn screens_that_panic_are_quarantined() {
+    let (mut logic, send, _clock) = setup();
+    let (a, _) = CountingScreen::new("a", Rgb888::RED);
+    logic.add(Box::new(a));
+    send.send(Command::Add(Box::new(PanickingScreen))).unwrap();
+
+    let mut display = Framebuffer::new(PANEL_SIZE);
+    logic.draw(&mut display).unwrap();
+    let state = logic.state();
+    assert_eq!(ids(&logic), ["a"]);
+    assert_eq!(state.draw_failures, 1);
+    assert_eq!(state.quarantined[0].id, "panicking");
+    assert_eq!(state.quarantined[0].zone, "main");
+    assert_eq!(state.quarantined[0].reason, "oops");
+
+    // The rest of the rotation carries on
+    logic.draw(&mut display).unwrap();
+    assert!(display.pixels().iter().all(|c| *c == Rgb888::RED));
+}
+
+fn colour_at(display: &Framebuffer, x: i32, y: i32) -> Rgb888 {
+    display.pixel(Point::new(x, y)).unwrap()
+}
+
+fn ids(logic: &DisplayLogic) -> Vec<String> {
+    logic.state().screens.into_iter().map(|s| s.id).collect()
+}
+
+fn text_spec(id: &str) -> ScreenSpec {
+    ScreenSpec::Text {
+        text: id.to_string(),
+        colour: [255, 255, 255],
+        show_count: None,
+        id: Some(id.to_string()),
+        expires: None,
+        multi_line: false,
+        fit: false,
+        font: None,
+        effect: None,
+        transition: None,
+    }
+}
@@ -0,0 +1,256 @@
+//! Tests for finding fonts by name.
+
+use std::{fs, path::Path};
+
+use embedded_graphics::{
+    pixelcolor::Rgb888,
+    prelude::*,
+    text::{renderer::TextRenderer, Baseline},
+};
+use logic::{
+    config::Config,
+    fonts::{self, BitmapFont, BitmapTextStyle, Charset, DEFAULT_FONT},
+    framebuffer::Framebuffer,
+    screens::{Screen, ScreenSpec, TextScreen},
+};
+
+/// A tiny proportional font, with `H`, `W`, `i`, `j`, `é`, `?` and space.
+const TINY_BDF: &str = include_str!("bdf/tiny.bdf");
+
+#[test]
+fn every_font_is_listed() {
+    let names = fonts::names();
+    for name in ["4x6", "6x13-bold", "10x20", "9x14"] {
+        assert!(names.contains(&name), "{name} is missing");
+    }
+    assert!(fonts::exists(DEFAULT_FONT));
+    assert!(!fonts::exists("comic-sans"));
+
+    // Each font is only in each charset once
+    let all = fonts::all();
+    for (i, a) in all.iter().enumerate() {
+        assert!(!all[i + 1..]
+            .iter()
+            .any(|b| a.name == b.name && a.charset == b.charset));
+    }
+    let ibm = all.iter().find(|f| f.name == "9x14").unwrap();
+    assert_eq!(ibm.charset, Charset::Ibm437);
+    assert_eq!((ibm.size().width, ibm.size().height), (9, 14));
+
+    let big = fonts::font_for(fonts::BIG_FONT, "OPEN");
+    assert_eq!(big.name, fonts::BIG_FONT);
+    assert_eq!((big.size().width, big.size().height), (16, 32));
+    assert!(!big.supports("€"));
+}
+
+#[test]
+fn fonts_are_picked_to_fit_the_text() {
+    let font = fonts::font_for("6x13", "Hello");
+    assert_eq!(font.name, "6x13");
+    assert!(font.supports("Hello\nthere?"));
+
+    let font = fonts::font_for("6x13", "Καλημέρα");
+    assert_eq!((font.name, font.charset), ("6x13", Charset::Iso8859_7));
+
+    let font = fonts::font_for("7x13", "Școală");
+    assert_eq!(font.charset, Charset::Iso8859_16);
+}
+
+#[test]
+fn missing_fonts_and_characters_fall_back() {
+    assert_eq!(fonts::font_for("comic-sans", "Hello").name, DEFAULT_FONT);
+
+    // The IBM font doesn't have a euro sign
+    let font = fonts::font_for("9x14", "Tickets €5");
+    assert_eq!(
+        (font.name, font.charset),
+        (DEFAULT_FONT, Charset::Iso8859_15)
+    );
+
+    // Nothing has this, so it's drawn as a question mark in the font that was asked for
+    let font = fonts::font_for("6x13", "🦀");
+    assert_eq!(font.name, "6x13");
+    assert!(!font.supports("🦀"));
+}
+
+#[test]
+fn missing_characters_fall_back_one_at_a_time() {
+    let fallback = |name: &str, c| {
+        let font = fonts::font_for(name, "").font;
+        fonts::fallback_for(font, c).map(|f| (f.name, f.charset))
+    };
+
+    // Another version of the same font first
+    assert_eq!(fallback("6x13", 'Ψ'), Some(("6x13", Charset::Iso8859_7)));
+    // Then the default font
+    assert_eq!(
+        fallback("9x14", 'Ж'),
+        Some((DEFAULT_FONT, Charset::Iso8859_5))
+    );
+    // Then whatever's closest in size
+    assert_eq!(fallback("6x13", '░'), Some(("9x14", Charset::Ibm437)));
+    assert_eq!(fallback("6x13", '🦀'), None);
+}
+
+#[test]
+fn text_keeps_its_font() {
+    let screen = TextScreen::with_text("Hello".to_string(), None).with_font("6x13-bold");
+    let Some(ScreenSpec::Text { font, .. }) = Screen::<Framebuffer>::spec(&screen) else {
+        panic!("text should have a spec");
+    };
+    assert_eq!(font.as_deref(), Some("6x13-bold"));
+
+    let screen = TextScreen::with_text("Hello".to_string(), None);
+    let Some(ScreenSpec::Text { font, .. }) = Screen::<Framebuffer>::spec(&screen) else {
+        panic!("text should have a spec");
+    };
+    assert_eq!(font, None);
+}
+
+#[test]
+fn bdf_fonts_are_loaded() {
+    let font = BitmapFont::load(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/bdf/tiny.bdf"))
+        .unwrap();
+    assert_eq!(font.name(), "tiny");
+    assert!(font.supports("Hi Wjé\n"));
+    assert!(!font.supports("x"));
+    assert_eq!((font.size().width, font.size().height), (8, 7));
+
+    // Each character has its own width, and missing ones are drawn as the default character
+    assert_eq!(font.width("Hi"), 6 + 2);
+    assert_eq!(font.width("W x"), 8 + 3 + 5);
+}
+
+#[test]
+fn pcf_fonts_are_loaded() {
+    // The same font as tiny.bdf, with its bitmaps in the opposite bit and byte order
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/bdf");
+    let pcf = BitmapFont::load(&dir.join("tiny.pcf")).unwrap();
+    let bdf = BitmapFont::load(&dir.join("tiny.bdf")).unwrap();
+    assert_eq!(pcf.name(), "tiny");
+    assert_eq!(pcf.size(), bdf.size());
+    assert!(pcf.supports("Hi Wjé\n"));
+    assert_eq!(pcf.width("W x"), bdf.width("W x"));
+
+    let draw = |font: BitmapFont| {
+        let mut frame = Framebuffer::new(Size::new(40, 8));
+        let style = BitmapTextStyle::new(Box::leak(Box::new(font)), Rgb888::WHITE);
+        let Ok(_) = style.draw_string("HiWjé?x", Point::zero(), Baseline::Top, &mut frame);
+        frame
+    };
+    assert_eq!(draw(pcf).pixels(), draw(bdf).pixels());
+
+    let bytes = fs::read(dir.join("tiny.pcf")).unwrap();
+    assert!(BitmapFont::parse_pcf("broken", &bytes[..bytes.len() / 2]).is_err());
+}
+
+#[test]
+fn fonts_can_be_kerned() {
+    let font = BitmapFont::parse("tiny-kerned", TINY_BDF)
+        .unwrap()
+        .with_kerning("# Tighter\nWi -2\n\niW 1")
+        .unwrap();
+    assert_eq!(font.width("Wi"), 8 + 2 - 2);
+    assert_eq!(font.width("iW"), 2 + 8 + 1);
+    assert_eq!(font.width("WiW"), 8 + 2 - 2 + 8 + 1);
+    assert_eq!(font.width("HW"), 6 + 8);
+
+    // Drawing moves on by the same amount as measuring
+    let style = BitmapTextStyle::new(Box::leak(Box::new(font)), Rgb888::WHITE);
+    let mut frame = Framebuffer::new(Size::new(40, 8));
+    let end = style
+        .draw_string("WiW", Point::zero(), Baseline::Top, &mut frame)
+        .unwrap();
+    assert_eq!(end, Point::new(17, 0));
+
+    let kerning = |kerning| {
+        BitmapFont::parse("tiny", TINY_BDF)
+            .unwrap()
+            .with_kerning(kerning)
+    };
+    assert!(kerning("W -1").is_err());
+    assert!(kerning("Wi").is_err());
+    assert!(kerning("Wi closer").is_err());
+
+    // They're loaded from next to the font
+    let dir = std::env::temp_dir().join(format!("kerned-font-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("tiny.bdf"), TINY_BDF).unwrap();
+    fs::write(dir.join("tiny.kern"), "Wi -2").unwrap();
+    let font = BitmapFont::load(&dir.join("tiny.bdf")).unwrap();
+    assert_eq!(font.width("Wi"), 8);
+}
+
+#[test]
+fn loaded_fonts_can_be_used_by_name() {
+    let info = fonts::add(BitmapFont::parse("tiny-by-name", TINY_BDF).unwrap());
+    assert!(fonts::exists("tiny-by-name"));
+    assert!(fonts::names().contains(&"tiny-by-name"));
+
+    let font = fonts::font_for("tiny-by-name", "HiW");
+    assert_eq!((font.name, font.charset), ("tiny-by-name", Charset::Loaded));
+    assert_eq!(fonts::name_of(info.font), Some("tiny-by-name"));
+    // Text it can't draw falls back, like any other font
+    assert_eq!(fonts::font_for("tiny-by-name", "Hello").name, DEFAULT_FONT);
+
+    // Text is measured with each character's own width
+    let metrics =
+        font.font
+            .style(Rgb888::WHITE)
+            .measure_string("HiW", Point::zero(), Baseline::Top);
+    assert_eq!(metrics.next_position, Point::new(16, 0));
+    assert_eq!(metrics.bounding_box.size, Size::new(16, 7));
+
+    let screen = TextScreen::with_text("HiW".to_string(), None).with_font("tiny-by-name");
+    let Some(ScreenSpec::Text { font, .. }) = Screen::<Framebuffer>::spec(&screen) else {
+        panic!("text should have a spec");
+    };
+    assert_eq!(font.as_deref(), Some("tiny-by-name"));
+}
+
+#[test]
+fn broken_bdf_fonts_are_rejected() {
+    for (what, bdf) in [
+        ("not bdf", "hello".to_string()),
+        (
+            "no characters",
+            TINY_BDF[..TINY_BDF.find("STARTCHAR").unwrap()].to_string(),
+        ),
+        ("bad bitmap", TINY_BDF.replace("F8", "X8")),
+        ("missing rows", TINY_BDF.replace("82\n82\n", "")),
+        (
+            "bad bounding box",
+            TINY_BDF.replace("BBX 5 6 0 0", "BBX 5 6"),
+        ),
+        (
+            "other charset",
+            TINY_BDF
+                .replace("\"ISO10646\"", "\"ISO8859\"")
+                .replace("\"1\"", "\"2\""),
+        ),
+    ] {
+        assert!(
+            BitmapFont::parse("broken", &bdf).is_err(),
+            "{what} was loaded"
+        );
+    }
+
+    let dir = std::env::temp_dir().join(format!("font-dir-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("fonts")).unwrap();
+    fs::write(dir.join("fonts/tiny.bdf"), TINY_BDF).unwrap();
+    let pcf = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/bdf/tiny.pcf");
+    fs::copy(pcf, dir.join("fonts/tiny-pcf.pcf")).unwrap();
+    // The font directory is relative to the config file, not wherever we're run from
+    let config_file = dir.join("display.toml");
+    fs::write(&config_file, "font_dir = \"fonts\"").unwrap();
+    let config = Config::load(&config_file).unwrap().unwrap();
+    assert_eq!(config.font_dir, Some(dir.join("fonts")));
+    assert!(fonts::exists("tiny"));
+    assert!(fonts::exists("tiny-pcf"));
+
+    fs::write(dir.join("fonts/broken.bdf"), "hello").unwrap();
+    let error = Config::load(&config_file).unwrap_err();
+    assert!(error.to_string().contains("broken.bdf"), "{error}");
+}
@@ -0,0 +1,538 @@
+//! Renders each screen headlessly and compares it against the PNGs in `tests/golden/`.
+//!
+//! If a screen's appearance changes on purpose, regenerate the images with
+//! `UPDATE_GOLDEN=1 cargo test -p logic --test golden`, and check the new images before committing them.
+//! When a comparison fails, the actual output and a diff image are written to `target/golden-failures/`.
+
+use std::{
+    env,
+    fmt::Write,
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::{atomic::AtomicBool, mpsc, Arc},
+    time::Duration,
+};
+
+use chrono::NaiveDate;
+use embedded_graphics::{
+    mono_font::{ascii::FONT_6X10, MonoTextStyle},
+    pixelcolor::Rgb888,
+    prelude::*,
+    primitives::Rectangle,
+};
+use logic::{
+    clock::FakeClock,
+    framebuffer::Framebuffer,
+    overlay::Toast,
+    screens::{
+        AmbientScreen, ClockScreen, EnvironmentScreen, HateScreen, Screen, ScreenMessage,
+        ScriptScreen, TestScreen, TextEffect, TextScreen,
+    },
+    Command, DisplayLogic, PANEL_SIZE,
+};
+
+/// Draw the screen once at each of the given times since the start, and return the last frame.
+fn render(screen: impl Screen<Framebuffer>, at: &[Duration]) -> Framebuffer {
+    render_sized(screen, PANEL_SIZE, at)
+}
+
+/// Like [`render`], but with a display of the given size, ie for screens meant for a smaller zone.
+fn render_sized(mut screen: impl Screen<Framebuffer>, size: Size, at: &[Duration]) -> Framebuffer {
+    let clock = start_clock();
+    let mut frame = Framebuffer::new(size);
+    for t in at {
+        clock.advance(*t - clock.elapsed());
+        let Ok(()) = screen.draw(&mut frame, &clock);
+    }
+
+    frame
+}
+
+/// A clock stopped at the time all golden images are rendered at.
+fn start_clock() -> FakeClock {
+    FakeClock::new(
+        NaiveDate::from_ymd_opt(2025, 6, 3)
+            .unwrap()
+            .and_hms_opt(19, 30, 0)
+            .unwrap(),
+    )
+}
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+fn failures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../../target/golden-failures")
+}
+
+/// Compare the frame against the golden image with the given name, or overwrite it if `UPDATE_GOLDEN` is set.
+fn assert_golden(name: &str, frame: &Framebuffer) {
+    let path = golden_dir().join(format!("{name}.png"));
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(golden_dir()).unwrap();
+        write_png(&path, frame);
+        return;
+    }
+
+    let Some(expected) = read_png(&path) else {
+        panic!(
+            "no golden image at {}, run with UPDATE_GOLDEN=1 to create it",
+            path.display()
+        );
+    };
+    if &expected == frame {
+        return;
+    }
+
+    // Work out where the differences are
+    let differing = frame
+        .bounding_box()
+        .points()
+        .filter(|p| expected.pixel(*p) != frame.pixel(*p))
+        .collect::<Vec<_>>();
+    let mut diff = Framebuffer::new(frame.size());
+    for p in frame.bounding_box().points() {
+        let colour = if differing.contains(&p) {
+            Rgb888::RED
+        } else {
+            // Dimmed version of what was expected, for context
+            let c = expected.pixel(p).unwrap_or_default();
+            Rgb888::new(c.r() / 4, c.g() / 4, c.b() / 4)
+        };
+        diff.set_pixel(p, colour);
+    }
+
+    fs::create_dir_all(failures_dir()).unwrap();
+    let actual_path = failures_dir().join(format!("{name}.actual.png"));
+    let diff_path = failures_dir().join(format!("{name}.diff.png"));
+    write_png(&actual_path, frame);
+    write_png(&diff_path, &diff);
+
+    panic!(
+        "{name} doesn't match its golden image: {} pixels differ, within {:?}\n\
+         expected (top) vs actual (bottom), with differences marked by `!`:\n{}\n{}\n\
+         actual output written to {}\ndiff written to {}",
+        differing.len(),
+        bounds_of(&differing),
+        ascii_art(&expected, &differing),
+        ascii_art(frame, &differing),
+        actual_path.display(),
+        diff_path.display(),
+    );
+}
+
+/// The smallest rectangle containing all the given points.
+fn bounds_of(points: &[Point]) -> Rectangle {
+    let min = points
+        .iter()
+        .fold(points[0], |acc, p| acc.component_min(*p));
+    let max = points
+        .iter()
+        .fold(points[0], |acc, p| acc.component_max(*p));
+    Rectangle::with_corners(min, max)
+}
+
+/// Render the frame as text, with lit pixels as `#`, unlit as `.`, and anything in `differing` as `!`.
+fn ascii_art(frame: &Framebuffer, differing: &[Point]) -> String {
+    let mut out = String::new();
+    for y in 0..frame.size().height as i32 {
+        for x in 0..frame.size().width as i32 {
+            let p = Point::new(x, y);
+            let c = if differing.contains(&p) {
+                '!'
+            } else if frame.pixel(p) == Some(Rgb888::BLACK) {
+                '.'
+            } else {
+                '#'
+            };
+            out.push(c);
+        }
+        writeln!(out).unwrap();
+    }
+
+    out
+}
+
+fn write_png(path: &Path, frame: &Framebuffer) {
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(File::create(path).unwrap()),
+        frame.size().width,
+        frame.size().height,
+    );
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let data = frame
+        .pixels()
+        .iter()
+        .flat_map(|c| [c.r(), c.g(), c.b()])
+        .collect::<Vec<_>>();
+    encoder
+        .write_header()
+        .unwrap()
+        .write_image_data(&data)
+        .unwrap();
+}
+
+fn read_png(path: &Path) -> Option<Framebuffer> {
+    let mut decoder = png::Decoder::new(File::open(path).ok()?);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().unwrap();
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data).unwrap();
+    assert_eq!(info.color_type, png::ColorType::Rgb);
+
+    let mut frame = Framebuffer::new(Size::new(info.width, info.height));
+    for (pixel, rgb) in frame.pixels_mut().iter_mut().zip(data.chunks(3)) {
+        *pixel = Rgb888::new(rgb[0], rgb[1], rgb[2]);
+    }
+
+    Some(frame)
+}
+
+#[test]
+fn test_screen() {
+    assert_golden("test_screen", &render(TestScreen, &[Duration::ZERO]));
+}
+
+#[test]
+fn text_short() {
+    let screen = TextScreen::with_text("Hello, World!".to_string(), None);
+    assert_golden("text_short", &render(screen, &[Duration::ZERO]));
+}
+
+#[test]
+fn text_scrolling() {
+    let screen = TextScreen::with_text(
+        "some much longer text that goes off the screen".to_string(),
+        None,
+    );
+    // Scrolling starts on the first frame drawn, so draw one then move on by a second.
+    let times = [Duration::ZERO, Duration::from_secs(1)];
+    assert_golden("text_scrolling_1s", &render(screen, &times));
+}
+
+#[test]
+fn text_small_font() {
+    let screen = TextScreen::new(
+        "small text".to_string(),
+        MonoTextStyle::new(&FONT_6X10, Rgb888::CYAN),
+        None,
+    );
+    assert_golden("text_small_font", &render(screen, &[Duration::ZERO]));
+}
+
+#[test]
+fn text_named_font() {
+    let screen = TextScreen::with_text("Καλημέρα, 9x15!".to_string(), None).with_font("9x15-bold");
+    assert_golden("text_named_font", &render(screen, &[Duration::ZERO]));
+}
+
+#[test]
+fn text_bdf_font() {
+    let bdf = include_str!("bdf/tiny.bdf");
+    logic::fonts::add(logic::fonts::BitmapFont::parse("tiny", bdf).unwrap());
+    let screen = TextScreen::with_text("{red}Hi{/} Wjé?".to_string(), None).with_font("tiny");
+    assert_golden("text_bdf_font", &render(screen, &[Duration::ZERO]));
+}
+
+#[test]
+fn text_multi_line() {
+    let screen =
+        TextScreen::with_text("Open night tonight, all welcome!".to_string(), None).multi_line();
+    assert_golden("text_multi_line", &render(screen, &[Duration::ZERO]));
+}
+
+#[test]
+fn text_multi_line_newlines() {
+    let screen = TextScreen::with_text(
+        "Fire alarm test\nat 11am\nno need to leave".to_string(),
+        None,
+    )
+    .multi_line();
+    assert_golden(
+        "text_multi_line_newlines",
+        &render(screen, &[Duration::ZERO]),
+    );
+}
+
+#[test]
+fn text_multi_line_paged() {
+    let screen = TextScreen::with_text(
+        "This message is far too long to fit on the display all at once, even in a small font, \
+         so it's shown a page at a time instead of scrolling along. Each page is shown for a few seconds, which should \
+         be long enough to read it."
+            .to_string(),
+        None,
+    )
+    .multi_line();
+    assert_eq!(
+        screen.single_display_duration(&Framebuffer::new(PANEL_SIZE)),
+        Duration::from_secs(9)
+    );
+    // Pages move on every few seconds
+    let times = [Duration::ZERO, Duration::from_secs(4)];
+    assert_golden("text_multi_line_paged_4s", &render(screen, &times));
+}
+
+#[test]
+fn text_markup() {
+    let screen = TextScreen::with_text(
+        "{red}DANGER{/} {#ff8000}{b}laser{/}{/} {co2}".to_string(),
+        None,
+    );
+    assert_golden("text_markup", &render(screen, &[Duration::ZERO]));
+}
+
+#[test]
+fn text_markup_blink() {
+    let text = "{blink}{yellow}Fire alarm{/}{/} {b}{{test}";
+    let shown = TextScreen::with_text(text.to_string(), None);
+    assert_golden("text_markup_blink_on", &render(shown, &[Duration::ZERO]));
+    let hidden = TextScreen::with_text(text.to_string(), None);
+    let times = [Duration::ZERO, Duration::from_millis(700)];
+    assert_golden("text_markup_blink_off", &render(hidden, &times));
+}
+
+#[test]
+fn text_markup_scrolls_by_what_is_shown() {
+    let display = Framebuffer::new(PANEL_SIZE);
+    let plain = TextScreen::with_text(
+        "this is long enough that it has to scroll".to_string(),
+        None,
+    );
+    let marked_up = TextScreen::with_text(
+        "this is {red}long{/} enough that it has to {b}scroll".to_string(),
+        None,
+    );
+    assert_eq!(
+        marked_up.single_display_duration(&display),
+        plain.single_display_duration(&display) + Duration::from_millis(8)
+    );
+}
+
+#[test]
+fn text_fallback() {
+    let screen = TextScreen::with_text("“Hi” Καλη ❤️😀🔥".to_string(), None);
+    assert_golden("text_fallback", &render(screen, &[Duration::ZERO]));
+}
+
+#[test]
+fn text_fallback_multi_line() {
+    let screen = TextScreen::with_text(
+        "Pizza 🍕 and beer 🍺 tonight, Привет ☕\nall welcome 👍".to_string(),
+        None,
+    )
+    .multi_line();
+    assert_golden(
+        "text_fallback_multi_line",
+        &render(screen, &[Duration::ZERO]),
+    );
+}
+
+#[test]
+fn text_fallback_scrolls_by_what_is_shown() {
+    let display = Framebuffer::new(PANEL_SIZE);
+    let plain = TextScreen::with_text(
+        "this is long enough that it has to scroll".to_string(),
+        None,
+    );
+    // A character from another font, and an emoji with a variation selector that isn't drawn
+    let mixed = TextScreen::with_text(
+        "this is long enough that it has to scroll Ψ❤️".to_string(),
+        None,
+    );
+    assert_eq!(
+        mixed.single_display_duration(&display),
+        plain.single_display_duration(&display) + Duration::from_millis(8) * (10 + 10 + 12)
+    );
+}
+
+#[test]
+fn text_markup_multi_line() {
+    let screen = TextScreen::with_text(
+        "{yellow}Open night{/} tonight\nall {green}very{/} welcome {not markup}".to_string(),
+        None,
+    )
+    .multi_line();
+    assert_golden("text_markup_multi_line", &render(screen, &[Duration::ZERO]));
+}
+
+#[test]
+fn text_effects() {
+    let effects = [
+        ("rainbow", TextEffect::Rainbow, 500),
+        (
+            "gradient",
+            TextEffect::Gradient(Rgb888::RED, Rgb888::BLUE),
+            0,
+        ),
+        ("blink_off", TextEffect::Blink, 700),
+        ("wave", TextEffect::Wave, 250),
+        ("typewriter", TextEffect::Typewriter, 500),
+    ];
+    for (name, effect, millis) in effects {
+        let screen =
+            TextScreen::with_text("Open night {b}tonight".to_string(), None).with_effect(effect);
+        let times = [Duration::ZERO, Duration::from_millis(millis)];
+        assert_golden(&format!("text_effect_{name}"), &render(screen, &times));
+    }
+}
+
+#[test]
+fn text_effects_scroll() {
+    let screen = TextScreen::with_text(
+        "some much longer text that goes off the screen".to_string(),
+        None,
+    )
+    .with_effect(TextEffect::Wave);
+    let times = [Duration::ZERO, Duration::from_secs(1)];
+    assert_golden("text_effect_wave_scrolling_1s", &render(screen, &times));
+}
+
+#[test]
+fn text_effects_multi_line() {
+    let screen = TextScreen::with_text("Open night tonight\nall welcome".to_string(), None)
+        .multi_line()
+        .with_effect(TextEffect::Typewriter);
+    let times = [Duration::ZERO, Duration::from_millis(1500)];
+    assert_golden("text_effect_typewriter_multi_line", &render(screen, &times));
+}
+
+#[test]
+fn typewriter_text_stays_up_once_typed() {
+    let display = Framebuffer::new(PANEL_SIZE);
+    let mut screen: Box<dyn Screen<Framebuffer>> = Box::new(
+        TextScreen::with_text("Hello".to_string(), None).with_effect(TextEffect::Typewriter),
+    );
+    assert_eq!(
+        screen.single_display_duration(&display),
+        Duration::from_secs(5) + Duration::from_millis(80 * 5)
+    );
+
+    let clock = start_clock();
+    let mut frame = Framebuffer::new(PANEL_SIZE);
+    let Ok(()) = screen.draw(&mut frame, &clock);
+    clock.advance(Duration::from_millis(100));
+    assert!(screen.needs_redraw(&clock));
+    clock.advance(Duration::from_secs(1));
+    let Ok(()) = screen.draw(&mut frame, &clock);
+    assert!(!screen.needs_redraw(&clock));
+}
+
+#[test]
+fn text_fit() {
+    let texts = [
+        ("big", "OPEN!"),
+        ("one_line", "Open night tonight"),
+        ("two_lines", "Open night tonight, {green}all welcome!"),
+    ];
+    for (name, text) in texts {
+        let screen = TextScreen::with_text(text.to_string(), None).fit();
+        assert_golden(
+            &format!("text_fit_{name}"),
+            &render(screen, &[Duration::ZERO]),
+        );
+    }
+}
+
+#[test]
+fn fitted_text_only_scrolls_if_nothing_fits() {
+    let display = Framebuffer::new(PANEL_SIZE);
+    let duration = |text: &str, fit: bool| {
+        let mut screen = TextScreen::with_text(text.to_string(), None);
+        if fit {
+            screen = screen.fit();
+        }
+        Screen::<Framebuffer>::single_display_duration(&screen, &display)
+    };
+
+    let two_lines = "Open night tonight, all welcome!";
+    assert_eq!(duration(two_lines, true), Duration::from_secs(5));
+    assert_ne!(duration(two_lines, false), Duration::from_secs(5));
+
+    let long =
+        "some much longer text that goes off the screen, and wouldn't fit on two lines either";
+    assert_eq!(duration(long, true), duration(long, false));
+}
+
+#[test]
+fn environment() {
+    let screen = EnvironmentScreen::new(Some(18.9), Some(404));
+    assert_golden("environment", &render(screen, &[Duration::ZERO]));
+}
+
+#[test]
+fn environment_high_co2() {
+    let screen = EnvironmentScreen::new(Some(23.4), Some(1500));
+    assert_golden("environment_high_co2", &render(screen, &[Duration::ZERO]));
+}
+
+#[test]
+fn environment_no_readings() {
+    let screen = EnvironmentScreen::new(None, None);
+    assert_golden(
+        "environment_no_readings",
+        &render(screen, &[Duration::ZERO]),
+    );
+}
+
+#[test]
+fn script_example() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../scripts/outside.rhai");
+    let mut screen = ScriptScreen::load(&path).unwrap();
+    Screen::<Framebuffer>::handle_message(
+        &mut screen,
+        &ScreenMessage::Value {
+            topic: "sensor/outside/temperature".to_string(),
+            value: "12.5".to_string(),
+        },
+    );
+    assert_golden(
+        "script_example",
+        &render(screen, &[Duration::ZERO, Duration::from_secs(30)]),
+    );
+}
+
+#[test]
+fn hate() {
+    let times = [Duration::ZERO, Duration::from_secs(2)];
+    assert_golden("hate_2s", &render(HateScreen::new(), &times));
+}
+
+#[test]
+fn clock() {
+    let screen = ClockScreen::default();
+    assert_golden(
+        "clock",
+        &render_sized(screen, Size::new(40, 32), &[Duration::ZERO]),
+    );
+}
+
+#[test]
+fn ambient() {
+    let times = [Duration::ZERO, Duration::from_secs(10)];
+    assert_golden("ambient_10s", &render(AmbientScreen::new(), &times));
+}
+
+#[test]
+fn toast_over_text() {
+    let clock = start_clock();
+    let (send, recv) = mpsc::channel();
+    let mut logic = DisplayLogic::new(recv, Arc::new(AtomicBool::new(false)));
+    logic.set_clock(Box::new(clock.clone()));
+    logic.add(Box::new(TextScreen::with_text(
+        "Hello, World!".to_string(),
+        None,
+    )));
+    send.send(Command::Notify(Toast::new("Door opened")))
+        .unwrap();
+
+    // Once it's finished sliding in, the text should have moved up out of the way
+    let mut frame = Framebuffer::new(PANEL_SIZE);
+    let Ok(()) = logic.draw(&mut frame);
+    clock.advance(Duration::from_secs(1));
+    let Ok(()) = logic.draw(&mut frame);
+    assert_golden("toast_over_text", &frame);
+}
@@ -0,0 +1,92 @@
+//! Tests for parsing schedules, and when they're active.
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use logic::schedule::Schedule;
+
+/// The given time on a day in the week of 2nd June 2025, which starts on a monday.
+fn at(day: Weekday, time: &str) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2025, 6, 2 + day.num_days_from_monday())
+        .unwrap()
+        .and_time(NaiveTime::parse_from_str(time, "%H:%M").unwrap())
+}
+
+fn time(s: &str) -> NaiveTime {
+    NaiveTime::parse_from_str(s, "%H:%M").unwrap()
+}
+
+#[test]
+fn schedules_are_parsed() {
+    let weekdays = [
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+    ];
+    assert_eq!(
+        "weekdays 18:00-23:00".parse::<Schedule>().unwrap(),
+        Schedule::on_days(weekdays).between(time("18:00"), time("23:00"))
+    );
+    assert_eq!(
+        "tue".parse::<Schedule>().unwrap(),
+        Schedule::on_days([Weekday::Tue])
+    );
+    assert_eq!(
+        "sat-sun".parse::<Schedule>().unwrap(),
+        Schedule::on_days([Weekday::Sat, Weekday::Sun])
+    );
+    // Ranges can wrap around the end of the week
+    assert_eq!(
+        "fri-mon".parse::<Schedule>().unwrap(),
+        Schedule::on_days([Weekday::Fri, Weekday::Sat, Weekday::Sun, Weekday::Mon])
+    );
+    assert_eq!(
+        "mon,thu 18:00-23:00".parse::<Schedule>().unwrap(),
+        Schedule::on_days([Weekday::Mon, Weekday::Thu]).between(time("18:00"), time("23:00"))
+    );
+    assert_eq!(
+        "22:00-02:00".parse::<Schedule>().unwrap(),
+        Schedule::always().between(time("22:00"), time("02:00"))
+    );
+    assert_eq!("daily".parse::<Schedule>().unwrap(), Schedule::always());
+}
+
+#[test]
+fn schedules_round_trip() {
+    for schedule in ["daily", "tue", "mon,thu 18:00-23:00", "daily 22:00-02:00"] {
+        assert_eq!(schedule.parse::<Schedule>().unwrap().to_string(), schedule);
+    }
+}
+
+#[test]
+fn bad_schedules_are_explained() {
+    let error = |s: &str| s.parse::<Schedule>().unwrap_err().to_string();
+
+    assert!(error("someday").contains("someday"));
+    assert!(error("mon-someday").contains("someday"));
+    assert!(error("tue 18:00").contains("18:00-23:00"));
+    assert!(error("tue 18:00-25:00").contains("25:00"));
+    assert!(error("18:00-19:00 20:00-21:00").contains("more than one"));
+}
+
+#[test]
+fn schedules_are_active_in_their_window() {
+    let schedule: Schedule = "weekdays 18:00-23:00".parse().unwrap();
+    assert!(!schedule.is_active(at(Weekday::Tue, "17:59")));
+    assert!(schedule.is_active(at(Weekday::Tue, "18:00")));
+    assert!(schedule.is_active(at(Weekday::Tue, "22:59")));
+    assert!(!schedule.is_active(at(Weekday::Tue, "23:00")));
+    assert!(!schedule.is_active(at(Weekday::Sat, "19:00")));
+}
+
+#[test]
+fn windows_run_past_midnight() {
+    // Belongs to friday, so it carries on into saturday morning, but doesn't start on saturday night
+    let schedule: Schedule = "fri 22:00-02:00".parse().unwrap();
+    assert!(!schedule.is_active(at(Weekday::Fri, "21:59")));
+    assert!(schedule.is_active(at(Weekday::Fri, "23:30")));
+    assert!(schedule.is_active(at(Weekday::Sat, "01:59")));
+    assert!(!schedule.is_active(at(Weekday::Sat, "02:00")));
+    assert!(!schedule.is_active(at(Weekday::Sat, "23:00")));
+    assert!(!schedule.is_active(at(Weekday::Fri, "01:00")));
+}
@@ -0,0 +1,184 @@
+//! Tests for screens drawn by scripts.
+
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{atomic::AtomicBool, mpsc, Arc},
+    time::Duration,
+};
+
+use chrono::NaiveDate;
+use embedded_graphics::{pixelcolor::Rgb888, prelude::*};
+use logic::{
+    clock::FakeClock,
+    config::Config,
+    framebuffer::Framebuffer,
+    screens::{Screen, ScriptScreen},
+    Command, DisplayLogic, PANEL_SIZE,
+};
+
+/// Write a script to a temporary file with the given name, and return its path.
+fn script(name: &str, source: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("scripts-{}-{name}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(format!("{name}.rhai"));
+    fs::write(&path, source).unwrap();
+
+    path
+}
+
+fn clock() -> FakeClock {
+    FakeClock::new(
+        NaiveDate::from_ymd_opt(2025, 6, 3)
+            .unwrap()
+            .and_hms_opt(19, 30, 0)
+            .unwrap(),
+    )
+}
+
+#[test]
+fn scripts_can_draw_and_remember_things() {
+    let path = script(
+        "counter",
+        r#"
+        fn duration() { 7 }
+        fn draw() {
+            this.count = if this.count == () { 1 } else { this.count + 1 };
+            clear(rgb(0, 0, 255));
+            rect(0, 0, this.count, 1, rgb(255, 0, 0));
+            pixel(width() - 1, height() - 1, 0x00ff00);
+        }
+        "#,
+    );
+    let mut screen: Box<dyn Screen<Framebuffer>> = Box::new(ScriptScreen::load(&path).unwrap());
+    let clock = clock();
+    let mut frame = Framebuffer::new(PANEL_SIZE);
+    assert_eq!(screen.id(), "counter");
+    assert_eq!(
+        screen.single_display_duration(&frame),
+        Duration::from_secs(7)
+    );
+
+    for _ in 0..3 {
+        assert!(screen.needs_redraw(&clock));
+        let Ok(()) = screen.draw(&mut frame, &clock);
+        assert!(!screen.needs_redraw(&clock));
+        clock.advance(Duration::from_millis(50));
+    }
+    assert_eq!(screen.failure(), None);
+    let at = |x, y| frame.pixels()[(y * PANEL_SIZE.width + x) as usize];
+    assert_eq!(at(2, 0), Rgb888::RED);
+    assert_eq!(at(3, 0), Rgb888::BLUE);
+    assert_eq!(
+        at(PANEL_SIZE.width - 1, PANEL_SIZE.height - 1),
+        Rgb888::GREEN
+    );
+}
+
+#[test]
+fn scripts_can_use_mqtt_values() {
+    let path = script(
+        "values",
+        r#"
+        fn draw() {
+            if value("test/values/name") != "hello" { throw "wrong value"; }
+            if number("test/values/number") != 2.5 { throw "wrong number"; }
+            if number("test/values/name") != () { throw "not a number"; }
+            if value("test/values/missing") != () { throw "should be missing"; }
+        }
+        "#,
+    );
+    let (send, recv) = mpsc::channel();
+    let mut logic = DisplayLogic::new(recv, Arc::new(AtomicBool::new(false)));
+    logic.set_clock(Box::new(clock()));
+    let value = |topic: &str, value: &str| Command::SetValue(topic.to_string(), value.to_string());
+    send.send(value("test/values/name", "hello")).unwrap();
+    // Screens added after a value came in still get it
+    send.send(Command::Add(Box::new(ScriptScreen::load(&path).unwrap())))
+        .unwrap();
+    send.send(value("test/values/number", " 2.5")).unwrap();
+    let mut display = Framebuffer::new(PANEL_SIZE);
+    logic.draw(&mut display).unwrap();
+    logic.draw(&mut display).unwrap();
+    let state = logic.state();
+    assert!(state.quarantined.is_empty(), "{:?}", state.quarantined);
+    assert_eq!(state.screens[0].id, "values");
+}
+
+#[test]
+fn runaway_scripts_are_stopped() {
+    for (name, source) in [
+        ("forever", "fn draw() { loop {} }"),
+        ("string", r#"fn draw() { let s = "x"; loop { s += s; } }"#),
+        ("recursion", "fn f(n) { f(n + 1) } fn draw() { f(0) }"),
+        ("drawing", "fn draw() { loop { pixel(0, 0, 0); } }"),
+        ("error", "fn draw() { 1 / 0 }"),
+    ] {
+        let mut screen: Box<dyn Screen<Framebuffer>> =
+            Box::new(ScriptScreen::load(&script(name, source)).unwrap());
+        let Ok(()) = screen.draw(&mut Framebuffer::new(PANEL_SIZE), &clock());
+        assert!(screen.failure().is_some(), "{name} wasn't stopped");
+    }
+}
+
+#[test]
+fn broken_scripts_are_quarantined() {
+    assert!(ScriptScreen::load(&script("syntax", "fn draw( {")).is_err());
+    assert!(ScriptScreen::load(&script("no_draw", "fn drew() {}")).is_err());
+    assert!(ScriptScreen::load(&script("import", r#"fn draw() { import "x" as x; }"#)).is_ok());
+
+    let (_send, recv) = mpsc::channel();
+    let mut logic = DisplayLogic::new(recv, Arc::new(AtomicBool::new(false)));
+    logic.set_clock(Box::new(clock()));
+    logic.add(Box::new(ScriptScreen::load_or_failed(&script(
+        "broken",
+        "fn draw( {",
+    ))));
+    logic.add(Box::new(
+        ScriptScreen::load(&script("import", r#"fn draw() { import "x" as x; }"#)).unwrap(),
+    ));
+    let mut display = Framebuffer::new(PANEL_SIZE);
+    logic.draw(&mut display).unwrap();
+    logic.draw(&mut display).unwrap();
+
+    let state = logic.state();
+    assert!(state.screens.is_empty());
+    let quarantined = state
+        .quarantined
+        .iter()
+        .map(|q| q.id.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(quarantined, ["broken", "import"]);
+}
+
+#[test]
+fn scripts_are_loaded_from_the_script_dir() {
+    let dir = std::env::temp_dir().join(format!("script-dir-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("scripts")).unwrap();
+    fs::write(dir.join("scripts/b.rhai"), "fn draw() {}").unwrap();
+    fs::write(dir.join("scripts/a.rhai"), "fn draw() {}").unwrap();
+    fs::write(dir.join("scripts/notes.txt"), "not a script").unwrap();
+    // The script directory is relative to the config file, not wherever we're run from
+    let config_file = dir.join("display.toml");
+    fs::write(&config_file, "script_dir = \"scripts\"").unwrap();
+    let config = Config::load(&config_file).unwrap().unwrap();
+    assert_eq!(config.script_dir, Some(dir.join("scripts")));
+
+    let (_send, recv) = mpsc::channel();
+    let mut logic = DisplayLogic::new(recv, Arc::new(AtomicBool::new(false)));
+    config.add_scripts(&mut logic);
+    // Loading them again replaces them
+    config.add_scripts(&mut logic);
+    let ids = logic
+        .state()
+        .screens
+        .into_iter()
+        .map(|s| s.id)
+        .collect::<Vec<_>>();
+    assert_eq!(ids, ["a", "b"]);
+
+    fs::write(dir.join("scripts/c.rhai"), "fn draw( {").unwrap();
+    let error = Config::load(&config_file).unwrap_err();
+    assert!(error.to_string().contains("c.rhai"), "{error}");
+}
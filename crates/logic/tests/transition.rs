@@ -0,0 +1,89 @@
+//! Tests for parsing and drawing [`Transition`]s.
+
+use std::time::Duration;
+
+use embedded_graphics::{pixelcolor::Rgb888, prelude::*};
+use logic::{
+    framebuffer::Framebuffer,
+    screens::{Screen, ScreenSpec, TextScreen},
+    transition::{Transition, TransitionKind},
+    PANEL_SIZE,
+};
+
+/// A frame the size of the panel, filled with one colour.
+fn filled(colour: Rgb888) -> Framebuffer {
+    let mut frame = Framebuffer::new(PANEL_SIZE);
+    frame.pixels_mut().fill(colour);
+    frame
+}
+
+/// Blend from red to blue, halfway through the given kind of transition.
+fn halfway(kind: TransitionKind) -> Framebuffer {
+    let (from, to) = (filled(Rgb888::RED), filled(Rgb888::BLUE));
+    let mut out = filled(Rgb888::BLACK);
+    let transition = Transition::new(kind, Duration::from_millis(400));
+    let progress = transition.progress(Duration::from_millis(200));
+    transition.blend(progress, &from, &to, &mut out);
+    out
+}
+
+#[test]
+fn transitions_round_trip() {
+    for transition in [
+        "cut",
+        "crossfade 400ms",
+        "wipe 250ms",
+        "slide-left 1000ms",
+        "slide-up 300ms",
+        "dissolve 500ms",
+    ] {
+        let parsed = transition.parse::<Transition>().unwrap();
+        assert_eq!(parsed.to_string(), transition);
+    }
+    assert_eq!(
+        "crossfade".parse::<Transition>().unwrap(),
+        Transition::new(TransitionKind::Crossfade, Duration::from_millis(400))
+    );
+    assert_eq!(
+        "cut 100ms".parse::<Transition>().unwrap(),
+        Transition::cut()
+    );
+    assert!("fade 400ms".parse::<Transition>().is_err());
+    assert!("wipe 2s".parse::<Transition>().is_err());
+    assert!("wipe 400ms please".parse::<Transition>().is_err());
+
+    // Screens keep theirs when they're saved
+    let wipe = Transition::new(TransitionKind::Wipe, Duration::from_millis(250));
+    let screen = TextScreen::with_text("hi".to_string(), None).with_transition(wipe);
+    let spec = Screen::<Framebuffer>::spec(&screen).unwrap();
+    assert!(matches!(spec, ScreenSpec::Text { transition: Some(t), .. } if t == wipe));
+    assert_eq!(spec.build().spec(), Some(spec));
+}
+
+#[test]
+fn cuts_are_immediate() {
+    let out = halfway(TransitionKind::Cut);
+    assert!(out.pixels().iter().all(|&p| p == Rgb888::BLUE));
+}
+
+#[test]
+fn transitions_blend_halfway() {
+    let out = halfway(TransitionKind::Crossfade);
+    assert_eq!(out.pixel(Point::new(0, 0)), Some(Rgb888::new(128, 0, 128)));
+
+    // Wipes and slides have the next screen on one side and the previous one on the other
+    let out = halfway(TransitionKind::Wipe);
+    assert_eq!(out.pixel(Point::new(0, 0)), Some(Rgb888::BLUE));
+    assert_eq!(out.pixel(Point::new(191, 0)), Some(Rgb888::RED));
+    let out = halfway(TransitionKind::SlideLeft);
+    assert_eq!(out.pixel(Point::new(0, 0)), Some(Rgb888::RED));
+    assert_eq!(out.pixel(Point::new(191, 0)), Some(Rgb888::BLUE));
+    let out = halfway(TransitionKind::SlideUp);
+    assert_eq!(out.pixel(Point::new(0, 0)), Some(Rgb888::RED));
+    assert_eq!(out.pixel(Point::new(0, 31)), Some(Rgb888::BLUE));
+
+    // Dissolves have some of each
+    let out = halfway(TransitionKind::Dissolve);
+    assert!(out.pixels().contains(&Rgb888::RED));
+    assert!(out.pixels().contains(&Rgb888::BLUE));
+}
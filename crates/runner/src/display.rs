@@ -10,11 +10,11 @@ pub struct Display {
     canvas: Box<Canvas>,
 
     /// The logic/state behind what we render
-    logic: DisplayLogic<Canvas>,
+    logic: DisplayLogic,
 }
 
 impl Display {
-    pub fn new(matrix: RGBMatrix, canvas: Box<Canvas>, logic: DisplayLogic<Canvas>) -> Self {
+    pub fn new(matrix: RGBMatrix, canvas: Box<Canvas>, logic: DisplayLogic) -> Self {
         Self {
             matrix,
             canvas,
@@ -22,11 +22,10 @@ impl Display {
         }
     }
 
-    pub fn main_loop(mut self) -> Result<(), &'static str> {
+    /// Draw frames forever. Screens that fail to draw are dealt with by [`DisplayLogic`], so this never stops.
+    pub fn main_loop(mut self) -> ! {
         loop {
-            self.logic
-                .draw(&mut self.canvas)
-                .map_err(|_| "error drawing")?;
+            let Ok(()) = self.logic.draw(self.canvas.as_mut());
             self.canvas = self.matrix.update_on_vsync(self.canvas);
         }
     }
@@ -1,11 +1,17 @@
 use std::{
     env,
+    path::PathBuf,
+    process,
     sync::{atomic::AtomicBool, mpsc, Arc},
     thread,
 };
 
 use display::Display;
-use logic::{screens::TestScreen, DisplayLogic};
+use logic::{
+    clock::SystemClock,
+    config::{Config, DEFAULT_CONFIG_FILE},
+    DisplayLogic, PANEL_SIZE,
+};
 use mqtt::MQTTListener;
 use rpi_led_panel::{HardwareMapping, NamedPixelMapperType, RGBMatrix, RGBMatrixConfig};
 
@@ -15,26 +21,30 @@ mod mqtt;
 fn main() {
     env_logger::init();
 
-    // Display config
-    let config: RGBMatrixConfig = {
-        let mut c = RGBMatrixConfig::default();
-        c.hardware_mapping = HardwareMapping::adafruit_hat_pwm();
-        c.rows = 32;
-        c.cols = 192;
-        c.refresh_rate = 120;
-        c.pwm_bits = 11;
-        c.pwm_lsb_nanoseconds = 130;
-        c.dither_bits = 0;
-        c.led_brightness = 20;
-        c.slowdown = Some(1);
-        c.pixelmapper = vec![NamedPixelMapperType::Rotate(180)];
+    // Fail early if the config's wrong
+    let config_file = env::var_os("CONFIG_FILE").map_or(DEFAULT_CONFIG_FILE.into(), PathBuf::from);
+    let display_config = Config::load_or_default(&config_file).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        process::exit(1);
+    });
 
-        c
+    // Display config
+    let config = RGBMatrixConfig {
+        hardware_mapping: HardwareMapping::adafruit_hat_pwm(),
+        rows: PANEL_SIZE.height as usize,
+        cols: PANEL_SIZE.width as usize,
+        refresh_rate: 120,
+        pwm_bits: 11,
+        pwm_lsb_nanoseconds: 130,
+        dither_bits: 0,
+        led_brightness: 20,
+        slowdown: Some(1),
+        pixelmapper: vec![NamedPixelMapperType::Rotate(180)],
+        ..Default::default()
     };
 
     // Cross-thread commuication bits
     let (send, recv) = mpsc::channel();
-    let (del_send, del_recv) = mpsc::channel();
     let sleep = Arc::new(AtomicBool::new(false));
 
     let mqtt = MQTTListener::new(
@@ -42,15 +52,33 @@ fn main() {
             .unwrap_or_else(|_| "mqtt.hacklab".to_string())
             .as_str(),
         send,
-        del_send,
         sleep.clone(),
+        Box::new(SystemClock),
     )
-    .unwrap();
+    .unwrap()
+    .with_script_topics(display_config.script_topics.clone());
+
+    let mut display_logic = DisplayLogic::new(recv, sleep);
+
+    // Playlists are kept across restarts
+    let playlist_file = env::var("PLAYLIST_FILE").unwrap_or_else(|_| "playlists.json".to_string());
+    display_logic
+        .load_playlists(&playlist_file)
+        .expect("Couldn't load playlists");
 
-    let mut display_logic = DisplayLogic::new(recv, del_recv, sleep);
+    // Layout, sleep mode etc
+    display_config.apply(&mut display_logic);
 
-    // Start off with test screen so we know it's working
-    display_logic.add(Box::new(TestScreen));
+    // Pick up where we left off, if we can
+    let rotation_file = env::var("ROTATION_FILE").unwrap_or_else(|_| "rotation.json".to_string());
+    let restored = display_logic
+        .load_rotations(&rotation_file)
+        .expect("Couldn't load rotations");
+    if !restored {
+        display_config.add_screens(&mut display_logic);
+    }
+    // Scripts are always reloaded, in case they've been changed
+    display_config.add_scripts(&mut display_logic);
 
     // MQTT bits in one thread, drawing in the other
     let (matrix, canvas) = RGBMatrix::new(config, 0).expect("Matrix initialization failed");
@@ -60,7 +88,7 @@ fn main() {
             mqtt.main_loop();
         });
         scope.spawn(move || {
-            display.main_loop().unwrap();
+            display.main_loop();
         });
     });
 }
@@ -5,17 +5,20 @@ use std::{
         atomic::{AtomicBool, Ordering},
         mpsc, Arc,
     },
-    time::{Duration, SystemTime},
+    thread,
+    time::{Duration, Instant},
 };
 
-use embedded_graphics::{
-    mono_font::{iso_8859_16::FONT_10X20, MonoTextStyle},
-    pixelcolor::Rgb888,
-    prelude::RgbColor,
-};
+use embedded_graphics::{pixelcolor::Rgb888, prelude::RgbColor};
 use log::{debug, warn};
-use logic::screens::{EnvironmentScreen, HateScreen, Screen, TextScreen};
-use rpi_led_panel::Canvas;
+use logic::{
+    clock::Clock,
+    fonts::{self, DEFAULT_FONT},
+    overlay::Toast,
+    playlist::Playlist,
+    screens::{EnvironmentScreen, HateScreen, ScreenMessage, TextEffect, TextScreen},
+    Command,
+};
 use rumqttc::{Client, Event, Incoming, MqttOptions, Outgoing, Publish, QoS, SubscribeFilter};
 
 /// Deals with listening on the MQTT bus, and sending messages to the logic based off of that.
@@ -24,27 +27,50 @@ pub struct MQTTListener {
     mqtt_options: MqttOptions,
 
     /// Communicating with the logic
-    screen_channel: mpsc::Sender<Box<dyn Screen<Canvas>>>,
-    screen_del_channel: mpsc::Sender<String>,
+    command_channel: mpsc::Sender<Command>,
 
     /// For text screen
     next_colour: Rgb888,
+    next_font: String,
+    next_effect: Option<TextEffect>,
 
     /// For environment screen
-    last_co2: Option<(u32, SystemTime)>,
-    last_temp: Option<(f32, SystemTime)>,
+    last_co2: Option<(u32, Instant)>,
+    last_temp: Option<(f32, Instant)>,
 
     sleep: Arc<AtomicBool>,
+
+    /// Extra topics to subscribe to, so scripts can use their values.
+    script_topics: Vec<String>,
+
+    /// Used to check when environment readings expire.
+    clock: Box<dyn Clock>,
 }
 
 // Topics for text screen
 const TEXT_COLOUR_TOPIC: &str = "display/g1/windowled/colour";
 const TEXT_TOPIC: &str = "display/g1/windowled/text";
+const TEXT_FONT_TOPIC: &str = "display/g1/windowled/font";
+const TEXT_EFFECT_TOPIC: &str = "display/g1/windowled/effect";
+
+// Topics for controlling the display
+const CONTROL_TOPIC: &str = "display/g1/windowled/control";
+const DELETE_TOPIC: &str = "display/g1/windowled/delete";
+const BRIGHTNESS_TOPIC: &str = "display/g1/windowled/brightness";
+const NOTIFY_TOPIC: &str = "display/g1/windowled/notify";
+
+// Topics for playlists
+const PLAYLIST_TOPIC: &str = "display/g1/windowled/playlist";
+const DEFINE_PLAYLIST_TOPIC: &str = "display/g1/windowled/playlist/define";
+const DELETE_PLAYLIST_TOPIC: &str = "display/g1/windowled/playlist/delete";
 
 // Topics for environment screen
 const TEMP_TOPIC: &str = "environment/g1/elsys/temperature";
 const CO2_TOPIC: &str = "environment/g1/elsys/co2";
 
+/// The current state of the display is published here, retained.
+const STATUS_TOPIC: &str = "display/g1/windowled/status";
+
 const GLOBAL_PRESENCE_TOPIC: &str = "sensor/global/presence";
 const CHECK_TIME_EXPIRY_TOPIC: &str = "timesignal/300";
 
@@ -52,28 +78,40 @@ const CATASTROPHE_LEVER_TOPIC: &str = "catastrophe/state/lever";
 
 const ENVIRONMENT_DATA_TIMEOUT: Duration = Duration::from_mins(2);
 
+/// Text messages that haven't been shown enough times by now are thrown away, ie if the space has been empty.
+const TEXT_MESSAGE_LIFETIME: Duration = Duration::from_hours(12);
+
 impl MQTTListener {
-    /// Create a new listener for the given MQTT server, communicating with the logic loop via the given channels.
+    /// Create a new listener for the given MQTT server, communicating with the logic loop via the given channel.
     pub fn new(
         conn_string: &str,
-        screen_channel: mpsc::Sender<Box<dyn Screen<Canvas>>>,
-        screen_del_channel: mpsc::Sender<String>,
+        command_channel: mpsc::Sender<Command>,
         sleep: Arc<AtomicBool>,
+        clock: Box<dyn Clock>,
     ) -> Result<Self, io::Error> {
         let mut mqtt_options = MqttOptions::new("rpiledmatrix", conn_string, 1883);
         mqtt_options.set_keep_alive(Duration::from_secs(120));
 
         Ok(Self {
             mqtt_options,
-            screen_channel,
-            screen_del_channel,
+            command_channel,
             next_colour: Rgb888::MAGENTA,
+            next_font: DEFAULT_FONT.to_string(),
+            next_effect: None,
             last_co2: None,
             last_temp: None,
             sleep,
+            script_topics: Vec::new(),
+            clock,
         })
     }
 
+    /// Also subscribe to the given topics, so their values can be used by scripts. See [`Command::SetValue`].
+    pub fn with_script_topics(mut self, topics: Vec<String>) -> Self {
+        self.script_topics = topics;
+        self
+    }
+
     /// Run the main MQTT loop, processing events and sending the results to the logic loop.
     pub fn main_loop(mut self) -> ! {
         // Setup
@@ -82,6 +120,15 @@ impl MQTTListener {
             .subscribe_many([
                 SubscribeFilter::new(TEXT_TOPIC.to_string(), QoS::ExactlyOnce),
                 SubscribeFilter::new(TEXT_COLOUR_TOPIC.to_string(), QoS::ExactlyOnce),
+                SubscribeFilter::new(TEXT_FONT_TOPIC.to_string(), QoS::ExactlyOnce),
+                SubscribeFilter::new(TEXT_EFFECT_TOPIC.to_string(), QoS::ExactlyOnce),
+                SubscribeFilter::new(CONTROL_TOPIC.to_string(), QoS::ExactlyOnce),
+                SubscribeFilter::new(DELETE_TOPIC.to_string(), QoS::ExactlyOnce),
+                SubscribeFilter::new(BRIGHTNESS_TOPIC.to_string(), QoS::ExactlyOnce),
+                SubscribeFilter::new(NOTIFY_TOPIC.to_string(), QoS::ExactlyOnce),
+                SubscribeFilter::new(PLAYLIST_TOPIC.to_string(), QoS::ExactlyOnce),
+                SubscribeFilter::new(DEFINE_PLAYLIST_TOPIC.to_string(), QoS::ExactlyOnce),
+                SubscribeFilter::new(DELETE_PLAYLIST_TOPIC.to_string(), QoS::ExactlyOnce),
                 SubscribeFilter::new(TEMP_TOPIC.to_string(), QoS::ExactlyOnce),
                 SubscribeFilter::new(CO2_TOPIC.to_string(), QoS::ExactlyOnce),
                 SubscribeFilter::new(GLOBAL_PRESENCE_TOPIC.to_string(), QoS::ExactlyOnce),
@@ -89,8 +136,31 @@ impl MQTTListener {
                 SubscribeFilter::new(CATASTROPHE_LEVER_TOPIC.to_string(), QoS::ExactlyOnce),
             ])
             .unwrap();
+        if !self.script_topics.is_empty() {
+            client
+                .subscribe_many(
+                    self.script_topics
+                        .iter()
+                        .map(|t| SubscribeFilter::new(t.clone(), QoS::AtMostOnce)),
+                )
+                .unwrap();
+        }
 
-        self.refresh_environment_screen();
+        // Publish the state of the display whenever it changes
+        let (state_send, state_recv) = mpsc::channel();
+        self.command_channel
+            .send(Command::Subscribe(state_send))
+            .unwrap();
+        let status_client = client.clone();
+        thread::spawn(move || {
+            for state in state_recv {
+                debug!("publishing new display state");
+                let json = serde_json::to_vec(&state).expect("the state can always be serialised");
+                if let Err(e) = status_client.publish(STATUS_TOPIC, QoS::AtLeastOnce, true, json) {
+                    warn!("error publishing display state: {e}");
+                }
+            }
+        });
 
         loop {
             // Process messages
@@ -112,13 +182,16 @@ impl MQTTListener {
     fn attempt_handle_message(&mut self, msg: Publish, client: &mut Client) -> Option<()> {
         let payload = String::from_utf8(msg.payload.to_vec()).ok()?;
 
-        // If asleep, try not to process so much
+        // Scripts can use the value of anything we're subscribed to
+        self.command_channel
+            .send(Command::SetValue(msg.topic.to_string(), payload.clone()))
+            .unwrap();
+
+        // Anything else that arrives while asleep is still passed on, and shown when we wake up
         if msg.topic.as_str() == GLOBAL_PRESENCE_TOPIC {
             self.sleep.store(payload == "empty", Ordering::Relaxed);
             debug!("new sleep state: {}", payload == "empty");
-        }
-        if self.sleep.load(Ordering::Relaxed) {
-            return None;
+            return Some(());
         }
 
         match msg.topic.as_str() {
@@ -137,14 +210,125 @@ impl MQTTListener {
 
                 Some(())
             }
+            TEXT_FONT_TOPIC => {
+                // Store the font name, for future messages on the text topic to use
+                let name = payload.trim();
+                if !fonts::exists(name) {
+                    warn!("unknown font {name:?}, falling back to {DEFAULT_FONT}");
+                }
+                self.next_font = name.to_string();
+
+                Some(())
+            }
+            TEXT_EFFECT_TOPIC => {
+                // Store the effect, for future messages on the text topic to use. Anything else turns it off.
+                self.next_effect = match payload.trim() {
+                    "" | "none" => None,
+                    effect => match effect.parse() {
+                        Ok(effect) => Some(effect),
+                        Err(e) => {
+                            warn!("{e}");
+                            None
+                        }
+                    },
+                };
+
+                Some(())
+            }
             TEXT_TOPIC => {
-                // Show some text
-                self.screen_channel
-                    .send(Box::new(TextScreen::new(
-                        payload,
-                        MonoTextStyle::new(&FONT_10X20, self.next_colour),
-                        None,
-                    )))
+                // Show some text. If it's been split into lines, it's meant to be read that way, otherwise it's shown
+                // as big as it fits.
+                let multi_line = payload.contains('\n');
+                let style = fonts::font_for(&self.next_font, &payload)
+                    .font
+                    .style(self.next_colour);
+                let mut screen = TextScreen::new(payload, style, None)
+                    .with_font(&self.next_font)
+                    .with_expiry(self.clock.local_now() + TEXT_MESSAGE_LIFETIME);
+                if multi_line {
+                    screen = screen.multi_line();
+                } else {
+                    screen = screen.fit();
+                }
+                if let Some(effect) = self.next_effect {
+                    screen = screen.with_effect(effect);
+                }
+                self.command_channel
+                    .send(Command::Add(Box::new(screen)))
+                    .unwrap();
+
+                Some(())
+            }
+
+            // Controlling the display
+            CONTROL_TOPIC => {
+                let command = match payload.trim() {
+                    "next" => Command::Next,
+                    "previous" => Command::Previous,
+                    "pause" => Command::PauseRotation,
+                    "resume" => Command::ResumeRotation,
+                    "clear" => Command::ClearAll,
+                    _ => {
+                        warn!("unknown control command {:?}", payload);
+                        return None;
+                    }
+                };
+                self.command_channel.send(command).unwrap();
+
+                Some(())
+            }
+            DELETE_TOPIC => {
+                self.command_channel
+                    .send(Command::Delete(payload.trim().to_string()))
+                    .unwrap();
+
+                Some(())
+            }
+            BRIGHTNESS_TOPIC => {
+                let brightness = u8::from_str(payload.trim()).ok()?;
+                self.command_channel
+                    .send(Command::SetBrightness(brightness))
+                    .unwrap();
+
+                Some(())
+            }
+
+            NOTIFY_TOPIC => {
+                // Shown over whatever's on screen, using the current text colour
+                self.command_channel
+                    .send(Command::Notify(
+                        Toast::new(payload.trim()).with_colours(self.next_colour, Rgb888::BLACK),
+                    ))
+                    .unwrap();
+
+                Some(())
+            }
+
+            // Playlists
+            PLAYLIST_TOPIC => {
+                self.command_channel
+                    .send(Command::SwitchPlaylist(payload.trim().to_string()))
+                    .unwrap();
+
+                Some(())
+            }
+            DEFINE_PLAYLIST_TOPIC => {
+                let playlist: Playlist = match serde_json::from_str(&payload) {
+                    Ok(playlist) => playlist,
+                    Err(e) => {
+                        warn!("invalid playlist definition: {e}");
+                        return None;
+                    }
+                };
+                self.command_channel
+                    .send(Command::DefinePlaylist(playlist))
+                    .unwrap();
+
+                Some(())
+            }
+            DELETE_PLAYLIST_TOPIC => {
+                self.command_channel
+                    .send(Command::DeletePlaylist(payload.trim().to_string()))
                     .unwrap();
 
                 Some(())
@@ -159,9 +343,9 @@ impl MQTTListener {
                     return Some(());
                 }
 
-                self.last_temp = Some((val, SystemTime::now()));
+                self.last_temp = Some((val, self.clock.now()));
 
-                self.refresh_environment_screen();
+                self.send_reading("temperature", Some(val));
 
                 Some(())
             }
@@ -172,9 +356,9 @@ impl MQTTListener {
                     debug!("rejecting faulty co2 reading");
                     return Some(());
                 }
-                self.last_co2 = Some((val, SystemTime::now()));
+                self.last_co2 = Some((val, self.clock.now()));
 
-                self.refresh_environment_screen();
+                self.send_reading("co2", Some(val as f32));
 
                 Some(())
             }
@@ -182,39 +366,39 @@ impl MQTTListener {
             CHECK_TIME_EXPIRY_TOPIC => {
                 debug!("checking expiry of temp/co2 data");
 
-                let mut refresh = false;
-                if self.last_temp.is_some_and(|x| {
-                    SystemTime::now().duration_since(x.1).unwrap() > ENVIRONMENT_DATA_TIMEOUT
-                }) {
-                    debug!("temp expired");
+                if self
+                    .last_temp
+                    .is_some_and(|x| self.clock.now() - x.1 > ENVIRONMENT_DATA_TIMEOUT)
+                {
+                    warn!("temp expired");
                     self.last_temp = None;
-                    refresh = true;
+                    self.send_reading("temperature", None);
                 }
 
-                if self.last_co2.is_some_and(|x| {
-                    SystemTime::now().duration_since(x.1).unwrap() > ENVIRONMENT_DATA_TIMEOUT
-                }) {
-                    debug!("co2 expired");
+                if self
+                    .last_co2
+                    .is_some_and(|x| self.clock.now() - x.1 > ENVIRONMENT_DATA_TIMEOUT)
+                {
+                    warn!("co2 expired");
                     self.last_co2 = None;
-                    refresh = true;
-                }
-
-                if refresh {
-                    warn!("one or more data points expired");
-                    self.refresh_environment_screen();
+                    self.send_reading("co2", None);
                 }
 
                 Some(())
             }
 
             CATASTROPHE_LEVER_TOPIC => {
-                self.screen_del_channel.send("hate".to_string()).unwrap();
                 if payload == "on" {
-                    self.screen_channel.send(Box::new(HateScreen::new())).ok()?;
+                    self.command_channel
+                        .send(Command::Upsert(Box::new(HateScreen::new())))
+                        .ok()?;
                     client
                         .publish("display/g1/leds", QoS::AtLeastOnce, false, "red")
                         .ok()?;
                 } else {
+                    self.command_channel
+                        .send(Command::Delete("hate".to_string()))
+                        .unwrap();
                     client
                         .publish("display/g1/leds", QoS::AtLeastOnce, false, "rainbow")
                         .ok()?;
@@ -227,17 +411,18 @@ impl MQTTListener {
         }
     }
 
-    /// Delete and replace the environment screen, based on updated info in `self`
-    fn refresh_environment_screen(&mut self) {
-        self.screen_del_channel
-            .send("environment".to_string())
-            .unwrap();
-
-        self.screen_channel
-            .send(Box::new(EnvironmentScreen::new(
-                self.last_temp.map(|x| x.0),
-                self.last_co2.map(|x| x.0),
-            )))
+    /// Send a new reading to the environment screen, or add it with the readings in `self` if it isn't there.
+    fn send_reading(&self, key: &str, value: Option<f32>) {
+        let (temp, co2) = (self.last_temp.map(|x| x.0), self.last_co2.map(|x| x.0));
+        self.command_channel
+            .send(Command::MessageOrAdd(
+                "environment".to_string(),
+                ScreenMessage::Reading {
+                    key: key.to_string(),
+                    value,
+                },
+                Box::new(EnvironmentScreen::new(temp, co2)),
+            ))
             .unwrap();
     }
 }
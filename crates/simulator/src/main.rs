@@ -1,7 +1,17 @@
-use embedded_graphics::{pixelcolor::Rgb888, prelude::*};
-use embedded_graphics_simulator::{OutputSettingsBuilder, SimulatorDisplay, Window};
-use logic::DisplayLogic;
+use embedded_graphics::pixelcolor::Rgb888;
+use embedded_graphics_simulator::{
+    sdl2::Keycode, OutputSettingsBuilder, SimulatorDisplay, SimulatorEvent, Window,
+};
+use log::info;
+use logic::{
+    config::{Config, DEFAULT_CONFIG_FILE},
+    overlay::Toast,
+    Command, DisplayLogic, PANEL_SIZE,
+};
 use std::{
+    env,
+    path::PathBuf,
+    process,
     sync::{
         atomic::{AtomicBool, Ordering},
         mpsc, Arc,
@@ -12,8 +22,6 @@ use std::{
 
 const TARGET_FRAMERATE: u64 = 120;
 
-mod local;
-
 fn main() -> Result<(), core::convert::Infallible> {
     // Boring stuff: Logging, deal with ctrl+c
     env_logger::init();
@@ -26,8 +34,15 @@ fn main() -> Result<(), core::convert::Infallible> {
     })
     .expect("Error setting Ctrl-C handler");
 
+    // Same config as the real thing
+    let config_file = env::var_os("CONFIG_FILE").map_or(DEFAULT_CONFIG_FILE.into(), PathBuf::from);
+    let config = Config::load_or_default(&config_file).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        process::exit(1);
+    });
+
     // Setup output window
-    let mut display = SimulatorDisplay::<Rgb888>::new(Size::new(192, 32));
+    let mut display = SimulatorDisplay::<Rgb888>::new(PANEL_SIZE);
     let output_settings = OutputSettingsBuilder::new()
         .pixel_spacing(2)
         .scale(4)
@@ -35,13 +50,16 @@ fn main() -> Result<(), core::convert::Infallible> {
     let mut window = Window::new("LED display simulator", &output_settings);
 
     // Wire up display logic
-    let (_, recv) = mpsc::channel();
-    let (_, recv_del) = mpsc::channel();
-    let _sleep = Arc::new(AtomicBool::new(false));
-    let mut display_logic = DisplayLogic::new(recv, recv_del, _sleep);
+    let (send, recv) = mpsc::channel();
+    let sleeping = Arc::new(AtomicBool::new(false));
+    let mut display_logic = DisplayLogic::new(recv, sleeping.clone());
+    let (send_state, recv_state) = mpsc::channel();
+    let mut brightness = u8::MAX;
+    let mut paused = false;
 
-    // Copy local.rs.tmpl to local.rs
-    local::add_screens(&mut display_logic);
+    config.apply(&mut display_logic);
+    config.add_screens(&mut display_logic);
+    config.add_scripts(&mut display_logic);
 
     loop {
         if !keep_going.load(Ordering::Relaxed) {
@@ -51,6 +69,49 @@ fn main() -> Result<(), core::convert::Infallible> {
         let frame_start = Instant::now();
         display_logic.draw(&mut display)?;
         window.update(&display);
+
+        // Keyboard controls
+        for event in window.events() {
+            let command = match event {
+                SimulatorEvent::Quit => {
+                    keep_going.store(false, Ordering::Relaxed);
+                    continue;
+                }
+                SimulatorEvent::KeyDown { keycode, .. } => match keycode {
+                    Keycode::RIGHT => Command::Next,
+                    Keycode::LEFT => Command::Previous,
+                    Keycode::SPACE => {
+                        paused = !paused;
+                        if paused {
+                            Command::PauseRotation
+                        } else {
+                            Command::ResumeRotation
+                        }
+                    }
+                    Keycode::UP | Keycode::DOWN => {
+                        brightness = if keycode == Keycode::UP {
+                            brightness.saturating_add(32)
+                        } else {
+                            brightness.saturating_sub(32)
+                        };
+                        Command::SetBrightness(brightness)
+                    }
+                    Keycode::C => Command::ClearAll,
+                    Keycode::N => Command::Notify(Toast::new("Door opened")),
+                    Keycode::S => Command::QueryState(send_state.clone()),
+                    Keycode::Z => {
+                        sleeping.fetch_xor(true, Ordering::Relaxed);
+                        continue;
+                    }
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            send.send(command).unwrap();
+        }
+        for state in recv_state.try_iter() {
+            info!("{:#?}", state);
+        }
         sleep(frame_start + Duration::from_millis(1000 / TARGET_FRAMERATE) - Instant::now());
     }
 